@@ -71,10 +71,10 @@ impl ABI {
         }
     }
 
-    pub fn mangle_name(&self, name: &str, argtypes: &Type, funcdefs: i32) -> String {
+    pub fn mangle_name(&self, path: &Vec<String>, name: &str, argtypes: &Type, funcdefs: i32) -> String {
         match *self {
             ABI::Molten |
-            ABI::MoltenFunc => molten_mangle_name(name, argtypes, funcdefs),
+            ABI::MoltenFunc => molten_mangle_name(path, name, argtypes, funcdefs),
             // TODO C++, etc
             _ => String::from(name),
         }
@@ -91,11 +91,13 @@ impl fmt::Display for ABI {
 }
 
 
-pub fn molten_mangle_name(name: &str, argtypes: &Type, funcdefs: i32) -> String {
+pub fn molten_mangle_name(path: &Vec<String>, name: &str, argtypes: &Type, funcdefs: i32) -> String {
     if funcdefs >= 2 {
-        format!("_Z{}{}{}", name.len(), name, molten_mangle_type(argtypes))
+        // Each module block that the function is inside of is added before its name
+        let path = path.iter().map(|module| format!("M{}{}", module.len(), module)).collect::<String>();
+        format!("_Z{}{}{}{}", path, name.len(), name, molten_mangle_type(argtypes))
     } else {
-        String::from(name)
+        path.iter().map(|module| format!("{}_", module)).collect::<String>() + name
     }
 }
 
@@ -16,11 +16,13 @@ pub struct Pos {
     pub column: usize,
     pub line: u32,
     pub filenum: u16,
+    pub expansion: Option<R<Pos>>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Visibility {
     Private,
+    Protected,
     Public,
 }
 
@@ -36,6 +38,13 @@ pub enum AssignType {
     Update,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportKind {
+    All,
+    Alias(Ident),
+    Names(Vec<Ident>),
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Literal {
     Unit,
@@ -44,6 +53,7 @@ pub enum Literal {
     Integer(isize),
     Real(f64),
     String(String),
+    ByteString(Pos, Vec<u8>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -70,7 +80,9 @@ pub struct ClassSpec {
 #[derive(Clone, Debug, PartialEq)]
 pub struct MatchCase {
     pub id: NodeID,
+    pub pos: Pos,
     pub pat: Pattern,
+    pub guard: Option<AST>,
     pub body: AST,
 }
 
@@ -85,6 +97,16 @@ pub enum Pattern {
     EnumArgs(NodeID, R<Pattern>, Vec<Pattern>),
     Tuple(NodeID, Vec<Pattern>),
     Record(NodeID, Vec<(Ident, Pattern)>),
+    Or(NodeID, Vec<Pattern>),
+    Range(NodeID, AST, AST, bool, NodeID),
+    List(NodeID, Vec<Pattern>, Option<(usize, R<Pattern>)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub pos: Pos,
+    pub ident: Ident,
+    pub args: Vec<AST>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -99,6 +121,7 @@ pub struct EnumVariant {
 #[derive(Clone, Debug, PartialEq)]
 pub enum AST {
     Literal(NodeID, Literal),
+    Interpolate(NodeID, Pos, Vec<AST>),
     Nil(NodeID),
     PtrCast(NodeID, Type, R<AST>),
     Ref(NodeID, Pos, R<AST>),
@@ -116,7 +139,8 @@ pub enum AST {
     Accessor(NodeID, Pos, R<AST>, Ident, NodeID),
 
     Block(NodeID, Pos, Vec<AST>),
-    Invoke(NodeID, Pos, R<AST>, Vec<AST>),
+    Invoke(NodeID, Pos, R<AST>, Vec<Type>, Vec<AST>),
+    Keyword(NodeID, Pos, Ident, R<AST>),
     //Prefix(NodeID, Pos, Ident, R<AST>),
     //Infix(NodeID, Pos, Ident, R<AST>, R<AST>),
 
@@ -126,17 +150,25 @@ pub enum AST {
     Try(NodeID, Pos, R<AST>, Vec<MatchCase>),
     Match(NodeID, Pos, R<AST>, Vec<MatchCase>),
     For(NodeID, Pos, Ident, R<AST>, R<AST>),
+    Range(NodeID, Pos, R<AST>, R<AST>, Option<R<AST>>, bool),
     While(NodeID, Pos, R<AST>, R<AST>),
 
-    Declare(NodeID, Pos, Visibility, Ident, Type),
-    Function(NodeID, Pos, Visibility, Option<Ident>, Vec<Argument>, Option<Type>, R<AST>, ABI),
+    Declare(NodeID, Pos, Visibility, Ident, Vec<Type>, Vec<Argument>, Type),
+    Function(NodeID, Pos, Visibility, Option<Ident>, Vec<Type>, Vec<Argument>, Option<Type>, R<AST>, ABI),
     New(NodeID, Pos, ClassSpec),
     Class(NodeID, Pos, ClassSpec, Option<ClassSpec>, Vec<AST>),
     TypeAlias(NodeID, Pos, ClassSpec, Type),
-    Enum(NodeID, Pos, ClassSpec, Vec<EnumVariant>),
-
-    Import(NodeID, Pos, Ident, Vec<AST>),
-    Definition(NodeID, Pos, Mutability, Ident, Option<Type>, R<AST>),
+    Enum(NodeID, Pos, ClassSpec, Vec<EnumVariant>, Vec<AST>),
+    Trait(NodeID, Pos, Ident, Vec<AST>),
+    Impl(NodeID, Pos, Ident, Type, Vec<AST>),
+
+    Import(NodeID, Pos, Ident, ImportKind, Vec<AST>),
+    Module(NodeID, Pos, Ident, Vec<AST>),
+    Attributes(NodeID, Pos, Vec<Attribute>, R<AST>),
+    Macro(NodeID, Pos, Ident, Vec<Ident>, String, R<AST>),
+    Definition(NodeID, Pos, Visibility, Mutability, Ident, Option<Type>, Option<R<AST>>),
+    Const(NodeID, Pos, Ident, Option<Type>, R<AST>),
+    Destructure(NodeID, Pos, R<Pattern>, R<AST>),
     Assignment(NodeID, Pos, R<AST>, R<AST>, AssignType),
 }
 
@@ -149,11 +181,12 @@ impl Pos {
             column: span.get_utf8_column(),
             line: span.line,
             filenum: 0,
+            expansion: None,
         }
     }
 
     pub fn empty() -> Pos {
-        Pos { offset: 0, column: 0, line: 0, filenum: 0 }
+        Pos { offset: 0, column: 0, line: 0, filenum: 0, expansion: None }
     }
 
     pub fn exerpt(&self, text: &[u8]) -> String {
@@ -226,16 +259,45 @@ impl ClassSpec {
 }
 
 impl MatchCase {
-    pub fn new(pat: Pattern, body: AST) -> Self {
+    pub fn new(pos: Pos, pat: Pattern, guard: Option<AST>, body: AST) -> Self {
         Self {
             id: NodeID::generate(),
+            pos: pos,
             pat: pat,
+            guard: guard,
             body: body,
         }
     }
 }
 
 impl Pattern {
+    pub fn is_simple_binding(&self) -> bool {
+        match *self {
+            Pattern::Binding(_, _) => true,
+            Pattern::Annotation(_, _, ref pat) => pat.is_simple_binding(),
+            _ => false,
+        }
+    }
+
+    pub fn get_bindings(&self) -> Vec<(NodeID, String)> {
+        match *self {
+            Pattern::Binding(id, ref ident) => vec!((id, ident.name.clone())),
+            Pattern::Annotation(_, _, ref pat) => pat.get_bindings(),
+            Pattern::EnumArgs(_, _, ref args) |
+            Pattern::Tuple(_, ref args) => args.iter().flat_map(|arg| arg.get_bindings()).collect(),
+            Pattern::Record(_, ref items) => items.iter().flat_map(|(_, item)| item.get_bindings()).collect(),
+            Pattern::Or(_, ref alts) => alts[0].get_bindings(),
+            Pattern::List(_, ref items, ref rest) => {
+                let mut bindings: Vec<(NodeID, String)> = items.iter().flat_map(|item| item.get_bindings()).collect();
+                if let Some((_, ref rest)) = rest {
+                    bindings.extend(rest.get_bindings());
+                }
+                bindings
+            },
+            _ => vec!(),
+        }
+    }
+
     pub fn get_id(&self) -> NodeID {
         match *self {
             Pattern::Literal(id, _) |
@@ -243,12 +305,34 @@ impl Pattern {
             Pattern::Annotation(id, _, _) |
             Pattern::Identifier(id, _) |
             Pattern::Resolve(id, _, _, _) |
-            Pattern::EnumArgs(id, _, _) => id,
+            Pattern::EnumArgs(id, _, _) |
+            Pattern::Tuple(id, _) |
+            Pattern::Record(id, _) |
+            Pattern::Or(id, _) |
+            Pattern::Range(id, _, _, _, _) |
+            Pattern::List(id, _, _) => id,
             _ => UniqueID(0),
         }
     }
 }
 
+impl Attribute {
+    pub fn new(pos: Pos, ident: Ident, args: Vec<AST>) -> Self {
+        Self {
+            pos: pos,
+            ident: ident,
+            args: args,
+        }
+    }
+
+    pub fn get_string_arg(&self, index: usize) -> Option<String> {
+        match self.args.get(index) {
+            Some(AST::Literal(_, Literal::String(ref s))) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
 impl EnumVariant {
     pub fn new(pos: Pos, ident: Ident, ttype: Option<Type>) -> Self {
         Self {
@@ -264,6 +348,7 @@ impl EnumVariant {
 impl AST {
     pub fn get_pos(&self) -> Pos {
         match *self {
+            AST::Interpolate(_, ref pos, _) |
             AST::Ref(_, ref pos, _) |
             AST::Deref(_, ref pos, _) |
             AST::List(_, ref pos, _) |
@@ -274,7 +359,8 @@ impl AST {
             AST::Index(_, ref pos, _, _) |
             AST::Resolver(_, ref pos, _, _, _) |
             AST::Accessor(_, ref pos, _, _, _) |
-            AST::Invoke(_, ref pos, _, _) |
+            AST::Invoke(_, ref pos, _, _, _) |
+            AST::Keyword(_, ref pos, _, _) |
             AST::SideEffect(_, ref pos, _, _) |
             AST::Block(_, ref pos, _) |
             AST::If(_, ref pos, _, _, _) |
@@ -282,16 +368,24 @@ impl AST {
             AST::Try(_, ref pos, _, _) |
             AST::Match(_, ref pos, _, _) |
             AST::For(_, ref pos, _, _, _) |
-            AST::Declare(_, ref pos, _, _, _) |
-            AST::Function(_, ref pos, _, _, _, _, _, _) |
+            AST::Range(_, ref pos, _, _, _, _) |
+            AST::Declare(_, ref pos, _, _, _, _, _) |
+            AST::Function(_, ref pos, _, _, _, _, _, _, _) |
             AST::New(_, ref pos, _) |
             AST::Class(_, ref pos, _, _, _) |
-            AST::Import(_, ref pos, _, _) |
-            AST::Definition(_, ref pos, _, _, _, _) |
+            AST::Import(_, ref pos, _, _, _) |
+            AST::Module(_, ref pos, _, _) |
+            AST::Attributes(_, ref pos, _, _) |
+            AST::Macro(_, ref pos, _, _, _, _) |
+            AST::Definition(_, ref pos, _, _, _, _, _) |
+            AST::Const(_, ref pos, _, _, _) |
+            AST::Destructure(_, ref pos, _, _) |
             AST::Assignment(_, ref pos, _, _, _) |
             AST::While(_, ref pos, _, _) |
             AST::TypeAlias(_, ref pos, _, _) |
-            AST::Enum(_, ref pos, _, _) => { pos.clone() }
+            AST::Enum(_, ref pos, _, _, _) |
+            AST::Trait(_, ref pos, _, _) |
+            AST::Impl(_, ref pos, _, _, _) => { pos.clone() }
             _ => Pos::empty(),
         }
     }
@@ -299,6 +393,7 @@ impl AST {
     pub fn get_id(&self) -> NodeID {
         match *self {
             AST::Literal(ref id, _) |
+            AST::Interpolate(ref id, _, _) |
             AST::Nil(ref id) |
             AST::PtrCast(ref id, _, _) |
             AST::Ref(ref id, _, _) |
@@ -311,7 +406,8 @@ impl AST {
             AST::Index(ref id, _, _, _) |
             AST::Resolver(ref id, _, _, _, _) |
             AST::Accessor(ref id, _, _, _, _) |
-            AST::Invoke(ref id, _, _, _) |
+            AST::Invoke(ref id, _, _, _, _) |
+            AST::Keyword(ref id, _, _, _) |
             AST::SideEffect(ref id, _, _, _) |
             AST::Block(ref id, _, _) |
             AST::If(ref id, _, _, _, _) |
@@ -319,20 +415,63 @@ impl AST {
             AST::Try(ref id, _, _, _) |
             AST::Match(ref id, _, _, _) |
             AST::For(ref id, _, _, _, _) |
-            AST::Declare(ref id, _, _, _, _) |
-            AST::Function(ref id, _, _, _, _, _, _, _) |
+            AST::Range(ref id, _, _, _, _, _) |
+            AST::Declare(ref id, _, _, _, _, _, _) |
+            AST::Function(ref id, _, _, _, _, _, _, _, _) |
             AST::New(ref id, _, _) |
             AST::Class(ref id, _, _, _, _) |
-            AST::Import(ref id, _, _, _) |
-            AST::Definition(ref id, _, _, _, _, _) |
+            AST::Import(ref id, _, _, _, _) |
+            AST::Module(ref id, _, _, _) |
+            AST::Attributes(ref id, _, _, _) |
+            AST::Macro(ref id, _, _, _, _, _) |
+            AST::Definition(ref id, _, _, _, _, _, _) |
+            AST::Const(ref id, _, _, _, _) |
+            AST::Destructure(ref id, _, _, _) |
             AST::Assignment(ref id, _, _, _, _) |
             AST::While(ref id, _, _, _) |
-            AST::Enum(ref id, _, _, _) |
+            AST::Enum(ref id, _, _, _, _) |
+            AST::Trait(ref id, _, _, _) |
+            AST::Impl(ref id, _, _, _, _) |
             AST::TypeAlias(ref id, _, _, _) => { *id }
             _ => UniqueID(0),
         }
     }
 
+    pub fn is_keyword(&self) -> bool {
+        match *self {
+            AST::Keyword(_, _, _, _) => true,
+            _ => false,
+        }
+    }
+
+    // Returns whether it's an equality test, the value being tested, and the nil node, for comparisons like `x != nil`
+    pub fn get_nil_comparison(&self) -> Option<(bool, &AST, &AST)> {
+        match *self {
+            AST::Invoke(_, _, ref fexpr, _, ref args) if args.len() == 2 => {
+                let equal = match **fexpr {
+                    AST::Identifier(_, _, ref ident) if ident.as_str() == "==" => true,
+                    AST::Identifier(_, _, ref ident) if ident.as_str() == "!=" => false,
+                    _ => return None,
+                };
+                match (&args[0], &args[1]) {
+                    (value, nil @ AST::Nil(_)) |
+                    (nil @ AST::Nil(_), value) => Some((equal, value, nil)),
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+
+    pub fn with_visibility(self, vis: Visibility) -> AST {
+        match self {
+            AST::Definition(id, pos, _, mutable, ident, ttype, value) => AST::Definition(id, pos, vis, mutable, ident, ttype, value),
+            AST::Declare(id, pos, _, ident, params, args, ttype) => AST::Declare(id, pos, vis, ident, params, args, ttype),
+            AST::Function(id, pos, _, ident, params, args, rtype, body, abi) => AST::Function(id, pos, vis, ident, params, args, rtype, body, abi),
+            node => node,
+        }
+    }
+
     pub fn make_ptr_cast(ttype: Type, expr: AST) -> AST {
         AST::PtrCast(NodeID::generate(), ttype, r(expr))
     }
@@ -349,6 +488,10 @@ impl AST {
         AST::Literal(NodeID::generate(), literal)
     }
 
+    pub fn make_interpolate(pos: Pos, pieces: Vec<AST>) -> AST {
+        AST::Interpolate(NodeID::generate(), pos, pieces)
+    }
+
     pub fn make_nil() -> AST {
         AST::Nil(NodeID::generate())
     }
@@ -390,7 +533,15 @@ impl AST {
     }
 
     pub fn make_invoke(pos: Pos, fexpr: AST, args: Vec<AST>) -> AST {
-        AST::Invoke(NodeID::generate(), pos, r(fexpr), args)
+        AST::Invoke(NodeID::generate(), pos, r(fexpr), vec!(), args)
+    }
+
+    pub fn make_invoke_with_types(pos: Pos, fexpr: AST, types: Vec<Type>, args: Vec<AST>) -> AST {
+        AST::Invoke(NodeID::generate(), pos, r(fexpr), types, args)
+    }
+
+    pub fn make_keyword(pos: Pos, ident: Ident, expr: AST) -> AST {
+        AST::Keyword(NodeID::generate(), pos, ident, r(expr))
     }
 
     pub fn make_side_effect(pos: Pos, ident: Ident, args: Vec<AST>) -> AST {
@@ -421,12 +572,16 @@ impl AST {
         AST::For(NodeID::generate(), pos, ident, r(list), r(body))
     }
 
-    pub fn make_decl(pos: Pos, vis: Visibility, ident: Ident, ttype: Type) -> AST {
-        AST::Declare(NodeID::generate(), pos, vis, ident, ttype)
+    pub fn make_range(pos: Pos, start: AST, end: AST, step: Option<AST>, inclusive: bool) -> AST {
+        AST::Range(NodeID::generate(), pos, r(start), r(end), step.map(|step| r(step)), inclusive)
     }
 
-    pub fn make_func(pos: Pos, vis: Visibility, ident: Option<Ident>, args: Vec<Argument>, rtype: Option<Type>, body: AST, abi: ABI) -> AST {
-        AST::Function(NodeID::generate(), pos, vis, ident, args, rtype, r(body), abi)
+    pub fn make_decl(pos: Pos, vis: Visibility, ident: Ident, params: Vec<Type>, args: Vec<Argument>, ttype: Type) -> AST {
+        AST::Declare(NodeID::generate(), pos, vis, ident, params, args, ttype)
+    }
+
+    pub fn make_func(pos: Pos, vis: Visibility, ident: Option<Ident>, params: Vec<Type>, args: Vec<Argument>, rtype: Option<Type>, body: AST, abi: ABI) -> AST {
+        AST::Function(NodeID::generate(), pos, vis, ident, params, args, rtype, r(body), abi)
     }
 
     pub fn make_new(pos: Pos, classspec: ClassSpec) -> AST {
@@ -437,12 +592,32 @@ impl AST {
         AST::Class(NodeID::generate(), pos, classspec, parentspec, body)
     }
 
-    pub fn make_import(pos: Pos, ident: Ident, decls: Vec<AST>) -> AST {
-        AST::Import(NodeID::generate(), pos, ident, decls)
+    pub fn make_import(pos: Pos, ident: Ident, kind: ImportKind, decls: Vec<AST>) -> AST {
+        AST::Import(NodeID::generate(), pos, ident, kind, decls)
+    }
+
+    pub fn make_module(pos: Pos, ident: Ident, body: Vec<AST>) -> AST {
+        AST::Module(NodeID::generate(), pos, ident, body)
+    }
+
+    pub fn make_attributes(pos: Pos, attrs: Vec<Attribute>, node: AST) -> AST {
+        AST::Attributes(NodeID::generate(), pos, attrs, r(node))
+    }
+
+    pub fn make_macro(pos: Pos, ident: Ident, args: Vec<Ident>, text: String, body: AST) -> AST {
+        AST::Macro(NodeID::generate(), pos, ident, args, text, r(body))
     }
 
     pub fn make_def(pos: Pos, mutable: Mutability, ident: Ident, ttype: Option<Type>, value: AST) -> AST {
-        AST::Definition(NodeID::generate(), pos, mutable, ident, ttype, r(value))
+        AST::Definition(NodeID::generate(), pos, Visibility::Private, mutable, ident, ttype, Some(r(value)))
+    }
+
+    pub fn make_const(pos: Pos, ident: Ident, ttype: Option<Type>, value: AST) -> AST {
+        AST::Const(NodeID::generate(), pos, ident, ttype, r(value))
+    }
+
+    pub fn make_destructure(pos: Pos, pat: Pattern, value: AST) -> AST {
+        AST::Destructure(NodeID::generate(), pos, r(pat), r(value))
     }
 
     pub fn make_assign(pos: Pos, left: AST, right: AST, ty: AssignType) -> AST {
@@ -457,8 +632,16 @@ impl AST {
         AST::TypeAlias(NodeID::generate(), pos, classspec, ttype)
     }
 
-    pub fn make_type_enum(pos: Pos, classspec: ClassSpec, variants: Vec<EnumVariant>) -> AST {
-        AST::Enum(NodeID::generate(), pos, classspec, variants)
+    pub fn make_type_enum(pos: Pos, classspec: ClassSpec, variants: Vec<EnumVariant>, body: Vec<AST>) -> AST {
+        AST::Enum(NodeID::generate(), pos, classspec, variants, body)
+    }
+
+    pub fn make_trait(pos: Pos, ident: Ident, body: Vec<AST>) -> AST {
+        AST::Trait(NodeID::generate(), pos, ident, body)
+    }
+
+    pub fn make_impl(pos: Pos, ident: Ident, ttype: Type, body: Vec<AST>) -> AST {
+        AST::Impl(NodeID::generate(), pos, ident, ttype, body)
     }
 
     /*
@@ -4,13 +4,17 @@ use std::cell::RefCell;
 use abi::ABI;
 use types::Type;
 use session::{ Session, Error };
-use scope::{ ScopeRef, Context };
-use ast::{ NodeID, Mutability, ClassSpec, Pattern, AST };
+use scope::{ Scope, ScopeRef, Context };
+use ast::{ NodeID, Mutability, Argument, ClassSpec, ImportKind, Pattern, AST };
 use misc::{ UniqueID, r };
 
+use defs::Def;
 use defs::enums::EnumDef;
+use defs::modules::ModuleDef;
+use defs::constants::ConstDef;
 use defs::classes::ClassDef;
-use defs::functions::AnyFunc;
+use defs::functions::{ AnyFunc, FuncDef };
+use defs::traits::{ TraitDef, ImplDef };
 use defs::types::TypeAliasDef;
 use defs::variables::{ AnyVar, VarDef, ArgDef };
 
@@ -25,15 +29,23 @@ pub enum CodeContext {
 pub struct NameBinder<'sess> {
     pub session: &'sess Session,
     pub context: RefCell<Vec<CodeContext>>,
+    pub blocks: RefCell<Vec<ScopeRef>>,
+    pub default_scope: RefCell<Option<ScopeRef>>,
 }
 
 
 impl<'sess> NameBinder<'sess> {
-    pub fn bind_names(session: &'sess Session, scope: ScopeRef, code: &Vec<AST>) {
-        let namebinder = NameBinder {
+    pub fn new(session: &'sess Session) -> Self {
+        NameBinder {
             session: session,
             context: RefCell::new(vec!()),
-        };
+            blocks: RefCell::new(vec!()),
+            default_scope: RefCell::new(None),
+        }
+    }
+
+    pub fn bind_names(session: &'sess Session, scope: ScopeRef, code: &Vec<AST>) {
+        let namebinder = NameBinder::new(session);
 
         let ttype = namebinder.bind_names_vec(scope, code);
         if session.errors.get() > 0 {
@@ -42,6 +54,11 @@ impl<'sess> NameBinder<'sess> {
         debug!("POST-BINDING: {:?}", code);
     }
 
+    /// Bind the copy of a default value that's made for a call, in the scope of the function's definition
+    pub fn bind_default_copy(session: &'sess Session, scope: ScopeRef, node: &AST) -> Result<(), Error> {
+        NameBinder::new(session).bind_names_node_or_error(scope, node)
+    }
+
 
     pub fn bind_names_vec(&self, scope: ScopeRef, code: &Vec<AST>) {
         for node in code {
@@ -59,57 +76,116 @@ impl<'sess> NameBinder<'sess> {
     #[must_use]
     fn bind_names_node_or_error(&self, scope: ScopeRef, node: &AST) -> Result<(), Error> {
         match *node {
-            AST::Function(ref id, _, ref vis, ref ident, ref args, ref ret, ref body, ref abi) => {
+            AST::Function(ref id, _, ref vis, ref ident, ref params, ref args, ref ret, ref body, ref abi) => {
                 let fscope = self.session.map.add(*id, Some(scope.clone()));
                 fscope.set_basename(ident.as_ref().map_or(format!("anon{}", id), |ident| ident.name.clone()));
 
+                // Declared type params are always new to the function, even if the name is used by an outer scope
+                let mut params = params.clone();
+                for param in params.iter_mut() {
+                    bind_type_names(self.session, fscope.clone(), Some(param), true)?;
+                }
+
                 // Check for typevars in the type params
                 let mut argtypes = vec!();
                 for ref arg in args.iter() {
                     let mut ttype = arg.ttype.clone();
                     bind_type_names(self.session, fscope.clone(), ttype.as_mut(), false)?;
+                    self.bind_trait_bound(arg, ttype.as_ref());
                     // TODO this is assumed to be always immutable, but maybe shouldn't be
                     ArgDef::define(self.session, fscope.clone(), arg.id, Mutability::Immutable, &arg.ident.name, ttype.clone())?;
                     argtypes.push(ttype.unwrap_or_else(|| scope.new_typevar(self.session, false)));
+                    // Default values are evaluated at the call site, so they can't refer to other arguments
+                    self.bind_default(scope.clone(), arg);
                 }
                 let mut ret = ret.clone();
                 bind_type_names(self.session, fscope.clone(), ret.as_mut(), false)?;
+                let fname = ident.as_ref().map_or(String::from("anonymous function"), |ident| ident.name.clone());
+                self.check_declared_typevars(scope.clone(), &fname, &params, args.iter().filter_map(|arg| arg.ttype.as_ref()).chain(ret.iter()))?;
+                self.session.set_type_params(*id, params);
 
                 // Build type according to the definition, using typevars for missing types
                 let nftype = Type::Function(r(Type::Tuple(argtypes)), r(ret.unwrap_or_else(|| scope.new_typevar(self.session, false))), *abi);
 
                 // Define the function variable and it's arguments variables
-                AnyFunc::define(self.session, scope.clone(), *id, *vis, &ident.as_ref().map(|ref ident| String::from(ident.as_str())), *abi, Some(nftype))?;
+                AnyFunc::define(self.session, scope.clone(), *id, *vis, &ident.as_ref().map(|ref ident| String::from(ident.as_str())), *abi, args.clone(), Some(nftype))?;
 
                 self.bind_names_node(fscope, body)
             },
 
-            AST::Invoke(_, _, ref fexpr, ref args) => {
+            AST::Invoke(ref id, _, ref fexpr, ref types, ref args) => {
+                if types.len() > 0 {
+                    let mut types = types.clone();
+                    for ttype in types.iter_mut() {
+                        bind_type_names(self.session, scope.clone(), Some(ttype), false)?;
+                    }
+                    self.session.set_type_params(*id, types);
+                }
                 self.bind_names_node(scope.clone(), fexpr);
                 self.bind_names_vec(scope, args);
             },
 
-            AST::Definition(ref id, _, ref mutable, ref ident, ref ttype, ref code) => {
+            AST::Definition(ref id, _, ref vis, ref mutable, ref ident, ref ttype, ref code) => {
+                let mut ttype = ttype.clone();
+                bind_type_names(self.session, scope.clone(), ttype.as_mut(), false)?;
+                AnyVar::define(self.session, scope.clone(), *id, *vis, *mutable, &ident.name, ttype)?;
+                if let Some(ref code) = *code {
+                    self.bind_names_node(scope, code);
+                }
+            },
+
+            AST::Const(ref id, _, ref ident, ref ttype, ref code) => {
                 let mut ttype = ttype.clone();
                 bind_type_names(self.session, scope.clone(), ttype.as_mut(), false)?;
-                AnyVar::define(self.session, scope.clone(), *id, *mutable, &ident.name, ttype)?;
+                ConstDef::define(self.session, scope.clone(), *id, &ident.name, ttype)?;
                 self.bind_names_node(scope, code);
             },
 
-            AST::Declare(ref id, _, ref vis, ref ident, ref ttype) => {
+            AST::Destructure(_, _, ref pat, ref code) => {
+                self.bind_names_node(scope.clone(), code);
+                self.bind_names_pattern(scope, pat)?;
+            },
+
+            AST::Declare(ref id, _, ref vis, ref ident, ref params, ref args, ref ttype) => {
                 let mut ttype = ttype.clone();
                 bind_type_names(self.session, scope.clone(), Some(&mut ttype), false)?;
+                self.check_declared_typevars(scope.clone(), &ident.name, params, Some(&ttype).into_iter())?;
+                let mut params = params.clone();
+                for param in params.iter_mut() {
+                    bind_type_names(self.session, scope.clone(), Some(param), false)?;
+                }
+                self.session.set_type_params(*id, params);
+                for (arg, atype) in args.iter().zip(ttype.get_argtypes().map(|atypes| atypes.as_vec()).unwrap_or(vec!())) {
+                    self.bind_trait_bound(arg, Some(&atype));
+                    self.bind_default(scope.clone(), arg);
+                }
                 let abi = ttype.get_abi().unwrap_or(ABI::Molten);
-                AnyFunc::define(self.session, scope.clone(), *id, *vis, &Some(ident.name.clone()), abi, Some(ttype))?;
+                AnyFunc::define(self.session, scope.clone(), *id, *vis, &Some(ident.name.clone()), abi, args.clone(), Some(ttype))?;
+            },
+
+            AST::Keyword(_, _, _, ref expr) => {
+                self.bind_names_node(scope, expr);
             },
 
-            AST::Identifier(ref id, _, ref ident) => {
+            AST::Identifier(ref id, ref pos, ref ident) => {
                 if self.session.get_ref(*id).is_err() {
                     // TODO you must check to make sure if we are accessing a variable or argument, that it is either local or we are in a closure...
                     //      (the latter being more difficult to figure out; we need to some kind of context value)
-                    match scope.get_var_def(&ident.name) {
-                        Some(defid) => self.session.set_ref(*id, defid),
-                        None => return Err(Error::new(format!("NameError: undefined identifier {:?}", ident.name)))
+                    let lookup = if self.session.is_macro_ref(*id) { self.session.map.get_global() } else { scope.clone() };
+                    match lookup.get_var_def(&ident.name) {
+                        Some(defid) => {
+                            self.check_default_name(lookup, &ident.name)?;
+                            self.session.set_ref(*id, defid)
+                        },
+                        None => {
+                            if let Some(module) = self.session.get_private_import(&ident.name) {
+                                return Err(Error::new(format!("VisibilityError: {:?} is private to module {:?}, and can't be used outside of it", ident.name, module)));
+                            }
+                            if self.is_defined_in_block(scope.clone(), &ident.name) {
+                                self.session.print_warning(Error::new_pos(pos, format!("Warning: {:?} is defined inside a block, and blocks now have their own scope, so it must be defined outside of the block to be used here", ident.name)));
+                            }
+                            return Err(Error::new(format!("NameError: undefined identifier {:?}", ident.name)))
+                        },
                     }
                 }
             },
@@ -132,6 +208,9 @@ impl<'sess> NameBinder<'sess> {
                     let lscope = self.session.map.add(case.id, Some(scope.clone()));
                     lscope.set_context(Context::Block);
                     self.bind_names_pattern(lscope.clone(), &case.pat)?;
+                    if let Some(ref guard) = case.guard {
+                        self.bind_names_node(lscope.clone(), guard);
+                    }
                     self.bind_names_node(lscope.clone(), &case.body);
                 }
             },
@@ -148,8 +227,14 @@ impl<'sess> NameBinder<'sess> {
             AST::Ref(_, _, ref code) |
             AST::Deref(_, _, ref code) => { self.bind_names_node(scope, code); },
 
-            AST::Tuple(_, _, ref code) |
-            AST::Block(_, _, ref code) => { self.bind_names_vec(scope, code); },
+            AST::Tuple(_, _, ref code) => { self.bind_names_vec(scope, code); },
+
+            AST::Block(ref id, _, ref code) => {
+                let bscope = self.session.map.add(*id, Some(scope.clone()));
+                bscope.set_context(Context::Block);
+                self.bind_names_vec(bscope.clone(), code);
+                self.blocks.borrow_mut().push(bscope);
+            },
 
             AST::Record(_, _, ref items) => {
                 for &(_, ref expr) in items {
@@ -219,28 +304,74 @@ impl<'sess> NameBinder<'sess> {
                 //self.session.set_type(*id, ttype.clone());
             },
 
-            AST::Enum(ref id, _, ref classspec, ref variants) => {
+            AST::Enum(ref id, _, ref classspec, ref variants, ref body) => {
+                // The type params are only visible to the enum's own variants and functions
+                let tscope = self.session.map.get_or_add(*id, Some(scope.clone()));
                 let mut classspec = classspec.clone();
-                bind_classspec_type_names(self.session, scope.clone(), &mut classspec, true)?;
+                bind_classspec_type_names(self.session, tscope.clone(), &mut classspec, true)?;
                 let deftype = Type::Object(classspec.ident.name, *id, classspec.types);
                 let enumdef = EnumDef::define(self.session, scope.clone(), *id, deftype)?;
 
                 for variant in variants.iter() {
                     // TODO this still requires mut
                     let mut variant = variant.clone();
-                    bind_type_names(self.session, scope.clone(), variant.ttype.as_mut(), false)?;
-                    check_recursive_type(&variant.ttype.as_ref(), *id)?;
-                    enumdef.add_variant(self.session, scope.clone(), variant)?;
+                    bind_type_names(self.session, tscope.clone(), variant.ttype.as_mut(), false)?;
+                    enumdef.add_variant(self.session, tscope.clone(), variant)?;
+                }
+
+                self.bind_names_vec(tscope, body);
+            },
+
+            AST::Trait(ref id, _, ref ident, ref body) => {
+                let traitdef = TraitDef::define(self.session, scope.clone(), *id, &ident.name)?;
+                for node in body {
+                    if let AST::Declare(ref did, _, ref vis, ref dident, _, ref args, ref ttype) = *node {
+                        if traitdef.get_method(&dident.name).is_some() {
+                            return Err(Error::new(format!("TraitError: the method {:?} is declared more than once in trait {:?}", dident.name, ident.name)));
+                        }
+                        let mut ttype = ttype.clone();
+                        bind_type_names(self.session, traitdef.vars.clone(), Some(&mut ttype), false)?;
+                        FuncDef::define(self.session, traitdef.vars.clone(), *did, *vis, &Some(dident.name.clone()), args.clone(), Some(ttype))?;
+                        traitdef.add_method(&dident.name, *did);
+                    }
                 }
             },
 
-            AST::Resolver(_, _, ref left, _, ref oid) => {
+            AST::Impl(ref id, _, ref ident, ref ttype, ref body) => {
+                let traitdef = match scope.get_type_def(&ident.name).map(|defid| self.session.get_def(defid)) {
+                    Some(Ok(Def::Trait(traitdef))) => traitdef,
+                    _ => return Err(Error::new(format!("NameError: undefined trait {:?}", ident.name))),
+                };
+
+                // Any typevars in the type are only visible to the impl's methods
+                let tscope = self.session.map.get_or_add(*id, Some(scope.clone()));
+                let mut ttype = ttype.clone();
+                bind_type_names(self.session, tscope.clone(), Some(&mut ttype), false)?;
+                ImplDef::define(self.session, scope.clone(), *id, traitdef, ttype)?;
+
+                self.bind_names_vec(tscope, body);
+            },
+
+            AST::Resolver(_, _, ref left, ref field, ref oid) => {
                 // TODO should this always work on a type reference, or should classes be added as values as well as types?
                 //self.bind_names_node(scope, left);
                 match **left {
                     AST::Identifier(_, _, ref ident) => {
                         match scope.get_type_def(&ident.name) {
-                            Some(defid) => self.session.set_ref(*oid, defid),
+                            Some(defid) => {
+                                if let Ok(Def::Module(ref moduledef)) = self.session.get_def(defid) {
+                                    if !moduledef.vars.contains_local(&field.name) {
+                                        return Err(Error::new(format!("NameError: {:?} is not defined in module {:?}", field.name, ident.name)));
+                                    }
+
+                                    // Functions without the pub keyword can only be used inside of their own module
+                                    let fielddef = moduledef.vars.get_var_def(&field.name).and_then(|defid| self.session.get_def(defid).ok());
+                                    if fielddef.map(|def| def.is_private(self.session)).unwrap_or(false) && !Scope::is_ancestor(moduledef.vars.clone(), scope.clone()) {
+                                        return Err(Error::new(format!("VisibilityError: {:?} is private to module {:?}, and can't be used outside of it", field.name, ident.name)));
+                                    }
+                                }
+                                self.session.set_ref(*oid, defid)
+                            },
                             None => return Err(Error::new(format!("NameError: undefined type {:?}", ident.name)))
                         }
                     },
@@ -257,26 +388,138 @@ impl<'sess> NameBinder<'sess> {
                 self.bind_names_node(scope, right);
             },
 
-            AST::Import(_, _, _, ref decls) => {
-                self.bind_names_vec(scope, decls);
+            AST::Import(ref id, _, ref ident, ref kind, ref decls) => {
+                match *kind {
+                    ImportKind::All => self.bind_names_vec(scope, decls),
+                    ImportKind::Alias(ref alias) => {
+                        let moduledef = ModuleDef::define(self.session, scope.clone(), *id, Some(alias.name.clone()))?;
+                        self.bind_names_vec(moduledef.vars.clone(), decls);
+                    },
+                    ImportKind::Names(ref names) => {
+                        // The declarations are bound in the module's own scope, and only the listed names are added to this one
+                        let moduledef = ModuleDef::define(self.session, scope.clone(), *id, None)?;
+                        self.bind_names_vec(moduledef.vars.clone(), decls);
+                        for name in names {
+                            let def = moduledef.vars.get_var_def(&name.name).and_then(|defid| self.session.get_def(defid).ok());
+                            if def.map(|def| def.is_private(self.session)).unwrap_or(false) {
+                                return Err(Error::new(format!("VisibilityError: {:?} is private to module {:?}, and can't be used outside of it", name.name, ident.name)));
+                            }
+                            moduledef.import_name(scope.clone(), &name.name)?;
+                        }
+                    },
+                }
+            },
+
+            AST::Module(ref id, _, ref ident, ref body) => {
+                // Members are mangled with the module's name, so they don't collide with definitions outside of it
+                let moduledef = ModuleDef::define(self.session, scope.clone(), *id, Some(ident.name.clone()))?;
+                moduledef.vars.set_module_name(ident.name.clone());
+                self.bind_names_vec(moduledef.vars.clone(), body);
             },
 
             AST::Nil(_) |
             AST::GetValue(_) |
+            AST::Macro(_, _, _, _, _, _) |
             AST::Literal(_, _) => { }
 
+            AST::Attributes(_, _, _, _) |
+            AST::Interpolate(_, _, _) |
             AST::List(_, _, _) |
             AST::For(_, _, _, _, _) |
+            AST::Range(_, _, _, _, _, _) |
             AST::Index(_, _, _, _) => { panic!("InternalError: ast element shouldn't appear at this late phase: {:?}", node) }
         }
         Ok(())
     }
 
+    fn bind_default(&self, scope: ScopeRef, arg: &Argument) {
+        if let Some(ref default) = arg.default {
+            // Each call site binds its own copy of the default value, in the same scope as the original
+            self.session.map.set(arg.id, scope.clone());
+            let previous = self.default_scope.replace(Some(scope.clone()));
+            self.bind_names_node(scope, default);
+            self.default_scope.replace(previous);
+        }
+    }
+
+    fn check_default_name(&self, scope: ScopeRef, name: &String) -> Result<(), Error> {
+        // Default values are evaluated by the caller, so they can't use the local variables of the scope their function was defined in
+        if let Some(ref dscope) = *self.default_scope.borrow() {
+            let mut found = scope;
+            while !found.contains_local(name) {
+                match found.get_parent() {
+                    Some(parent) => found = parent,
+                    None => return Ok(()),
+                }
+            }
+            if !Scope::is_ancestor(found.clone(), self.session.map.get_global()) && Scope::is_ancestor(found, dscope.clone()) {
+                return Err(Error::new(format!("NameError: default values can only use global names, but {:?} is local to the function where it's defined", name)));
+            }
+        }
+        Ok(())
+    }
+
+    fn bind_trait_bound(&self, arg: &Argument, ttype: Option<&Type>) {
+        // Arguments added for trait bounds provide the trait's methods for values of the bound typevar
+        if let Some(Type::Object(_, ref traitid, ref params)) = ttype {
+            if let (Ok(Def::Trait(_)), Some(Type::Variable(_, ref varid, _))) = (self.session.get_def(*traitid), params.get(0)) {
+                self.session.add_bound(*varid, *traitid, arg.id, &arg.ident.name);
+            }
+        }
+    }
+
+    fn check_declared_typevars<'a, I>(&self, scope: ScopeRef, fname: &String, params: &Vec<Type>, types: I) -> Result<(), Error> where I: Iterator<Item=&'a Type> {
+        // Functions that don't declare their type params can still introduce typevars anywhere in their signature
+        if params.len() == 0 {
+            return Ok(());
+        }
+
+        let mut names = vec!();
+        for ttype in types {
+            collect_typevar_names(ttype, &mut names);
+        }
+
+        for name in names {
+            let declared = params.iter().any(|param| param.get_varname().ok().as_ref() == Some(&name)) || is_enclosing_typevar(scope.clone(), &name);
+            if !declared {
+                return Err(Error::new(format!("TypeError: the type variable '{} is not declared in the type parameters of {}", name, fname)));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_defined_in_block(&self, scope: ScopeRef, name: &String) -> bool {
+        // Before blocks had their own scope, their variables were visible anywhere after them in the enclosing function
+        self.blocks.borrow().iter().any(|block| {
+            if !block.contains_local(name) {
+                return false;
+            }
+
+            let mut parent = block.get_parent();
+            while let Some(pscope) = parent {
+                if Scope::is_ancestor(pscope.clone(), scope.clone()) {
+                    return true;
+                }
+                if pscope.context.get() != Context::Block {
+                    break;
+                }
+                parent = pscope.get_parent();
+            }
+            false
+        })
+    }
+
     #[must_use]
     pub fn bind_names_pattern(&self, scope: ScopeRef, pat: &Pattern) -> Result<(), Error> {
         match pat {
-            Pattern::Binding(id, ident) => {
-                VarDef::define(self.session, scope.clone(), *id, Mutability::Immutable, &ident.name, None)?;
+            Pattern::Binding(id, ident) => match scope.find_var_def(self.session, &ident.name) {
+                // A name that refers to a constant is compared against its value instead of being bound
+                Ok(Def::Const(constdef)) => {
+                    let refid = NodeID::generate();
+                    self.session.set_ref(refid, constdef.id);
+                    self.session.set_const_pattern(*id, refid);
+                },
+                _ => { VarDef::define(self.session, scope.clone(), *id, Mutability::Immutable, &ident.name, None)?; },
             },
             Pattern::Annotation(id, ttype, pat) => {
                 let mut ttype = ttype.clone();
@@ -311,9 +554,39 @@ impl<'sess> NameBinder<'sess> {
                     self.bind_names_pattern(scope.clone(), item)?;
                 }
             },
+            Pattern::List(_, items, rest) => {
+                for item in items {
+                    self.bind_names_pattern(scope.clone(), item)?;
+                }
+                if let Some((_, rest)) = rest {
+                    self.bind_names_pattern(scope.clone(), rest)?;
+                }
+            },
+            Pattern::Or(_, alts) => {
+                // Only the first alternative defines its bindings, and the others refer to those same definitions
+                // Names of constants aren't bindings, so the names are compared after each alternative is bound
+                self.bind_names_pattern(scope.clone(), &alts[0])?;
+                let mut names = self.session.get_pattern_bindings(&alts[0]).iter().map(|(_, name)| name.clone()).collect::<Vec<String>>();
+                names.sort();
+                for alt in alts.iter().skip(1) {
+                    let ascope = Scope::new_ref(Some(scope.clone()));
+                    self.bind_names_pattern(ascope, alt)?;
+                    let mut bindings = self.session.get_pattern_bindings(alt);
+                    bindings.sort_by(|a, b| a.1.cmp(&b.1));
+                    if bindings.iter().map(|(_, name)| name.clone()).collect::<Vec<String>>() != names {
+                        return Err(Error::new(format!("SyntaxError: alternatives in an or-pattern must bind the same names, expected {:?} but found {:?}", names, bindings.iter().map(|(_, name)| name.clone()).collect::<Vec<String>>())));
+                    }
+                    for (id, name) in bindings {
+                        self.session.set_ref(id, scope.get_var_def(&name).unwrap());
+                    }
+                }
+            },
+
+            // A literal pattern can also be the name of a constant
+            Pattern::Literal(_, node) => self.bind_names_node_or_error(scope.clone(), node)?,
 
             Pattern::Wild |
-            Pattern::Literal(_, _) |
+            Pattern::Range(_, _, _, _, _) |
             Pattern::Identifier(_, _) => { },
         }
         Ok(())
@@ -321,6 +594,34 @@ impl<'sess> NameBinder<'sess> {
 }
 
 
+fn is_enclosing_typevar(scope: ScopeRef, name: &String) -> bool {
+    // A typevar from an enclosing class or function is allowed, but not one left at the top level of the module by an earlier declaration
+    let mut current = Some(scope);
+    while let Some(scope) = current {
+        if scope.contains_type_local(name) {
+            return scope.get_parent().map_or(false, |parent| !parent.is_global());
+        }
+        current = scope.get_parent();
+    }
+    false
+}
+
+fn collect_typevar_names(ttype: &Type, names: &mut Vec<String>) {
+    match ttype {
+        Type::Variable(ref name, _, _) => if !names.contains(name) { names.push(name.clone()) },
+        Type::Object(_, _, ref types) |
+        Type::Tuple(ref types) => types.iter().for_each(|ttype| collect_typevar_names(ttype, names)),
+        Type::Record(ref types) => types.iter().for_each(|(_, ttype)| collect_typevar_names(ttype, names)),
+        Type::Function(ref args, ref ret, _) => {
+            collect_typevar_names(args, names);
+            collect_typevar_names(ret, names);
+        },
+        Type::Ref(ref ttype) |
+        Type::Nullable(ref ttype) => collect_typevar_names(ttype, names),
+        _ => { },
+    }
+}
+
 #[must_use]
 pub fn bind_type_names(session: &Session, scope: ScopeRef, ttype: Option<&mut Type>, always_new: bool) -> Result<(), Error> {
     match ttype {
@@ -374,7 +675,8 @@ pub fn bind_type_names(session: &Session, scope: ScopeRef, ttype: Option<&mut Ty
                     }
                 }
             },
-            &mut Type::Ref(ref mut ttype) => {
+            &mut Type::Ref(ref mut ttype) |
+            &mut Type::Nullable(ref mut ttype) => {
                 bind_type_names(session, scope.clone(), Some(ttype), always_new)?;
             },
             &mut Type::Ambiguous(_) => { },
@@ -416,39 +718,3 @@ pub fn bind_type_names(session: &Session, scope: ScopeRef, ttype: Type, always_n
     });
 */
 
-#[must_use]
-pub fn check_recursive_type(ttype: &Option<&Type>, forbidden_id: NodeID) -> Result<(), Error> {
-    match ttype {
-        Some(ttype) => match ttype {
-            Type::Object(ref name, ref id, ref types) => {
-                if *id == forbidden_id {
-                    return Err(Error::new(format!("TypeError: an enum cannot contain itself in {:?}", name)));
-                }
-
-                for ttype in types.iter() {
-                    check_recursive_type(&Some(ttype), forbidden_id)?;
-                }
-            },
-            Type::Tuple(ref types) => {
-                for ttype in types.iter() {
-                    check_recursive_type(&Some(ttype), forbidden_id)?;
-                }
-            },
-            Type::Record(ref types) => {
-                for (_, ttype) in types.iter() {
-                    check_recursive_type(&Some(ttype), forbidden_id)?;
-                }
-            },
-            Type::Variable(ref name, ref id, ref existential) => {
-                // TODO this might be an error?
-            },
-            Type::Ref(_) |
-            Type::Function(_, _, _) => {
-                // Any reference or function containing the type is ok
-            },
-            Type::Ambiguous(_) => { },
-        },
-        None => { },
-    }
-    Ok(())
-}
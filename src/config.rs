@@ -14,6 +14,7 @@ pub struct Options<'a> {
     pub optlevel: u32,
     pub no_gc: bool,
     pub linkfile_only: bool,
+    pub test: bool,
 }
 
 
@@ -34,6 +35,7 @@ impl Options<'static> {
                 optlevel: 0,
                 no_gc: false,
                 linkfile_only: false,
+                test: false,
             });
         }
     }
@@ -0,0 +1,134 @@
+
+use defs::Def;
+use session::{ Session, Error };
+use ast::{ AST, Ident, Literal };
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstValue {
+    Literal(Literal),
+    Tuple(Vec<ConstValue>),
+    Record(Vec<(Ident, ConstValue)>),
+}
+
+
+#[must_use]
+pub fn evaluate(session: &Session, node: &AST) -> Result<ConstValue, Error> {
+    match *node {
+        AST::Literal(_, Literal::ByteString(_, _)) => Err(Error::new(format!("ConstError: byte strings can't be used in a constant expression"))),
+        AST::Literal(_, ref lit) => Ok(ConstValue::Literal(lit.clone())),
+
+        AST::Tuple(_, _, ref items) => {
+            Ok(ConstValue::Tuple(items.iter().map(|item| evaluate(session, item)).collect::<Result<Vec<ConstValue>, Error>>()?))
+        },
+
+        AST::Record(_, _, ref items) => {
+            let mut values = vec!();
+            for (ident, item) in items {
+                values.push((ident.clone(), evaluate(session, item)?));
+            }
+            Ok(ConstValue::Record(values))
+        },
+
+        AST::Identifier(ref id, _, ref ident) |
+        AST::Resolver(ref id, _, _, ref ident, _) => {
+            match session.get_def_from_ref(*id) {
+                Ok(Def::Const(ref constdef)) => constdef.get_value().ok_or(Error::new(format!("ConstError: the value of {:?} isn't known yet", ident.name))),
+                _ => Err(Error::new(format!("ConstError: {:?} is not a constant, and can't be used in a constant expression", ident.name))),
+            }
+        },
+
+        AST::If(_, _, ref cond, ref texpr, ref fexpr) => {
+            match evaluate(session, cond)? {
+                ConstValue::Literal(Literal::Boolean(true)) => evaluate(session, texpr),
+                ConstValue::Literal(Literal::Boolean(false)) => evaluate(session, fexpr),
+                value => Err(Error::new(format!("ConstError: expected a boolean condition but found {:?}", value))),
+            }
+        },
+
+        AST::SideEffect(_, _, ref op, ref args) => {
+            // The right hand side is only evaluated if it's needed, the same as at runtime
+            let expected = op.name.as_str() == "or";
+            for arg in args {
+                match evaluate(session, arg)? {
+                    ConstValue::Literal(Literal::Boolean(value)) if value == expected => return Ok(ConstValue::Literal(Literal::Boolean(value))),
+                    ConstValue::Literal(Literal::Boolean(_)) => { },
+                    value => return Err(Error::new(format!("ConstError: expected a boolean operand to {:?} but found {:?}", op.name, value))),
+                }
+            }
+            Ok(ConstValue::Literal(Literal::Boolean(!expected)))
+        },
+
+        AST::Invoke(_, _, ref fexpr, _, ref args) => {
+            let name = match **fexpr {
+                AST::Identifier(_, _, ref ident) => ident.name.clone(),
+                _ => return Err(Error::new(format!("ConstError: only operators on constant values can be used in a constant expression"))),
+            };
+
+            let mut values = vec!();
+            for arg in args {
+                match evaluate(session, arg)? {
+                    ConstValue::Literal(lit) => values.push(lit),
+                    _ => return Err(Error::new(format!("ConstError: the function {:?} cannot be called in a constant expression", name))),
+                }
+            }
+
+            match evaluate_operator(name.as_str(), values.as_slice())? {
+                Some(lit) => Ok(ConstValue::Literal(lit)),
+                None => Err(Error::new(format!("ConstError: the function {:?} cannot be called in a constant expression", name))),
+            }
+        },
+
+        _ => Err(Error::new(format!("ConstError: expression is not constant"))),
+    }
+}
+
+fn evaluate_operator(name: &str, args: &[Literal]) -> Result<Option<Literal>, Error> {
+    use ast::Literal::{ Boolean, Character, Integer, Real, String };
+
+    let division_by_zero = || Error::new(format!("ConstError: division by zero in a constant expression"));
+
+    Ok(Some(match (name, args) {
+        ("+", &[Integer(a), Integer(b)]) => Integer(a.wrapping_add(b)),
+        ("-", &[Integer(a), Integer(b)]) => Integer(a.wrapping_sub(b)),
+        ("*", &[Integer(a), Integer(b)]) => Integer(a.wrapping_mul(b)),
+        ("/", &[Integer(a), Integer(b)]) => Integer(a.checked_div(b).ok_or_else(division_by_zero)?),
+        ("%", &[Integer(a), Integer(b)]) => Integer(a.checked_rem(b).ok_or_else(division_by_zero)?),
+        ("&", &[Integer(a), Integer(b)]) => Integer(a & b),
+        ("|", &[Integer(a), Integer(b)]) => Integer(a | b),
+        ("~", &[Integer(a)]) => Integer(!a),
+        ("not", &[Integer(a)]) => Boolean(a == 0),
+
+        ("+", &[Real(a), Real(b)]) => Real(a + b),
+        ("-", &[Real(a), Real(b)]) => Real(a - b),
+        ("*", &[Real(a), Real(b)]) => Real(a * b),
+        ("/", &[Real(a), Real(b)]) => Real(a / b),
+        ("%", &[Real(a), Real(b)]) => Real(a % b),
+
+        ("+", &[String(ref a), String(ref b)]) => String(a.clone() + b),
+
+        ("not", &[Boolean(a)]) => Boolean(!a),
+
+        ("==", &[ref a, ref b]) => Boolean(a == b),
+        ("!=", &[ref a, ref b]) => Boolean(a != b),
+
+        ("<", &[ref a, ref b]) | (">", &[ref a, ref b]) | ("<=", &[ref a, ref b]) | (">=", &[ref a, ref b]) => {
+            let ordering = match (a, b) {
+                (Integer(a), Integer(b)) => a.partial_cmp(b),
+                (Real(a), Real(b)) => a.partial_cmp(b),
+                (Character(a), Character(b)) => a.partial_cmp(b),
+                _ => return Ok(None),
+            };
+            Boolean(match (name, ordering) {
+                (_, None) => false,
+                ("<", Some(ordering)) => ordering.is_lt(),
+                (">", Some(ordering)) => ordering.is_gt(),
+                ("<=", Some(ordering)) => ordering.is_le(),
+                (_, Some(ordering)) => ordering.is_ge(),
+            })
+        },
+
+        _ => return Ok(None),
+    }))
+}
+
@@ -0,0 +1,350 @@
+
+use session::{ Session, Error };
+use ast::{ NodeID, Literal, MatchCase, Pattern, AST };
+
+
+// The ways of constructing a value that a pattern can distinguish between
+#[derive(Clone, Debug, PartialEq)]
+enum Ctor {
+    Variant(NodeID, NodeID),
+    Bool(bool),
+    Tuple(usize),
+    Record(Vec<String>),
+    List(usize),
+    // Lists of at least the given length, represented by the given number of items at the start and at the end
+    ListAtLeast(usize, usize, usize),
+    // Literals and ranges, which can never cover all of the possible values
+    Other(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    List(Vec<Pat>, Option<Vec<Pat>>),
+    Or(Vec<Pat>),
+}
+
+type Row = Vec<Pat>;
+
+
+pub struct CoverageChecker<'sess> {
+    pub session: &'sess Session,
+}
+
+impl<'sess> CoverageChecker<'sess> {
+    #[must_use]
+    pub fn check_match(session: &'sess Session, cases: &Vec<MatchCase>) -> Result<(), Error> {
+        let checker = CoverageChecker {
+            session: session,
+        };
+
+        let mut rows = vec!();
+        for case in cases {
+            let pat = checker.convert_pattern(&case.pat);
+            if !checker.is_useful(&rows, &vec!(pat.clone())) {
+                session.print_warning(Error::new_pos(&case.pos, format!("Warning: unreachable case in match, the pattern {} will never be matched", checker.format_pattern(&pat))));
+            }
+
+            // A case with a guard might not match, so it doesn't cover any values
+            if case.guard.is_none() {
+                rows.push(vec!(pat));
+            }
+        }
+
+        match checker.find_missing(&rows, 1) {
+            Some(witness) => Err(Error::new(format!("TypeError: non-exhaustive match, the pattern {} is not covered", checker.format_pattern(&witness[0])))),
+            None => Ok(()),
+        }
+    }
+
+    fn convert_pattern(&self, pat: &Pattern) -> Pat {
+        match pat {
+            Pattern::Binding(ref id, ref ident) if self.session.get_const_pattern(*id).is_some() => Pat::Ctor(Ctor::Other(ident.name.clone()), vec!()),
+            Pattern::Wild |
+            Pattern::Binding(_, _) |
+            Pattern::Identifier(_, _) => Pat::Wild,
+            Pattern::Annotation(_, _, ref pat) => self.convert_pattern(pat),
+            Pattern::Literal(_, AST::Literal(_, Literal::Boolean(value))) => Pat::Ctor(Ctor::Bool(*value), vec!()),
+            Pattern::Literal(_, AST::Literal(_, Literal::Unit)) => Pat::Ctor(Ctor::Tuple(0), vec!()),
+            Pattern::Literal(_, ref lit) => Pat::Ctor(Ctor::Other(literal_key(lit)), vec!()),
+            Pattern::Range(_, ref start, ref end, ref inclusive, _) => Pat::Ctor(Ctor::Other(format!("{}{}{}", literal_key(start), if *inclusive { "..=" } else { ".." }, literal_key(end))), vec!()),
+            Pattern::Resolve(ref id, _, _, _) => match self.get_variant(*id) {
+                Some(ctor) => {
+                    let arity = self.get_arity(&ctor);
+                    Pat::Ctor(ctor, vec![Pat::Wild; arity])
+                },
+                None => Pat::Wild,
+            },
+            Pattern::EnumArgs(ref id, _, ref args) => match self.get_variant(*id) {
+                Some(ctor) => Pat::Ctor(ctor, args.iter().map(|arg| self.convert_pattern(arg)).collect()),
+                None => Pat::Wild,
+            },
+            Pattern::Tuple(_, ref items) => Pat::Ctor(Ctor::Tuple(items.len()), items.iter().map(|item| self.convert_pattern(item)).collect()),
+            Pattern::Record(ref id, ref items) => {
+                // Any fields that aren't named by the pattern will match any value
+                let names = match self.session.get_type(*id).as_ref().map(|ttype| ttype.get_record_types()) {
+                    Some(Ok(types)) => types.iter().map(|(name, _)| name.clone()).collect(),
+                    _ => items.iter().map(|(ident, _)| ident.name.clone()).collect::<Vec<String>>(),
+                };
+                let args = names.iter().map(|name| {
+                    match items.iter().find(|(ident, _)| ident.name == *name) {
+                        Some((_, item)) => self.convert_pattern(item),
+                        None => Pat::Wild,
+                    }
+                }).collect();
+                Pat::Ctor(Ctor::Record(names), args)
+            },
+            Pattern::List(_, ref items, ref rest) => match rest {
+                Some((split, _)) => Pat::List(items[..*split].iter().map(|item| self.convert_pattern(item)).collect(), Some(items[*split..].iter().map(|item| self.convert_pattern(item)).collect())),
+                None => Pat::List(items.iter().map(|item| self.convert_pattern(item)).collect(), None),
+            },
+            Pattern::Or(_, ref alts) => Pat::Or(alts.iter().map(|alt| self.convert_pattern(alt)).collect()),
+        }
+    }
+
+    fn get_variant(&self, id: NodeID) -> Option<Ctor> {
+        let variant_id = self.session.get_ref(id).ok()?;
+        let enum_id = self.session.get_ref(variant_id).ok()?;
+        self.session.get_def(enum_id).ok()?.as_enum().ok()?;
+        Some(Ctor::Variant(enum_id, variant_id))
+    }
+
+    fn get_arity(&self, ctor: &Ctor) -> usize {
+        match ctor {
+            Ctor::Variant(enum_id, variant_id) => {
+                let enumdef = self.session.get_def(*enum_id).unwrap().as_enum().unwrap();
+                enumdef.get_variant_type_by_id(*variant_id).map(|ttype| ttype.as_vec().len()).unwrap_or(0)
+            },
+            Ctor::Tuple(size) => *size,
+            Ctor::Record(names) => names.len(),
+            Ctor::List(size) => *size,
+            Ctor::ListAtLeast(_, start, end) => start + end,
+            Ctor::Bool(_) |
+            Ctor::Other(_) => 0,
+        }
+    }
+
+    fn is_useful(&self, rows: &Vec<Row>, row: &Row) -> bool {
+        if row.len() == 0 {
+            return rows.len() == 0;
+        }
+
+        let rows = expand_or_patterns(rows);
+        match row[0] {
+            Pat::Or(ref alts) => {
+                alts.iter().any(|alt| {
+                    let mut alt_row = vec!(alt.clone());
+                    alt_row.extend(row[1..].iter().cloned());
+                    self.is_useful(&rows, &alt_row)
+                })
+            },
+            Pat::Wild => match self.get_complete_ctors(&rows, &row[0]) {
+                Some(ctors) => ctors.iter().any(|ctor| self.is_useful(&self.specialize(&rows, ctor), &self.specialize_row(row, ctor).unwrap())),
+                None => self.is_useful(&default_rows(&rows), &row[1..].to_vec()),
+            },
+            _ => {
+                let ctors = match row[0] {
+                    Pat::Ctor(ref ctor, _) => vec!(ctor.clone()),
+                    _ => self.get_complete_ctors(&rows, &row[0]).unwrap(),
+                };
+                ctors.iter().any(|ctor| match self.specialize_row(row, ctor) {
+                    Some(subrow) => self.is_useful(&self.specialize(&rows, ctor), &subrow),
+                    None => false,
+                })
+            },
+        }
+    }
+
+    fn find_missing(&self, rows: &Vec<Row>, width: usize) -> Option<Row> {
+        if width == 0 {
+            return if rows.len() == 0 { Some(vec!()) } else { None };
+        }
+
+        let rows = expand_or_patterns(rows);
+        match self.get_complete_ctors(&rows, &Pat::Wild) {
+            Some(ctors) => {
+                for ctor in ctors {
+                    let arity = self.get_arity(&ctor);
+                    if let Some(witness) = self.find_missing(&self.specialize(&rows, &ctor), arity + width - 1) {
+                        let mut result = vec!(Pat::Ctor(ctor, witness[..arity].to_vec()));
+                        result.extend(witness[arity..].iter().cloned());
+                        return Some(result);
+                    }
+                }
+                None
+            },
+            None => {
+                let witness = self.find_missing(&default_rows(&rows), width - 1)?;
+                let mut result = vec!(self.find_missing_ctor(&rows));
+                result.extend(witness);
+                Some(result)
+            },
+        }
+    }
+
+    fn get_complete_ctors(&self, rows: &Vec<Row>, extra: &Pat) -> Option<Vec<Ctor>> {
+        let heads: Vec<&Pat> = rows.iter().map(|row| &row[0]).chain(Some(extra)).collect();
+
+        // Lists are split into each of the lengths that the patterns distinguish, plus all the longer lists
+        if heads.iter().any(|head| if let Pat::List(_, _) = head { true } else { false }) {
+            let (mut fixed, mut start, mut end) = (0, 0, 0);
+            for head in heads.iter() {
+                match head {
+                    Pat::List(items, None) => fixed = fixed.max(items.len() + 1),
+                    Pat::List(before, Some(after)) => {
+                        start = start.max(before.len());
+                        end = end.max(after.len());
+                    },
+                    _ => { },
+                }
+            }
+            let min = fixed.max(start + end);
+            let mut ctors: Vec<Ctor> = (0..min).map(|size| Ctor::List(size)).collect();
+            ctors.push(Ctor::ListAtLeast(min, start, end));
+            return Some(ctors);
+        }
+
+        let ctors = get_head_ctors(rows);
+        match ctors.first() {
+            Some(Ctor::Variant(enum_id, _)) => {
+                let all = self.get_all_variants(*enum_id);
+                if all.iter().all(|ctor| ctors.contains(ctor)) { Some(all) } else { None }
+            },
+            Some(Ctor::Bool(_)) => {
+                let all = vec!(Ctor::Bool(true), Ctor::Bool(false));
+                if all.iter().all(|ctor| ctors.contains(ctor)) { Some(all) } else { None }
+            },
+            Some(Ctor::Tuple(_)) |
+            Some(Ctor::Record(_)) => Some(vec!(ctors[0].clone())),
+            _ => None,
+        }
+    }
+
+    fn get_all_variants(&self, enum_id: NodeID) -> Vec<Ctor> {
+        let enumdef = self.session.get_def(enum_id).unwrap().as_enum().unwrap();
+        let variants = enumdef.variants.borrow();
+        variants.iter().map(|variant| Ctor::Variant(enum_id, variant.id)).collect()
+    }
+
+    fn find_missing_ctor(&self, rows: &Vec<Row>) -> Pat {
+        let ctors = get_head_ctors(rows);
+        let all = match ctors.first() {
+            Some(Ctor::Variant(enum_id, _)) => self.get_all_variants(*enum_id),
+            Some(Ctor::Bool(_)) => vec!(Ctor::Bool(true), Ctor::Bool(false)),
+            _ => vec!(),
+        };
+
+        match all.into_iter().find(|ctor| !ctors.contains(ctor)) {
+            Some(ctor) => {
+                let arity = self.get_arity(&ctor);
+                Pat::Ctor(ctor, vec![Pat::Wild; arity])
+            },
+            None => Pat::Wild,
+        }
+    }
+
+    fn specialize(&self, rows: &Vec<Row>, ctor: &Ctor) -> Vec<Row> {
+        rows.iter().filter_map(|row| self.specialize_row(row, ctor)).collect()
+    }
+
+    fn specialize_row(&self, row: &Row, ctor: &Ctor) -> Option<Row> {
+        let mut result = match (&row[0], ctor) {
+            (Pat::Wild, _) => vec![Pat::Wild; self.get_arity(ctor)],
+            (Pat::Ctor(ref rctor, ref args), _) if rctor == ctor => args.clone(),
+            (Pat::List(ref items, None), Ctor::List(size)) if items.len() == *size => items.clone(),
+            (Pat::List(ref before, Some(ref after)), Ctor::List(size)) if before.len() + after.len() <= *size => {
+                let mut items = before.clone();
+                items.extend(vec![Pat::Wild; size - before.len() - after.len()]);
+                items.extend(after.iter().cloned());
+                items
+            },
+            (Pat::List(ref before, Some(ref after)), Ctor::ListAtLeast(_, start, end)) => {
+                let mut items = before.clone();
+                items.extend(vec![Pat::Wild; start - before.len() + end - after.len()]);
+                items.extend(after.iter().cloned());
+                items
+            },
+            _ => return None,
+        };
+        result.extend(row[1..].iter().cloned());
+        Some(result)
+    }
+
+    fn format_pattern(&self, pat: &Pat) -> String {
+        let format_list = |items: &Vec<Pat>| items.iter().map(|item| self.format_pattern(item)).collect::<Vec<String>>().join(", ");
+        match pat {
+            Pat::Wild => String::from("_"),
+            Pat::Or(alts) => alts.iter().map(|alt| self.format_pattern(alt)).collect::<Vec<String>>().join(" | "),
+            Pat::List(before, None) => format!("[{}]", format_list(before)),
+            Pat::List(before, Some(after)) => {
+                let mut items: Vec<String> = before.iter().map(|item| self.format_pattern(item)).collect();
+                items.push(String::from(".."));
+                items.extend(after.iter().map(|item| self.format_pattern(item)));
+                format!("[{}]", items.join(", "))
+            },
+            Pat::Ctor(ctor, args) => match ctor {
+                Ctor::Variant(enum_id, variant_id) => {
+                    let enumdef = self.session.get_def(*enum_id).unwrap().as_enum().unwrap();
+                    let index = enumdef.get_variant_by_id(*variant_id).unwrap();
+                    let name = format!("{}::{}", enumdef.deftype.get_name().unwrap(), enumdef.variants.borrow()[index].ident.name);
+                    if args.len() == 0 { name } else { format!("{}({})", name, format_list(args)) }
+                },
+                Ctor::Bool(value) => format!("{}", value),
+                Ctor::Tuple(_) => format!("({})", format_list(args)),
+                Ctor::Record(names) => format!("{{ {} }}", names.iter().zip(args.iter()).map(|(name, arg)| format!("{} = {}", name, self.format_pattern(arg))).collect::<Vec<String>>().join(", ")),
+                Ctor::List(_) => format!("[{}]", format_list(args)),
+                Ctor::ListAtLeast(min, start, end) => {
+                    let mut items: Vec<String> = args[..*start].iter().map(|item| self.format_pattern(item)).collect();
+                    items.extend(vec![String::from("_"); min - start - end]);
+                    items.push(String::from(".."));
+                    items.extend(args[*start..].iter().map(|item| self.format_pattern(item)));
+                    format!("[{}]", items.join(", "))
+                },
+                Ctor::Other(key) => key.clone(),
+            },
+        }
+    }
+}
+
+
+fn expand_or_patterns(rows: &Vec<Row>) -> Vec<Row> {
+    let mut expanded = vec!();
+    for row in rows {
+        match row[0] {
+            Pat::Or(ref alts) => {
+                let alt_rows = alts.iter().map(|alt| {
+                    let mut alt_row = vec!(alt.clone());
+                    alt_row.extend(row[1..].iter().cloned());
+                    alt_row
+                }).collect();
+                expanded.extend(expand_or_patterns(&alt_rows));
+            },
+            _ => expanded.push(row.clone()),
+        }
+    }
+    expanded
+}
+
+fn default_rows(rows: &Vec<Row>) -> Vec<Row> {
+    rows.iter().filter(|row| row[0] == Pat::Wild).map(|row| row[1..].to_vec()).collect()
+}
+
+fn get_head_ctors(rows: &Vec<Row>) -> Vec<Ctor> {
+    rows.iter().filter_map(|row| match row[0] {
+        Pat::Ctor(ref ctor, _) => Some(ctor.clone()),
+        _ => None,
+    }).collect()
+}
+
+fn literal_key(node: &AST) -> String {
+    match node {
+        AST::Literal(_, Literal::String(value)) => format!("{:?}", value),
+        AST::Literal(_, Literal::Character(value)) => format!("{:?}", value),
+        AST::Literal(_, Literal::Integer(value)) => format!("{}", value),
+        AST::Literal(_, Literal::Real(value)) => format!("{:?}", value),
+        AST::Identifier(_, _, ident) => ident.name.clone(),
+        _ => format!("{:?}", node),
+    }
+}
+
@@ -8,7 +8,7 @@ use types::Type;
 use scope::{ Scope, ScopeRef };
 use session::{ Session, Error };
 use types::{ check_type, Check };
-use ast::{ NodeID, Mutability, Ident, AST };
+use ast::{ NodeID, Mutability, Visibility, Ident, AST };
 
 use defs::variables::FieldDef;
 
@@ -93,6 +93,23 @@ impl ClassDef {
     }
 
 
+    pub fn get_parent_class(&self, session: &Session) -> Option<ClassDefRef> {
+        let id = self.parenttype.as_ref()?.get_id().ok()?;
+        session.get_def(id).ok()?.as_class().ok()
+    }
+
+    pub fn is_subclass_of(&self, session: &Session, id: NodeID) -> bool {
+        self.id == id || self.get_parent_class(session).map(|parent| parent.is_subclass_of(session, id)).unwrap_or(false)
+    }
+
+    pub fn find_member_class(&self, session: &Session, name: &String) -> Option<ClassDefRef> {
+        let mut classdef = session.get_def(self.id).ok()?.as_class().ok()?;
+        while !classdef.structdef.vars.contains_local(name) {
+            classdef = classdef.get_parent_class(session)?;
+        }
+        Some(classdef)
+    }
+
     pub fn set_primative(&self) {
         self.primative.set(true);
     }
@@ -134,7 +151,7 @@ impl ClassDef {
         }
         for ref node in body.iter() {
             match **node {
-                AST::Definition(ref id, _, ref mutable, ref ident, _, _) => {
+                AST::Definition(ref id, _, _, ref mutable, ref ident, _, _) => {
                     self.structdef.add_field(session, *id, *mutable, ident.name.as_str(), session.get_type(*id).unwrap(), Define::IfNotExists);
                 },
                 _ => { }
@@ -224,7 +241,7 @@ impl StructDef {
     pub fn add_field(&self, session: &Session, id: NodeID, mutable: Mutability, name: &str, ttype: Type, define: Define) {
         let sname = String::from(name);
         if define == Define::IfNotExists && self.vars.get_var_def(&sname).is_none() {
-            FieldDef::define(session, self.vars.clone(), id, mutable, &sname, Some(ttype.clone())).unwrap();
+            FieldDef::define(session, self.vars.clone(), id, Visibility::Public, mutable, &sname, Some(ttype.clone())).unwrap();
         }
         self.fields.borrow_mut().push((id, sname, ttype));
     }
@@ -288,12 +305,12 @@ impl Vtable {
     pub fn build_vtable(&self, session: &Session, scope: ScopeRef, body: &Vec<AST>) {
         for ref node in body.iter() {
             match **node {
-                AST::Function(ref id, _, _, ref ident, _, _, _, _) => {
+                AST::Function(ref id, _, _, ref ident, _, _, _, _, _) => {
                     if let Some(Ident { ref name, .. }) = ident {
                         self.add_entry(session, scope.clone(), *id, name.as_str(), session.get_type(*id).unwrap());
                     }
                 },
-                AST::Declare(ref id, _, _, ref ident, _) => {
+                AST::Declare(ref id, _, _, ref ident, _, _, _) => {
                     let ttype = session.get_type(*id).unwrap();
                     match ttype {
                         Type::Function(_, _, _) => {
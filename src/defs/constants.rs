@@ -0,0 +1,50 @@
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use defs::Def;
+use types::Type;
+use ast::{ NodeID };
+use scope::{ ScopeRef };
+use session::{ Session, Error };
+use defs::variables::VarDef;
+use constants::ConstValue;
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstDef {
+    pub id: NodeID,
+    pub value: RefCell<Option<ConstValue>>,
+}
+
+pub type ConstDefRef = Rc<ConstDef>;
+
+
+impl ConstDef {
+    pub fn new(id: NodeID) -> Self {
+        Self {
+            id: id,
+            value: RefCell::new(None),
+        }
+    }
+
+    pub fn new_ref(id: NodeID) -> ConstDefRef {
+        Rc::new(Self::new(id))
+    }
+
+    #[must_use]
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, name: &String, ttype: Option<Type>) -> Result<ConstDefRef, Error> {
+        let constdef = Self::new_ref(id);
+        VarDef::set_var_def(session, scope, id, name, Def::Const(constdef.clone()), ttype)?;
+        Ok(constdef)
+    }
+
+    pub fn set_value(&self, value: ConstValue) {
+        *self.value.borrow_mut() = Some(value);
+    }
+
+    pub fn get_value(&self) -> Option<ConstValue> {
+        self.value.borrow().clone()
+    }
+}
+
@@ -43,6 +43,12 @@ impl EnumDef {
         let vars = Scope::new_ref(None);
         vars.set_basename(name.clone());
 
+        // Functions defined in the enum body are added to the enum's vars, the same as a class
+        let tscope = session.map.get_or_add(id, Some(scope.clone()));
+        tscope.set_redirect(true);
+        tscope.set_basename(name.clone());
+        tscope.define_type(String::from("Self"), Some(id))?;
+
         let enumdef = Self::new_ref(id, vars, deftype.clone());
         scope.define_type(name, Some(id))?;
         session.set_def(id, Def::Enum(enumdef.clone()));
@@ -71,5 +77,12 @@ impl EnumDef {
         let index = self.get_variant_by_id(id)?;
         self.variants.borrow()[index].ttype.clone()
     }
+
+    pub fn instantiate_variant_type(&self, session: &Session, scope: ScopeRef, id: NodeID) -> Option<Type> {
+        // Each use of a variant gets new typevars in place of the enum's type params
+        self.get_variant_by_id(id)?;
+        let ttype = session.get_type(id).unwrap();
+        Some(scope.map_all_typevars(session, ttype))
+    }
 }
 
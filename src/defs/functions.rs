@@ -6,26 +6,27 @@ use types::*;
 use defs::Def;
 use scope::{ Scope, ScopeRef };
 use session::{ Session, Error };
-use ast::{ NodeID, Mutability, Visibility };
+use ast::{ NodeID, Mutability, Visibility, Argument, AST };
 
 use defs::classes::{ Define, StructDef, StructDefRef };
+use defs::traits::{ split_bound_types, bounds_are_met };
 
 
 pub struct AnyFunc();
 
 impl AnyFunc {
     #[must_use]
-    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, abi: ABI, ttype: Option<Type>) -> Result<Def, Error> {
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, abi: ABI, args: Vec<Argument>, ttype: Option<Type>) -> Result<Def, Error> {
         match abi {
-            ABI::C => CFuncDef::define(session, scope.clone(), id, vis, name, ttype),
-            ABI::MoltenFunc => FuncDef::define(session, scope.clone(), id, vis, name, ttype),
+            ABI::C => CFuncDef::define(session, scope.clone(), id, vis, name, args, ttype),
+            ABI::MoltenFunc => FuncDef::define(session, scope.clone(), id, vis, name, args, ttype),
             ABI::Molten => {
                 if scope.is_redirect() && name.is_some() {
                     // TODO do you need a separate or selectable somehow method def for MoltenFunc types
-                    MethodDef::define(session, scope.clone(), id, vis, name, ttype)
+                    MethodDef::define(session, scope.clone(), id, vis, name, args, ttype)
                 } else {
                     //FuncDef::define(session, scope.clone(), id, vis, name, ttype)
-                    ClosureDef::define(session, scope.clone(), id, vis, name, ttype)
+                    ClosureDef::define(session, scope.clone(), id, vis, name, args, ttype)
                 }
             },
             _ => return Err(Error::new(format!("DefError: unsupported ABI {:?}", abi))),
@@ -33,21 +34,62 @@ impl AnyFunc {
     }
 }
 
+/// Match the positional and keyword arguments of a call against a function's declared arguments,
+/// returning the values in declaration order, with `default` used for any omitted arguments
+pub fn arrange_args<T, F>(params: &Vec<Argument>, args: &Vec<AST>, mut values: Vec<T>, default: F) -> Result<Vec<T>, Error> where F: Fn(&Argument) -> T {
+    let has_keywords = args.iter().any(|arg| arg.is_keyword());
+    if params.len() == 0 || (!has_keywords && args.len() >= params.len()) {
+        if has_keywords {
+            return Err(Error::new(format!("TypeError: keyword arguments can only be used when calling a named function")));
+        }
+        return Ok(values);
+    }
+
+    let mut arranged: Vec<Option<T>> = params.iter().map(|_| None).collect();
+    for (i, (arg, value)) in args.iter().zip(values.drain(..)).enumerate() {
+        let index = match arg {
+            AST::Keyword(_, _, ident, _) => match params.iter().position(|param| param.ident.name == ident.name) {
+                Some(index) => index,
+                None => return Err(Error::new(format!("TypeError: unknown argument name {:?}", ident.name))),
+            },
+            _ if i < params.len() => i,
+            _ => return Err(Error::new(format!("TypeError: expected at most {} arguments but found {}", params.len(), args.len()))),
+        };
+
+        if arranged[index].is_some() {
+            return Err(Error::new(format!("TypeError: argument {:?} was given more than once", params[index].ident.name)));
+        }
+        arranged[index] = Some(value);
+    }
+
+    let mut result = vec!();
+    for (param, value) in params.iter().zip(arranged.into_iter()) {
+        match value {
+            Some(value) => result.push(value),
+            None if param.default.is_some() => result.push(default(param)),
+            None => return Err(Error::new(format!("TypeError: missing argument {:?}", param.ident.name))),
+        }
+    }
+    Ok(result)
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct FuncDef {
     pub id: NodeID,
     pub vis: Visibility,
+    pub args: Vec<Argument>,
 }
 
 pub type FuncDefRef = Rc<FuncDef>;
 
 impl FuncDef {
     #[must_use]
-    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, ttype: Option<Type>) -> Result<Def, Error> {
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, args: Vec<Argument>, ttype: Option<Type>) -> Result<Def, Error> {
 
         let def = Def::Func(Rc::new(FuncDef {
             id: id,
             vis: vis,
+            args: args,
         }));
 
         FuncDef::set_func_def(session, scope.clone(), id, name, def.clone(), ttype)?;
@@ -182,7 +224,9 @@ impl OverloadDef {
             };
 
             if let Type::Function(ref btypes, _, _) = ttype {
-                if check_type(session, tscope.clone(), Some(*btypes.clone()), Some(atypes.clone()), Check::Def, false).is_ok() {
+                // The trait arguments aren't given by the caller, but a variant whose bounds aren't met by the argument types isn't a match
+                let (btypes, bounds) = split_bound_types(session, btypes.as_vec());
+                if check_type(session, tscope.clone(), Some(Type::Tuple(btypes.clone())), Some(atypes.clone()), Check::Def, false).is_ok() && bounds_are_met(session, &btypes, &atypes.as_vec(), &bounds) {
                     if found.len() < 1 || self.variants.borrow().iter().position(|lid| *lid == id).is_some() {
                         found.push((id, ttype.clone()));
                     }
@@ -198,6 +242,7 @@ impl OverloadDef {
 pub struct ClosureDef {
     pub id: NodeID,
     pub vis: Visibility,
+    pub args: Vec<Argument>,
     pub context_arg_id: NodeID,
     pub context_type_id: NodeID,
     pub context_struct: StructDefRef,
@@ -207,13 +252,14 @@ pub type ClosureDefRef = Rc<ClosureDef>;
 
 impl ClosureDef {
     #[must_use]
-    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, ttype: Option<Type>) -> Result<Def, Error> {
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, args: Vec<Argument>, ttype: Option<Type>) -> Result<Def, Error> {
         let ctid = NodeID::generate();
         let structdef = StructDef::new_ref(Scope::new_ref(Some(scope.clone())));
 
         let def = Def::Closure(Rc::new(ClosureDef {
             id: id,
             vis: vis,
+            args: args,
             context_arg_id: NodeID::generate(),
             context_type_id: ctid,
             context_struct: structdef,
@@ -251,8 +297,8 @@ pub type MethodDefRef = Rc<MethodDef>;
 
 impl MethodDef {
     #[must_use]
-    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, ttype: Option<Type>) -> Result<Def, Error> {
-        let closure = match ClosureDef::define(session, scope, id, vis, name, ttype) {
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, args: Vec<Argument>, ttype: Option<Type>) -> Result<Def, Error> {
+        let closure = match ClosureDef::define(session, scope, id, vis, name, args, ttype) {
             Ok(Def::Closure(cl)) => cl,
             result @ _ => return result,
         };
@@ -274,6 +320,7 @@ impl MethodDef {
 pub struct CFuncDef {
     pub id: NodeID,
     pub vis: Visibility,
+    pub args: Vec<Argument>,
     //pub ftype: Type,
 }
 
@@ -281,7 +328,7 @@ pub type CFuncDefRef = Rc<CFuncDef>;
 
 impl CFuncDef {
     #[must_use]
-    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, ttype: Option<Type>) -> Result<Def, Error> {
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &Option<String>, args: Vec<Argument>, ttype: Option<Type>) -> Result<Def, Error> {
         if scope.is_redirect() {
             return Err(Error::new(format!("DefError: cannot declare a C ABI function within a class body")));
         }
@@ -294,6 +341,7 @@ impl CFuncDef {
         let def = Def::CFunc(Rc::new(CFuncDef {
             id: id,
             vis: vis,
+            args: args,
         }));
 
         scope.define(name.clone(), Some(id))?;
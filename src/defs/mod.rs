@@ -1,17 +1,22 @@
 
 use scope::ScopeRef;
-use ast::Mutability;
+use ast::{ Mutability, Visibility, Argument };
 use session::{ Session, Error };
 
-//pub mod traits;
+pub mod traits;
 pub mod enums;
+pub mod modules;
+pub mod constants;
 pub mod types;
 pub mod classes;
 pub mod variables;
 pub mod functions;
 
 use defs::enums::{ EnumDefRef };
+use defs::modules::{ ModuleDefRef };
+use defs::constants::{ ConstDefRef };
 use defs::types::{ TypeAliasDefRef };
+use defs::traits::{ TraitDefRef, ImplDefRef };
 use defs::classes::{ ClassDefRef, StructDefRef };
 use defs::variables::{ VarDefRef, ArgDefRef, FieldDefRef };
 use defs::functions::{ FuncDefRef, OverloadDefRef, ClosureDefRef, MethodDefRef, CFuncDefRef };
@@ -21,16 +26,20 @@ use defs::functions::{ FuncDefRef, OverloadDefRef, ClosureDefRef, MethodDefRef,
 pub enum Def {
     TypeAlias(TypeAliasDefRef),
     Var(VarDefRef),
+    Const(ConstDefRef),
     Arg(ArgDefRef),
     Field(FieldDefRef),
     Class(ClassDefRef),
     Struct(StructDefRef),
     Enum(EnumDefRef),
+    Module(ModuleDefRef),
     Func(FuncDefRef),
     Overload(OverloadDefRef),
     Closure(ClosureDefRef),
     Method(MethodDefRef),
     CFunc(CFuncDefRef),
+    Trait(TraitDefRef),
+    Impl(ImplDefRef),
 }
 
 
@@ -50,6 +59,13 @@ impl Def {
         }
     }
 
+    pub fn as_const(&self) -> Result<ConstDefRef, Error> {
+        match *self {
+            Def::Const(ref constdef) => Ok(constdef.clone()),
+            _ => Err(Error::new(format!("DefError: expected const def but found {:#?}", self))),
+        }
+    }
+
     pub fn as_enum(&self) -> Result<EnumDefRef, Error> {
         match *self {
             Def::Enum(ref enumdef) => Ok(enumdef.clone()),
@@ -57,12 +73,29 @@ impl Def {
         }
     }
 
+    pub fn as_trait(&self) -> Result<TraitDefRef, Error> {
+        match *self {
+            Def::Trait(ref traitdef) => Ok(traitdef.clone()),
+            _ => Err(Error::new(format!("DefError: expected trait def but found {:#?}", self))),
+        }
+    }
+
+    pub fn as_impl(&self) -> Result<ImplDefRef, Error> {
+        match *self {
+            Def::Impl(ref impldef) => Ok(impldef.clone()),
+            _ => Err(Error::new(format!("DefError: expected impl def but found {:#?}", self))),
+        }
+    }
+
     pub fn get_vars(&self) -> Result<ScopeRef, Error> {
         match *self {
             Def::Class(ref class) => Ok(class.structdef.vars.clone()),
             Def::Struct(ref structdef) => Ok(structdef.vars.clone()),
             Def::Enum(ref enumdef) => Ok(enumdef.vars.clone()),
-            _ => Err(Error::new(format!("DefError: expected class, struct, or enum but found {:#?}", self))),
+            Def::Module(ref moduledef) => Ok(moduledef.vars.clone()),
+            Def::Trait(ref traitdef) => Ok(traitdef.vars.clone()),
+            Def::Impl(ref impldef) => Ok(impldef.vars.clone()),
+            _ => Err(Error::new(format!("DefError: expected class, struct, enum, or module but found {:#?}", self))),
         }
     }
 
@@ -91,9 +124,46 @@ impl Def {
     }
     */
 
+    pub fn get_args(&self) -> Option<Vec<Argument>> {
+        match *self {
+            Def::Func(ref def) => Some(def.args.clone()),
+            Def::CFunc(ref def) => Some(def.args.clone()),
+            Def::Closure(ref def) => Some(def.args.clone()),
+            Def::Method(ref def) => Some(def.closure.args.clone()),
+            _ => None,
+        }
+    }
+
+    /// Get the arguments given by the caller, leaving out the ones added for trait bounds
+    pub fn get_caller_args(&self, session: &Session) -> Option<Vec<Argument>> {
+        self.get_args().map(|args| args.into_iter().filter(|arg| !session.is_bound_arg(arg.id)).collect())
+    }
+
+    pub fn is_private(&self, session: &Session) -> bool {
+        match *self {
+            Def::Func(ref def) => def.vis == Visibility::Private,
+            Def::CFunc(ref def) => def.vis == Visibility::Private,
+            Def::Closure(ref def) => def.vis == Visibility::Private,
+            Def::Method(ref def) => def.vis == Visibility::Private,
+            Def::Overload(ref def) => def.variants.borrow().iter().all(|id| session.get_def(*id).map(|def| def.is_private(session)).unwrap_or(false)),
+            _ => false,
+        }
+    }
+
+    pub fn get_vis(&self) -> Visibility {
+        match *self {
+            Def::Func(ref def) => def.vis,
+            Def::CFunc(ref def) => def.vis,
+            Def::Closure(ref def) => def.vis,
+            Def::Method(ref def) => def.vis,
+            Def::Field(ref def) => def.vis,
+            _ => Visibility::Public,
+        }
+    }
+
     pub fn is_globally_accessible(&self) -> bool {
         match self {
-            Def::CFunc(_) | Def::Func(_) => true,
+            Def::CFunc(_) | Def::Func(_) | Def::Const(_) | Def::Impl(_) => true,
             _ => false,
         }
     }
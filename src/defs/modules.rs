@@ -0,0 +1,66 @@
+
+use std::rc::Rc;
+
+use defs::Def;
+use types::Type;
+use scope::ScopeRef;
+use session::{ Session, Error };
+use ast::{ NodeID };
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleDef {
+    pub id: NodeID,
+    pub vars: ScopeRef,
+}
+
+pub type ModuleDefRef = Rc<ModuleDef>;
+
+
+impl ModuleDef {
+    pub fn new(id: NodeID, vars: ScopeRef) -> Self {
+        Self {
+            id: id,
+            vars: vars,
+        }
+    }
+
+    pub fn new_ref(id: NodeID, vars: ScopeRef) -> ModuleDefRef {
+        Rc::new(Self::new(id, vars))
+    }
+
+    #[must_use]
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, name: Option<String>) -> Result<ModuleDefRef, Error> {
+        // The module's scope has no basename, so its declarations keep the same names as in the module itself
+        let vars = session.map.get_or_add(id, Some(scope.clone()));
+        vars.set_context(scope.context.get());
+
+        let moduledef = Self::new_ref(id, vars);
+        if let Some(name) = name {
+            scope.define_type(name.clone(), Some(id))?;
+            session.set_type(id, Type::Object(name, id, vec!()));
+        }
+        session.set_def(id, Def::Module(moduledef.clone()));
+
+        Ok(moduledef)
+    }
+
+    #[must_use]
+    pub fn import_name(&self, scope: ScopeRef, name: &String) -> Result<(), Error> {
+        let mut found = false;
+        if self.vars.contains_local(name) {
+            scope.define(name.clone(), self.vars.get_var_def(name))?;
+            found = true;
+        }
+        if self.vars.contains_type_local(name) {
+            scope.define_type(name.clone(), self.vars.get_type_def(name))?;
+            found = true;
+        }
+
+        match found {
+            true => Ok(()),
+            false => Err(Error::new(format!("NameError: {:?} is not defined in the imported module", name))),
+        }
+    }
+}
+
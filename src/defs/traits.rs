@@ -1,25 +1,177 @@
 
-use types::Type;
-use scope::ScopeRef;
-use ast::{ NodeID, AST };
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use defs::Def;
+use types::{ Type, resolve_type };
+use typecheck::match_typevars;
+use scope::{ ScopeRef, Context };
 use session::{ Session, Error };
-use llvm::LLVMValueRef;
+use ast::{ NodeID };
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraitDef {
+    pub id: NodeID,
+    pub selfid: NodeID,
+    pub vars: ScopeRef,
+    pub deftype: Type,
+    pub methods: RefCell<Vec<(String, NodeID)>>,
+    pub impls: RefCell<HashMap<NodeID, NodeID>>,
+}
+
+pub type TraitDefRef = Rc<TraitDef>;
+
+
+impl TraitDef {
+    pub fn new(id: NodeID, selfid: NodeID, vars: ScopeRef, deftype: Type) -> Self {
+        Self {
+            id: id,
+            selfid: selfid,
+            vars: vars,
+            deftype: deftype,
+            methods: RefCell::new(vec!()),
+            impls: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn new_ref(id: NodeID, selfid: NodeID, vars: ScopeRef, deftype: Type) -> TraitDefRef {
+        Rc::new(Self::new(id, selfid, vars, deftype))
+    }
+
+    #[must_use]
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, name: &String) -> Result<TraitDefRef, Error> {
+        // The method declarations are defined in the trait's own scope, where Self is a typevar for the implementing type
+        let tscope = session.map.get_or_add(id, Some(scope.clone()));
+        tscope.set_basename(name.clone());
+
+        let selfid = NodeID::generate();
+        let selftype = Type::Variable(String::from("Self"), selfid, true);
+        tscope.define_type(String::from("Self"), Some(selfid))?;
+        session.set_type(selfid, selftype.clone());
+
+        // The trait's type is the record of its methods, which is passed to functions with trait bounds
+        let deftype = Type::Object(name.clone(), id, vec!(selftype));
+        let traitdef = Self::new_ref(id, selfid, tscope, deftype.clone());
+        scope.define_type(name.clone(), Some(id))?;
+        session.set_def(id, Def::Trait(traitdef.clone()));
+        session.set_type(id, deftype);
+
+        Ok(traitdef)
+    }
+
+    pub fn add_method(&self, name: &String, id: NodeID) {
+        self.methods.borrow_mut().push((name.clone(), id));
+    }
+
+    pub fn get_method(&self, name: &String) -> Option<(usize, NodeID)> {
+        self.methods.borrow().iter().position(|(mname, _)| mname == name).map(|index| (index, self.methods.borrow()[index].1))
+    }
 
-/*
-pub trait Overloadable {
-    fn add_variant(&self, id: NodeID);
+    pub fn get_name(&self) -> String {
+        self.deftype.get_name().unwrap()
+    }
+
+    #[must_use]
+    pub fn add_impl(&self, session: &Session, typeid: NodeID, implid: NodeID) -> Result<(), Error> {
+        if self.impls.borrow().contains_key(&typeid) {
+            let typename = session.get_type(typeid).map(|ttype| format!("{}", ttype)).unwrap_or(String::from("type"));
+            return Err(Error::new(format!("TraitError: {} already implements the trait {}", typename, self.get_name())));
+        }
+        self.impls.borrow_mut().insert(typeid, implid);
+        session.add_impl(typeid, implid);
+        Ok(())
+    }
+
+    pub fn get_impl(&self, typeid: NodeID) -> Option<NodeID> {
+        self.impls.borrow().get(&typeid).cloned()
+    }
 }
 
-trait Invokable {
-    fn typecheck_invoke(&self, session: &Session, scope: ScopeRef, node: &mut AST) -> Result<Type, Error>;
-    fn codegen_invoke(&self, args: Vec<LLVMValueRef>) -> LLVMValueRef;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImplDef {
+    pub id: NodeID,
+    pub traitid: NodeID,
+    pub impltype: Type,
+    pub vars: ScopeRef,
+}
+
+pub type ImplDefRef = Rc<ImplDef>;
+
+
+impl ImplDef {
+    pub fn new(id: NodeID, traitid: NodeID, impltype: Type, vars: ScopeRef) -> Self {
+        Self {
+            id: id,
+            traitid: traitid,
+            impltype: impltype,
+            vars: vars,
+        }
+    }
+
+    pub fn new_ref(id: NodeID, traitid: NodeID, impltype: Type, vars: ScopeRef) -> ImplDefRef {
+        Rc::new(Self::new(id, traitid, impltype, vars))
+    }
+
+    #[must_use]
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, traitdef: TraitDefRef, impltype: Type) -> Result<ImplDefRef, Error> {
+        let typeid = impltype.get_id()?;
+        let name = format!("{}_{}", traitdef.get_name(), impltype.get_name()?);
+
+        // The methods are defined in the impl's own scope, so they don't conflict with the type's other methods,
+        // but it's otherwise transparent like a block so the methods can capture the names around the impl
+        let tscope = session.map.get_or_add(id, Some(scope.clone()));
+        tscope.set_context(Context::Block);
+        tscope.set_basename(name);
+        tscope.define_type(String::from("Self"), Some(typeid))?;
+
+        let impldef = Self::new_ref(id, traitdef.id, impltype, tscope);
+        traitdef.add_impl(session, typeid, id)?;
+        session.set_def(id, Def::Impl(impldef.clone()));
+        session.set_type(id, Type::Object(traitdef.get_name(), traitdef.id, vec!(impldef.impltype.clone())));
+
+        Ok(impldef)
+    }
 }
 
 
-trait CompileInvoke {
-    fn compile(&self, args: Vec<LLVMValueRef>) -> LLVMValueRef;
+/// Functions with trait bounds take the trait's methods for each bound type as trailing arguments, which are filled in
+/// when the call is checked, so they're split off from the arguments given by the caller
+pub fn split_bound_types(session: &Session, mut argtypes: Vec<Type>) -> (Vec<Type>, Vec<Type>) {
+    let count = argtypes.iter().rev().take_while(|atype| is_trait_type(session, atype)).count();
+    let bounds = argtypes.split_off(argtypes.len() - count);
+    (argtypes, bounds)
 }
-*/
 
+pub fn is_trait_type(session: &Session, ttype: &Type) -> bool {
+    match *ttype {
+        Type::Object(_, ref id, _) => session.get_def(*id).map(|def| def.as_trait().is_ok()).unwrap_or(false),
+        _ => false,
+    }
+}
 
+/// Check that the types given for the bound typevars implement their traits.  Types that aren't known yet are
+/// allowed here, and are checked again once the types of the call are resolved
+pub fn bounds_are_met(session: &Session, ptypes: &Vec<Type>, atypes: &Vec<Type>, bounds: &Vec<Type>) -> bool {
+    let mut varmap = HashMap::new();
+    for (ptype, atype) in ptypes.iter().zip(atypes.iter()) {
+        match_typevars(&mut varmap, ptype, &resolve_type(session, atype.clone(), false).unwrap_or(atype.clone()));
+    }
 
+    bounds.iter().all(|bound| {
+        let traitdef = match bound.get_id().and_then(|id| session.get_def(id)?.as_trait()) {
+            Ok(traitdef) => traitdef,
+            Err(_) => return true,
+        };
+        let vtype = match bound.get_params().ok().and_then(|params| params.get(0).cloned()) {
+            Some(Type::Variable(_, ref vid, _)) => varmap.get(vid).cloned(),
+            _ => None,
+        };
+        match vtype.map(|vtype| resolve_type(session, vtype, false)) {
+            Some(Ok(Type::Object(_, ref id, _))) => traitdef.get_impl(*id).is_some(),
+            _ => true,
+        }
+    })
+}
@@ -3,7 +3,7 @@ use std::rc::Rc;
 
 use defs::Def;
 use types::Type;
-use ast::{ NodeID, Mutability };
+use ast::{ NodeID, Mutability, Visibility };
 use scope::{ Scope, ScopeRef };
 use session::{ Session, Error };
 
@@ -12,10 +12,10 @@ pub struct AnyVar();
 
 impl AnyVar {
     #[must_use]
-    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, mutable: Mutability, name: &String, ttype: Option<Type>) -> Result<Def, Error> {
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, mutable: Mutability, name: &String, ttype: Option<Type>) -> Result<Def, Error> {
         // TODO should you have a different one for Global, given that it'll compile to something different, until you remove it via closures...
         if scope.is_redirect() {
-            FieldDef::define(session, scope, id, mutable, name, ttype)
+            FieldDef::define(session, scope, id, vis, mutable, name, ttype)
         } else {
             VarDef::define(session, scope, id, mutable, name, ttype)
         }
@@ -81,19 +81,19 @@ impl ArgDef {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct FieldDef {
+    pub vis: Visibility,
     pub mutable: Mutability,
-
 }
 
 pub type FieldDefRef = Rc<FieldDef>;
 
 impl FieldDef {
     #[must_use]
-    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, mutable: Mutability, name: &String, ttype: Option<Type>) -> Result<Def, Error> {
+    pub fn define(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, mutable: Mutability, name: &String, ttype: Option<Type>) -> Result<Def, Error> {
 
         let def = Def::Field(Rc::new(FieldDef {
+            vis: vis,
             mutable: mutable,
-
         }));
 
         VarDef::set_var_def(session, scope.clone(), id, name, def.clone(), ttype)?;
@@ -3,11 +3,13 @@ use std::fs::File;
 use std::io::prelude::*;
 
 use types::Type;
-use misc::UniqueID;
+use misc::{ UniqueID, r };
 use config::Options;
-use session::Session;
+use session::{ Session, Error };
 use scope::{ ScopeRef };
-use ast::{ AST, Mutability, Visibility, NodeID };
+use ast::{ AST, Mutability, Visibility, NodeID, Argument, Literal };
+use constants::ConstValue;
+use defs::traits::split_bound_types;
 
 
 pub fn write_exports(session: &Session, scope: ScopeRef, filename: &str, code: &Vec<AST>) {
@@ -29,17 +31,17 @@ pub fn build_declarations(session: &Session, scope: ScopeRef, code: &Vec<AST>) -
 
 fn build_declarations_node(declarations: &mut String, session: &Session, scope: ScopeRef, node: &AST) {
     match *node {
-        AST::Declare(ref id, _, ref vis, ref ident, _) => {
-            declarations.push_str(&emit_declaration(session, scope.clone(), *id, *vis, &ident.name));
+        AST::Declare(ref id, _, ref vis, ref ident, _, ref args, _) => {
+            declarations.push_str(&emit_declaration(session, scope.clone(), *id, *vis, &ident.name, args));
         },
 
-        AST::Function(ref id, _, ref vis, ref ident, _, _, _, _) => {
+        AST::Function(ref id, _, ref vis, ref ident, _, ref args, _, _, _) => {
             if let Some(ref ident) = *ident {
-                declarations.push_str(&emit_declaration(session, scope.clone(), *id, *vis, &ident.name));
+                declarations.push_str(&emit_declaration(session, scope.clone(), *id, *vis, &ident.name, args));
             }
         },
 
-        AST::Definition(_, _, _, _, _, ref body) => match **body {
+        AST::Definition(_, _, _, _, _, _, Some(ref body)) => match **body {
             ref node @ AST::Class(_, _, _, _, _) => build_declarations_node(declarations, session, scope.clone(), node),
             _ => { },
         },
@@ -53,23 +55,24 @@ fn build_declarations_node(declarations: &mut String, session: &Session, scope:
                 namespec.clone()
             };
 
+            declarations.push_str(&emit_attributes(session, *id));
             declarations.push_str(format!("class {} {{\n", fullspec).as_str());
             //declarations.push_str(format!("    decl __alloc__() -> {}\n", namespec).as_str());
             //declarations.push_str(format!("    decl __init__({}) -> Nil\n", namespec).as_str());
             for node in body {
                 match *node {
-                    AST::Definition(ref id, _, ref mutable, ref ident, _, _) => {
+                    AST::Definition(ref id, _, ref vis, ref mutable, ref ident, _, _) => {
                         declarations.push_str("    ");
-                        declarations.push_str(&emit_field(session, tscope.clone(), *id, *mutable, &ident.name));
+                        declarations.push_str(&emit_field(session, tscope.clone(), *id, *vis, *mutable, &ident.name));
                     },
-                    AST::Declare(ref id, _, ref vis, ref ident, _) => {
+                    AST::Declare(ref id, _, ref vis, ref ident, _, ref args, _) => {
                         declarations.push_str("    ");
-                        declarations.push_str(&emit_declaration(session, tscope.clone(), *id, *vis, &ident.name));
+                        declarations.push_str(&emit_member_declaration(session, tscope.clone(), *id, *vis, &ident.name, args));
                     },
-                    AST::Function(ref id, _, ref vis, ref ident, _, _, _, _) => {
+                    AST::Function(ref id, _, ref vis, ref ident, _, ref args, _, _, _) => {
                         if let Some(ref ident) = *ident {
                             declarations.push_str("    ");
-                            declarations.push_str(&emit_declaration(session, tscope.clone(), *id, *vis, &ident.name));
+                            declarations.push_str(&emit_member_declaration(session, tscope.clone(), *id, *vis, &ident.name, args));
                         }
                     },
                     _ => {  },
@@ -77,24 +80,265 @@ fn build_declarations_node(declarations: &mut String, session: &Session, scope:
             }
             declarations.push_str(format!("}}\n").as_str());
         },
+
+        AST::Enum(ref id, _, ref classspec, _, ref body) => {
+            let tscope = session.map.get(&id);
+            let enumdef = session.get_def(*id).unwrap().as_enum().unwrap();
+            declarations.push_str(format!("enum {} =\n", unparse_type(session, tscope.clone(), enumdef.deftype.clone())).as_str());
+            for variant in enumdef.variants.borrow().iter() {
+                match variant.ttype {
+                    Some(ref ttype) => {
+                        let items: Vec<String> = ttype.as_vec().iter().map(|t| unparse_type(session, tscope.clone(), t.clone())).collect();
+                        declarations.push_str(format!("| {}({})\n", variant.ident.name, items.join(", ")).as_str());
+                    },
+                    None => declarations.push_str(format!("| {}\n", variant.ident.name).as_str()),
+                }
+            }
+
+            let mut functions = String::new();
+            for node in body {
+                match *node {
+                    AST::Declare(ref id, _, ref vis, ref ident, _, ref args, _) |
+                    AST::Function(ref id, _, ref vis, Some(ref ident), _, ref args, _, _, _) => {
+                        functions.push_str("    ");
+                        functions.push_str(&emit_member_declaration(session, tscope.clone(), *id, *vis, &ident.name, args));
+                    },
+                    _ => { },
+                }
+            }
+            if functions.len() > 0 {
+                declarations.push_str(format!("{{\n{}}}\n", functions).as_str());
+            }
+        },
+
+        AST::Trait(ref id, _, ref ident, ref body) => {
+            let tscope = session.map.get(&id);
+            declarations.push_str(format!("trait {} {{\n", ident.name).as_str());
+            for node in body {
+                if let AST::Declare(ref id, _, _, ref ident, _, ref args, _) = *node {
+                    let ttype = session.get_type(*id).unwrap();
+                    if let Some(ftype) = unparse_named_function(session, tscope.clone(), &ttype, args) {
+                        declarations.push_str(format!("    fn {}{}\n", ident.name, ftype).as_str());
+                    }
+                }
+            }
+            declarations.push_str(format!("}}\n").as_str());
+        },
+
+        AST::Impl(ref id, _, ref ident, _, ref body) => {
+            let tscope = session.map.get(&id);
+            let impldef = session.get_def(*id).unwrap().as_impl().unwrap();
+            declarations.push_str(format!("impl {} for {} {{\n", ident.name, unparse_type(session, tscope.clone(), impldef.impltype.clone())).as_str());
+            for node in body {
+                match *node {
+                    AST::Declare(ref id, _, ref vis, ref ident, _, ref args, _) |
+                    AST::Function(ref id, _, ref vis, Some(ref ident), _, ref args, _, _, _) => {
+                        declarations.push_str("    ");
+                        declarations.push_str(&emit_member_declaration(session, tscope.clone(), *id, *vis, &ident.name, args));
+                    },
+                    _ => { },
+                }
+            }
+            declarations.push_str(format!("}}\n").as_str());
+        },
+
+        AST::Macro(_, _, ref ident, ref args, ref text, _) => {
+            let args: Vec<&str> = args.iter().map(|arg| arg.name.as_str()).collect();
+            declarations.push_str(format!("macro {}({}) => {}\n", ident.name, args.join(", "), text).as_str());
+        },
+
+        AST::Const(ref id, ref pos, ref ident, _, _) => {
+            let ttype = session.get_type(*id).unwrap();
+            let value = session.get_def(*id).unwrap().as_const().unwrap().get_value().unwrap();
+            match unparse_const(&value) {
+                Ok(text) => declarations.push_str(format!("const {}: {} = {}\n", ident.name, unparse_type(session, scope.clone(), ttype), text).as_str()),
+                Err(err) => session.print_error(err.add_pos(pos)),
+            }
+        },
+
+        AST::Module(ref id, _, ref ident, ref body) => {
+            let mscope = session.map.get(&id);
+            declarations.push_str(format!("module {} {{\n", ident.name).as_str());
+            for line in build_declarations(session, mscope, body).lines() {
+                declarations.push_str(format!("    {}\n", line).as_str());
+            }
+            declarations.push_str(format!("}}\n").as_str());
+        },
         _ => { },
     }
 }
 
-fn emit_declaration(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &String) -> String {
+// Private functions are declared without the pub keyword, so that a module which imports them can report an error when they're used.
+// They can't be called from outside, so only their type is declared, and their default values don't need to be exportable
+fn emit_declaration(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &String, args: &Vec<Argument>) -> String {
     if vis == Visibility::Public {
-        //let name = get_mangled_name(session, tscope.clone(), &ident.name, *id);
-        let ttype = session.get_type(id).unwrap();
-        format!("decl {}{}\n", name, unparse_type(session, scope.clone(), ttype))
+        emit_function_declaration(session, scope, id, "pub ", name, args)
     } else {
-        String::from("")
+        let ttype = strip_bound_types(session, session.get_type(id).unwrap());
+        let name = name.clone() + &unparse_type_params(session, id);
+        format!("{}decl {}{}\n", emit_attributes(session, id), name, unparse_type(session, scope.clone(), ttype))
+    }
+}
+
+// Members are always declared, even when private, because the vtable and struct layout depend on them
+fn emit_member_declaration(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, name: &String, args: &Vec<Argument>) -> String {
+    emit_function_declaration(session, scope, id, &format!("{} ", unparse_vis(vis)), name, args)
+}
+
+fn emit_function_declaration(session: &Session, scope: ScopeRef, id: NodeID, prefix: &str, name: &String, args: &Vec<Argument>) -> String {
+    //let name = get_mangled_name(session, tscope.clone(), &ident.name, *id);
+    let ttype = strip_bound_types(session, session.get_type(id).unwrap());
+    let args = args.iter().filter(|arg| !session.is_bound_arg(arg.id)).cloned().collect();
+    let name = name.clone() + &unparse_type_params(session, id);
+    match unparse_named_function(session, scope.clone(), &ttype, &args) {
+        Some(ftype) => format!("{}{}decl {}{}\n", emit_attributes(session, id), prefix, name, ftype),
+        None => format!("{}{}decl {}{}\n", emit_attributes(session, id), prefix, name, unparse_type(session, scope.clone(), ttype)),
+    }
+}
+
+fn unparse_type_params(session: &Session, id: NodeID) -> String {
+    match session.get_type_params(id) {
+        Some(ref params) if params.len() > 0 => format!("<{}>", params.iter().map(|param| unparse_type_param(session, param)).collect::<Vec<String>>().join(", ")),
+        _ => String::from(""),
+    }
+}
+
+fn unparse_type_param(session: &Session, param: &Type) -> String {
+    let name = format!("'{}", param.get_varname().unwrap());
+    let traits = param.get_id().map(|varid| session.get_bounds(varid)).unwrap_or(vec!()).iter()
+        .filter_map(|(traitid, _, _)| session.get_def(*traitid).and_then(|def| def.as_trait()).ok().map(|traitdef| traitdef.get_name()))
+        .collect::<Vec<String>>();
+    match traits.len() {
+        0 => name,
+        _ => format!("{}: {}", name, traits.join(" + ")),
+    }
+}
+
+fn strip_bound_types(session: &Session, ttype: Type) -> Type {
+    // The trait arguments are declared by the bounds on the type params instead, which add them again when the declaration is imported
+    match ttype {
+        Type::Function(argtypes, rettype, abi) => Type::Function(r(Type::Tuple(split_bound_types(session, argtypes.as_vec()).0)), rettype, abi),
+        ttype => ttype,
     }
 }
 
-fn emit_field(session: &Session, scope: ScopeRef, id: NodeID, mutable: Mutability, name: &String) -> String {
+fn unparse_vis(vis: Visibility) -> &'static str {
+    match vis {
+        Visibility::Private => "priv",
+        Visibility::Protected => "protected",
+        Visibility::Public => "pub",
+    }
+}
+
+fn emit_attributes(session: &Session, id: NodeID) -> String {
+    // Only the attributes that affect the users of a declaration are exported
+    let mut text = String::new();
+    for name in &["export_name", "deprecated"] {
+        if let Some(attr) = session.get_attribute(id, name) {
+            match attr.get_string_arg(0) {
+                Some(arg) => text.push_str(format!("@{}({:?}) ", name, arg).as_str()),
+                None => text.push_str(format!("@{} ", name).as_str()),
+            }
+        }
+    }
+    text
+}
+
+fn unparse_named_function(session: &Session, scope: ScopeRef, ttype: &Type, args: &Vec<Argument>) -> Option<String> {
+    match *ttype {
+        // Arguments that are destructured by a pattern have generated names, which can't be declared
+        Type::Function(_, _, _) if args.iter().any(|arg| arg.ident.name.starts_with(|ch: char| ch.is_digit(10))) => None,
+        Type::Function(ref argtypes, ref ret, ref abi) if args.len() > 0 => {
+            let argtypes = match **argtypes {
+                Type::Tuple(ref types) if types.len() == args.len() => types,
+                _ => return None,
+            };
+
+            let mut named = vec!();
+            for (arg, atype) in args.iter().zip(argtypes.iter()) {
+                let default = match arg.default {
+                    Some(ref default) => match unparse_expr(default) {
+                        Ok(text) => format!(" = {}", text),
+                        Err(err) => {
+                            session.print_error(err.add_pos(&arg.pos));
+                            return None;
+                        },
+                    },
+                    None => String::from(""),
+                };
+                named.push(format!("{}: {}{}", arg.ident.name, unparse_type(session, scope.clone(), atype.clone()), default));
+            }
+            Some(format!("({}) -> {}{}", named.join(", "), unparse_type(session, scope.clone(), *ret.clone()), abi))
+        },
+        _ => None,
+    }
+}
+
+fn unparse_expr(node: &AST) -> Result<String, Error> {
+    match *node {
+        AST::Literal(_, ref lit) => unparse_literal(lit),
+        AST::Nil(_) => Ok(String::from("nil")),
+        AST::Identifier(_, _, ref ident) => Ok(ident.name.clone()),
+        AST::Tuple(_, _, ref items) => {
+            let items = items.iter().map(|item| unparse_expr(item)).collect::<Result<Vec<String>, Error>>()?;
+            Ok(format!("({})", items.join(", ")))
+        },
+        AST::Record(_, _, ref items) => {
+            let items = items.iter().map(|(name, item)| Ok(format!("{} = {}", name.name, unparse_expr(item)?))).collect::<Result<Vec<String>, Error>>()?;
+            Ok(format!("{{ {} }}", items.join(", ")))
+        },
+        _ => Err(Error::new(format!("ExportError: default values must be literals or simple names to be exported: {:?}", node))),
+    }
+}
+
+fn unparse_literal(lit: &Literal) -> Result<String, Error> {
+    Ok(match *lit {
+        Literal::Unit => String::from("()"),
+        Literal::Boolean(value) => format!("{}", value),
+        Literal::Character(value) => format!("'\\u{{{:x}}}'", value),
+        Literal::Integer(value) => format!("{}", value),
+        Literal::Real(value) => format!("{:?}", value),
+        Literal::String(ref value) => format!("\"{}\"", escape_string(value)),
+        Literal::ByteString(_, _) => return Err(Error::new(format!("ExportError: byte strings can't be exported as default values"))),
+    })
+}
+
+fn unparse_const(value: &ConstValue) -> Result<String, Error> {
+    match *value {
+        ConstValue::Literal(ref lit) => unparse_literal(lit),
+        ConstValue::Tuple(ref items) => {
+            let items = items.iter().map(|item| unparse_const(item)).collect::<Result<Vec<String>, Error>>()?;
+            Ok(format!("({})", items.join(", ")))
+        },
+        ConstValue::Record(ref items) => {
+            let items = items.iter().map(|(name, item)| Ok(format!("{} = {}", name.name, unparse_const(item)?))).collect::<Result<Vec<String>, Error>>()?;
+            Ok(format!("{{ {} }}", items.join(", ")))
+        },
+    }
+}
+
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::new();
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '$' => escaped.push_str("\\$"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn emit_field(session: &Session, scope: ScopeRef, id: NodeID, vis: Visibility, mutable: Mutability, name: &String) -> String {
     let ttype = session.get_type(id).unwrap();
+    let vis_str = if vis != Visibility::Public { format!("{} ", unparse_vis(vis)) } else { String::from("") };
     let mutable_str = if let Mutability::Mutable = mutable { "mut " } else { "" };
-    format!("let {}{}: {}\n", mutable_str, name, unparse_type(session, scope.clone(), ttype))
+    format!("{}let {}{}: {}\n", vis_str, mutable_str, name, unparse_type(session, scope.clone(), ttype))
 }
 
 pub fn unparse_type(session: &Session, scope: ScopeRef, ttype: Type) -> String {
@@ -132,6 +376,9 @@ pub fn unparse_type(session: &Session, scope: ScopeRef, ttype: Type) -> String {
         Type::Ref(ttype) => {
             format!("ref {}", unparse_type(session, scope.clone(), *ttype))
         },
+        Type::Nullable(ttype) => {
+            format!("{}?", unparse_type(session, scope.clone(), *ttype))
+        },
         Type::Ambiguous(variants) => {
             let varstr: Vec<String> = variants.iter().map(|v| unparse_type(session, scope.clone(), v.clone())).collect();
             format!("Ambiguous[{}]", varstr.join(", "))
@@ -21,10 +21,10 @@ use self::llvm_sys::transforms::pass_manager_builder::*;
 
 use ast::NodeID;
 use config::Options;
-use session::Session;
+use session::{ Session, Error };
 use misc::{ UniqueID, r };
 
-use llvm::llcode::{ LLType, LLLit, LLRef, LLCmpType, LLLink, LLCC, LLExpr, LLGlobal, LLBlock };
+use llvm::llcode::{ LLType, LLLit, LLRef, LLCmpType, LLLink, LLCC, LLAttr, LLExpr, LLGlobal, LLBlock };
 
 
 #[derive(Clone)]
@@ -138,6 +138,9 @@ impl<'sess> LLVM<'sess> {
     pub fn build_module(&self, globals: &Vec<LLGlobal>) {
         unsafe {
             self.build_declarations(globals);
+            if self.session.errors.get() > 0 {
+                panic!("Exiting due to previous errors");
+            }
             self.build_definitions(globals);
         }
     }
@@ -298,7 +301,15 @@ impl<'sess> LLVM<'sess> {
                 //self.build_type(&LLType::Array(r(LLType::I64), (largest / 8) as usize))
                 let ptrsize = LLVMPointerSize(*self.target_data.borrow());
                 LLVMArrayType(LLVMIntType(ptrsize * 8), (largest as u32) / ptrsize)
-            }
+            },
+            LLType::Nullable(etype) => {
+                // Pointer values can use null directly, but anything else is boxed the same way a typevar would be
+                let rtype = self.build_type(etype);
+                match LLVMGetTypeKind(rtype) {
+                    LLVMTypeKind::LLVMPointerTypeKind => rtype,
+                    _ => self.tvar_type(),
+                }
+            },
         }
     }
 
@@ -395,6 +406,18 @@ impl<'sess> LLVM<'sess> {
         LLVMSetLinkage(val, linktype);
     }
 
+    pub unsafe fn build_attributes(&self, function: LLVMValueRef, attrs: &Vec<LLAttr>) {
+        for attr in attrs {
+            let name = match attr {
+                LLAttr::Inline => "alwaysinline",
+                LLAttr::NoInline => "noinline",
+                LLAttr::Cold => "cold",
+            };
+            let kind = LLVMGetEnumAttributeKindForName(cstr(name), name.len());
+            LLVMAddAttributeAtIndex(function, LLVMAttributeFunctionIndex, LLVMCreateEnumAttribute(self.context, kind, 0));
+        }
+    }
+
     pub unsafe fn get_callconv(&self, cc: LLCC) -> LLVMCallConv {
         match cc {
             LLCC::CCC => LLVMCallConv::LLVMCCallConv,
@@ -411,6 +434,29 @@ impl<'sess> LLVM<'sess> {
             LLLit::F64(num) => LLVMConstReal(self.f64_type(), *num),
             LLLit::Null(ltype) => self.null_const(self.build_type(ltype)),
             LLLit::ConstStr(string) => LLVMBuildGlobalStringPtr(self.builder, cstr(string.as_str()), cstr("__string")),
+            LLLit::ConstStruct(items) => {
+                let mut values: Vec<LLVMValueRef> = items.iter().map(|item| self.build_literal(item)).collect();
+                LLVMConstStructInContext(self.context, values.as_mut_ptr(), values.len() as u32, false as i32)
+            },
+        }
+    }
+
+    pub unsafe fn build_constant(&self, lit: &LLLit) -> LLVMValueRef {
+        // Constant initializers can't use the builder, so strings are built as their own private globals
+        match lit {
+            LLLit::ConstStr(string) => {
+                let value = LLVMConstStringInContext(self.context, cstr(string.as_str()), string.len() as u32, false as i32);
+                let global = LLVMAddGlobal(self.module, LLVMTypeOf(value), cstr("__string"));
+                LLVMSetInitializer(global, value);
+                LLVMSetGlobalConstant(global, true as i32);
+                self.build_linkage(global, LLLink::Private);
+                LLVMConstBitCast(global, self.str_type())
+            },
+            LLLit::ConstStruct(items) => {
+                let mut values: Vec<LLVMValueRef> = items.iter().map(|item| self.build_constant(item)).collect();
+                LLVMConstStructInContext(self.context, values.as_mut_ptr(), values.len() as u32, false as i32)
+            },
+            _ => self.build_literal(lit),
         }
     }
 
@@ -451,11 +497,26 @@ impl<'sess> LLVM<'sess> {
     }
 
     pub unsafe fn build_def_local(&self, name: &str, rtype: LLVMTypeRef, initval: LLVMValueRef) -> LLVMValueRef {
-        let pointer = LLVMBuildAlloca(self.builder, rtype, cstr(&name));
+        let pointer = self.build_entry_alloca(name, rtype);
         LLVMBuildStore(self.builder, self.build_cast(rtype, initval), pointer);
         pointer
     }
 
+    pub unsafe fn build_entry_alloca(&self, name: &str, rtype: LLVMTypeRef) -> LLVMValueRef {
+        // Locals are allocated in the entry block, so a block inside of a loop reuses its variables each time through
+        let entry = LLVMGetEntryBasicBlock(*self.curfunc.borrow());
+        let builder = LLVMCreateBuilderInContext(self.context);
+        let first = LLVMGetFirstInstruction(entry);
+        if first == ptr::null_mut() {
+            LLVMPositionBuilderAtEnd(builder, entry);
+        } else {
+            LLVMPositionBuilderBefore(builder, first);
+        }
+        let pointer = LLVMBuildAlloca(builder, rtype, cstr(&name));
+        LLVMDisposeBuilder(builder);
+        pointer
+    }
+
     pub unsafe fn build_load(&self, pointer: LLVMValueRef) -> LLVMValueRef {
         LLVMBuildLoad(self.builder, pointer, cstr(""))
     }
@@ -487,7 +548,7 @@ impl<'sess> LLVM<'sess> {
         for (index, value) in evalues.iter().enumerate() {
             let mut indices = vec!(self.i32_const(0), self.i32_const(index as i32));
             let field = LLVMBuildGEP(self.builder, pointer, indices.as_mut_ptr(), indices.len() as u32, cstr(""));
-            LLVMBuildStore(self.builder, *value, field);
+            LLVMBuildStore(self.builder, self.build_cast(LLVMStructGetTypeAtIndex(rtype, index as u32), *value), field);
         }
         LLVMBuildLoad(self.builder, pointer, cstr(""))
     }
@@ -675,6 +736,7 @@ impl<'sess> LLVM<'sess> {
                 let pred = match cmp {
                     LLCmpType::Equal => llvm_sys::LLVMIntPredicate::LLVMIntEQ,
                     LLCmpType::NotEqual => llvm_sys::LLVMIntPredicate::LLVMIntNE,
+                    LLCmpType::GreaterOrEqual => llvm_sys::LLVMIntPredicate::LLVMIntSGE,
                 };
                 LLVMBuildICmp(self.builder, pred, val1, val2, cstr(""))
             },
@@ -700,6 +762,10 @@ impl<'sess> LLVM<'sess> {
                 },
 
                 LLGlobal::DefGlobal(id, link, name, ltype) => {
+                    if LLVMGetNamedGlobal(self.module, cstring(&name)) != ptr::null_mut() {
+                        self.session.print_error(Error::new(format!("DefError: the symbol {:?} is defined more than once", name)));
+                        continue;
+                    }
                     let rtype = self.build_type(ltype);
                     let global = LLVMAddGlobal(self.module, rtype, cstr(name.as_str()));
                     LLVMSetInitializer(global, self.null_const(rtype));
@@ -707,8 +773,29 @@ impl<'sess> LLVM<'sess> {
                     self.set_value(*id, global);
                 },
 
+                LLGlobal::DeclGlobal(id, name, ltype) => {
+                    // the same module can be imported more than once, so its declarations are shared
+                    let mut global = LLVMGetNamedGlobal(self.module, cstring(&name));
+                    if global == ptr::null_mut() {
+                        let rtype = self.build_type(ltype);
+                        global = LLVMAddGlobal(self.module, rtype, cstr(name.as_str()));
+                        LLVMSetInitializer(global, self.null_const(rtype));
+                        self.build_linkage(global, LLLink::Once);
+                    }
+                    self.set_value(*id, global);
+                },
+
+                LLGlobal::DefConst(id, name, lit) => {
+                    let value = self.build_constant(lit);
+                    let global = LLVMAddGlobal(self.module, LLVMTypeOf(value), cstr(name.as_str()));
+                    LLVMSetInitializer(global, value);
+                    LLVMSetGlobalConstant(global, true as i32);
+                    self.build_linkage(global, LLLink::Private);
+                    self.set_value(*id, global);
+                },
+
                 LLGlobal::DeclCFunc(id, name, ltype, cc) |
-                LLGlobal::DefCFunc(id, _, name, ltype, _, _, cc) => {
+                LLGlobal::DefCFunc(id, _, name, ltype, _, _, cc, _) => {
                     let ftype = self.build_type(ltype);
                     let mut function = LLVMGetNamedFunction(self.module, cstring(&name));
                     if function == ptr::null_mut() {
@@ -753,14 +840,17 @@ impl<'sess> LLVM<'sess> {
     pub unsafe fn build_definitions(&self, globals: &Vec<LLGlobal>) {
         for global in globals {
             match &global {
-                LLGlobal::DefCFunc(id, link, _, _, args, body, _) => {
+                LLGlobal::DefCFunc(id, link, _, _, args, body, _, attrs) => {
                     let function = self.get_value(*id).unwrap();
                     self.build_linkage(function, *link);
+                    self.build_attributes(function, attrs);
                     self.build_cfunc_def(function, args, body);
                 },
 
                 LLGlobal::DefType(_, _, _) |
                 LLGlobal::DefGlobal(_, _, _, _) |
+                LLGlobal::DeclGlobal(_, _, _) |
+                LLGlobal::DefConst(_, _, _) |
                 LLGlobal::DeclCFunc(_, _, _, _) |
                 LLGlobal::DefNamedStruct(_, _, _) |
                 LLGlobal::SetStructBody(_, _, _) => { /* Nothing Needs To Be Done */ }
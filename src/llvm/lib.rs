@@ -75,10 +75,10 @@ pub fn declare_builtins_node<'sess>(session: &Session, scope: ScopeRef, node: &B
             let abi = ftype.as_ref().map(|t| t.get_abi().unwrap()).unwrap_or(ABI::Molten);
             match *func {
                 FuncKind::Function(_) => {
-                    FuncDef::define(session, scope.clone(), *id, Visibility::Private, &Some(String::from(*name)), ftype.clone()).unwrap();
+                    FuncDef::define(session, scope.clone(), *id, Visibility::Private, &Some(String::from(*name)), vec!(), ftype.clone()).unwrap();
                 },
                 _ => {
-                    AnyFunc::define(session, scope.clone(), *id, Visibility::Private, &Some(String::from(*name)), abi, ftype.clone()).unwrap();
+                    AnyFunc::define(session, scope.clone(), *id, Visibility::Private, &Some(String::from(*name)), abi, vec!(), ftype.clone()).unwrap();
                 },
             };
         },
@@ -112,7 +112,7 @@ pub unsafe fn define_builtins_node<'sess>(llvm: &LLVM<'sess>, transformer: &Tran
         BuiltinDef::Func(ref id, ref sname, ref types, ref func) => {
             let ftype = llvm.session.get_type(*id).unwrap();
             let (argtypes, rettype, abi) = ftype.get_function_types().unwrap();
-            let name = abi.mangle_name(sname, argtypes, 2);
+            let name = abi.mangle_name(&vec!(), sname, argtypes, 2);
             let ltype = transformer.transform_func_def_type(abi, &argtypes.as_vec(), rettype);
             match *func {
                 FuncKind::External => {
@@ -188,6 +188,8 @@ unsafe fn declare_irregular_functions(llvm: &LLVM) {
     //declare_c_function(llvm, "printf", &mut [llvm.str_type()], llvm.i64_type(), true);
     declare_c_function(llvm, "sprintf", &mut [llvm.str_type(), llvm.str_type()], llvm.i64_type(), true);
 
+    declare_c_function(llvm, "exit", &mut [llvm.i32_type()], llvm.void_type(), false);
+
     declare_c_function(llvm, "llvm.pow.f64", &mut [llvm.f64_type(), llvm.f64_type()], llvm.f64_type(), false);
 
     declare_c_function(llvm, "setjmp", &mut [llvm.str_type()], llvm.i32_type(), false);
@@ -262,6 +264,7 @@ pub fn get_builtins<'sess>() -> Vec<BuiltinDef<'sess>> {
     vec!(
         BuiltinDef::Class(id(), "()",     vec!(), vec!(), vec!()),
         BuiltinDef::Class(id(), "Nil",    vec!(), vec!(), vec!()),
+        BuiltinDef::Class(id(), "Never",  vec!(), vec!(), vec!()),
         BuiltinDef::Class(id(), "Bool",   vec!(), vec!(), vec!()),
         BuiltinDef::Class(id(), "Byte",   vec!(), vec!(), vec!()),
         BuiltinDef::Class(id(), "Char",   vec!(), vec!(), vec!()),
@@ -282,6 +285,7 @@ pub fn get_builtins<'sess>() -> Vec<BuiltinDef<'sess>> {
         BuiltinDef::Func(id(), "puts",       "(String) -> () / C",              FuncKind::External),
         BuiltinDef::Func(id(), "gets",       "(String) -> String / C",          FuncKind::External),
         BuiltinDef::Func(id(), "strlen",     "(String) -> Int / C",             FuncKind::External),
+        BuiltinDef::Func(id(), "exit",       "(Int) -> Never / C",              FuncKind::FromNamed),
         //BuiltinDef::Func(id(), "sprintf",    "'tmp",                          FuncKind::FromNamed),
         //BuiltinDef::Func(id(), "sprintf2",    "(String, String, '__a1, '__a2) -> () / C", FuncKind::Function(sprintf)),
         BuiltinDef::Func(id(), "sprintf",    "(String, String, '__a1, '__a2) -> () / C", FuncKind::FromNamed),
@@ -372,6 +376,8 @@ pub fn get_builtins<'sess>() -> Vec<BuiltinDef<'sess>> {
         BuiltinDef::Func(id(), "int", "(Char) -> Int / MF",         FuncKind::Function(int_char)),
         BuiltinDef::Func(id(), "int", "(Real) -> Int / MF",         FuncKind::Function(int_real)),
         BuiltinDef::Func(id(), "real", "(Int) -> Real / MF",        FuncKind::Function(real_int)),
+        BuiltinDef::Func(id(), "byte", "(Int) -> Byte / MF",        FuncKind::Function(byte_int)),
+        BuiltinDef::Func(id(), "int", "(Byte) -> Int / MF",         FuncKind::Function(int_byte)),
     )
 }
 
@@ -424,6 +430,8 @@ fn char_int(llvm: &LLVM, args: Vec<LLVMValueRef>) -> LLVMValueRef { unsafe { LLV
 fn int_char(llvm: &LLVM, args: Vec<LLVMValueRef>) -> LLVMValueRef { unsafe { LLVMBuildCast(llvm.builder, llvm::LLVMOpcode::LLVMTrunc, args[0], llvm.i32_type(), cstr("")) } }
 fn int_real(llvm: &LLVM, args: Vec<LLVMValueRef>) -> LLVMValueRef { unsafe { LLVMBuildFPToSI(llvm.builder, args[0], llvm.i64_type(), cstr("")) } }
 fn real_int(llvm: &LLVM, args: Vec<LLVMValueRef>) -> LLVMValueRef { unsafe { LLVMBuildSIToFP(llvm.builder, args[0], llvm.f64_type(), cstr("")) } }
+fn byte_int(llvm: &LLVM, args: Vec<LLVMValueRef>) -> LLVMValueRef { unsafe { LLVMBuildCast(llvm.builder, llvm::LLVMOpcode::LLVMTrunc, args[0], llvm.i8_type(), cstr("")) } }
+fn int_byte(llvm: &LLVM, args: Vec<LLVMValueRef>) -> LLVMValueRef { unsafe { LLVMBuildCast(llvm.builder, llvm::LLVMOpcode::LLVMZExt, args[0], llvm.i64_type(), cstr("")) } }
 
 
 /*
@@ -19,6 +19,7 @@ pub enum LLType {
     Function(Vec<LLType>, R<LLType>),
     Alias(NodeID),
     Largest(Vec<LLType>),
+    Nullable(R<LLType>),
 }
 
 impl LLType {
@@ -60,6 +61,7 @@ pub enum LLLit {
     F64(f64),
     Null(LLType),
     ConstStr(String),
+    ConstStruct(Vec<LLLit>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -73,6 +75,7 @@ pub enum LLRef {
 pub enum LLCmpType {
     Equal,
     NotEqual,
+    GreaterOrEqual,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -88,6 +91,13 @@ pub enum LLCC {
     FastCC,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LLAttr {
+    Inline,
+    NoInline,
+    Cold,
+}
+
 
 pub type LLBlock = Vec<LLExpr>;
 
@@ -127,7 +137,9 @@ pub enum LLGlobal {
     DefType(NodeID, String, LLType),
 
     DefGlobal(NodeID, LLLink, String, LLType),
-    DefCFunc(NodeID, LLLink, String, LLType, Vec<(NodeID, String)>, Vec<LLExpr>, LLCC),
+    DeclGlobal(NodeID, String, LLType),
+    DefConst(NodeID, String, LLLit),
+    DefCFunc(NodeID, LLLink, String, LLType, Vec<(NodeID, String)>, Vec<LLExpr>, LLCC, Vec<LLAttr>),
     DeclCFunc(NodeID, String, LLType, LLCC),
 
     DefNamedStruct(NodeID, String, bool),
@@ -10,15 +10,17 @@ use abi::ABI;
 use defs::Def;
 use types::Type;
 use config::Options;
-use session::Session;
+use session::{ Session, Error };
 use scope::{ Scope, ScopeRef };
-use ast::{ NodeID, Pos, Mutability, Visibility, Literal, Ident, Argument, ClassSpec, MatchCase, Pattern, EnumVariant, AST };
+use ast::{ NodeID, Pos, Mutability, Visibility, Literal, Ident, Argument, ClassSpec, ImportKind, MatchCase, Pattern, EnumVariant, AST };
 
-use defs::functions::{ FuncDef, ClosureDefRef };
+use defs::functions::{ FuncDef, ClosureDefRef, arrange_args };
 use defs::classes::{ ClassDefRef, StructDefRef, Define, Vtable };
+use defs::traits::split_bound_types;
 
 use misc::{ r };
-use llvm::llcode::{ LLType, LLLit, LLRef, LLCmpType, LLLink, LLCC, LLExpr, LLGlobal };
+use constants::ConstValue;
+use llvm::llcode::{ LLType, LLLit, LLRef, LLCmpType, LLLink, LLCC, LLAttr, LLExpr, LLGlobal };
 
 
 static EXCEPTION_POINT_NAME: &str = "__ExceptionPoint__";
@@ -27,6 +29,7 @@ static EXCEPTION_POINT_NAME: &str = "__ExceptionPoint__";
 pub enum CodeContext {
     Func(ABI, NodeID),
     ClassBody,
+    Import,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -54,6 +57,7 @@ impl<'sess> Transformer<'sess> {
 
         self.set_type(global.get_type_def(&"()".to_string()).unwrap(), LLType::I32);
         self.set_type(global.get_type_def(&"Nil".to_string()).unwrap(), LLType::Ptr(r(LLType::I8)));
+        self.set_type(global.get_type_def(&"Never".to_string()).unwrap(), LLType::I32);
         self.set_type(global.get_type_def(&"Bool".to_string()).unwrap(), LLType::I1);
         self.set_type(global.get_type_def(&"Byte".to_string()).unwrap(), LLType::I8);
         self.set_type(global.get_type_def(&"Char".to_string()).unwrap(), LLType::I32);
@@ -115,6 +119,19 @@ impl<'sess> Transformer<'sess> {
         ret
     }
 
+    fn is_importing(&self) -> bool {
+        self.context.borrow().contains(&CodeContext::Import)
+    }
+
+    fn add_shared_global(&self, id: NodeID, name: String, ltype: LLType) {
+        // Globals for imported items are defined in their own module, but the same module can be imported more than once
+        if self.is_importing() {
+            self.add_global(LLGlobal::DeclGlobal(id, name, ltype));
+        } else {
+            self.add_global(LLGlobal::DefGlobal(id, LLLink::Once, name, ltype));
+        }
+    }
+
     fn with_exception<F, R>(&self, expoint: NodeID, f: F) -> R where F: FnOnce() -> R {
         self.expoints.borrow_mut().push(expoint);
         let ret = f();
@@ -170,7 +187,7 @@ impl<'sess> Transformer<'sess> {
         )));
 
         let module_run_name = format!("run_{}", self.session.name.replace(".", "_"));
-        self.add_global(LLGlobal::DefCFunc(run_id, LLLink::Public, module_run_name, run_ltype, fargs, run_body, LLCC::FastCC));
+        self.add_global(LLGlobal::DefCFunc(run_id, LLLink::Public, module_run_name, run_ltype, fargs, run_body, LLCC::FastCC, vec!()));
 
         run_id
     }
@@ -199,7 +216,7 @@ impl<'sess> Transformer<'sess> {
 
         main_body.extend(self.create_exception_block(expoint, try, catch));
 
-        self.add_global(LLGlobal::DefCFunc(main_id, LLLink::Public, String::from("main"), main_ltype, vec!(), main_body, LLCC::CCC));
+        self.add_global(LLGlobal::DefCFunc(main_id, LLLink::Public, String::from("main"), main_ltype, vec!(), main_body, LLCC::CCC, vec!()));
     }
 
     pub fn transform_vec(&self, scope: ScopeRef, code: &Vec<AST>) -> Vec<LLExpr> {
@@ -231,10 +248,17 @@ impl<'sess> Transformer<'sess> {
 
             AST::Nil(id) => vec!(LLExpr::Literal(LLLit::Null(self.transform_value_type(&self.session.get_type(*id).unwrap())))),
 
-            AST::Block(_, _, code) => self.transform_vec(scope.clone(), code),
+            AST::Block(id, _, code) => self.transform_vec(self.session.map.get(id), code),
 
-            AST::Import(_, _, ident, decls) => {
-                self.transform_import(scope.clone(), &ident.name, decls)
+            AST::Import(id, _, ident, kind, decls) => {
+                let mscope = if *kind == ImportKind::All { scope.clone() } else { self.session.map.get(id) };
+                self.transform_import(mscope, &ident.name, decls)
+            },
+
+            AST::Module(id, _, _, body) => {
+                let mut exprs = self.transform_vec(self.session.map.get(id), body);
+                exprs.push(LLExpr::Literal(self.transform_lit(&Literal::Unit)));
+                exprs
             },
 
             AST::Try(_, _, code, cases) => {
@@ -246,23 +270,50 @@ impl<'sess> Transformer<'sess> {
             },
 
 
-            AST::Function(id, _, vis, ident, args, _, body, abi) => {
+            AST::Function(id, _, vis, ident, _, args, _, body, abi) => {
                 self.transform_func_def(scope.clone(), *abi, *id, *vis, ident.as_ref().map(|ident| &ident.name), args, body)
             },
 
-            AST::Declare(id, _, vis, ident, _) => {
+            AST::Declare(id, _, vis, ident, _, _, _) => {
                 let ttype = self.session.get_type(*id).unwrap();
                 let abi = ttype.get_abi().unwrap();
                 self.transform_func_decl(scope.clone(), abi, *id, *vis, &ident.name, &ttype)
             },
 
-            AST::Invoke(id, _, func, args) => {
+            AST::Invoke(_, _, _, _, _) if node.get_nil_comparison().is_some() => {
+                let (equal, value, nil) = node.get_nil_comparison().unwrap();
+                self.transform_nil_comparison(scope.clone(), equal, value, nil)
+            },
+
+            AST::Invoke(id, pos, func, _, args) => {
                 let abi = self.session.get_type(*id).unwrap().get_abi().unwrap();
-                self.transform_func_invoke(scope.clone(), abi, *id, func, args)
+                match self.arrange_invoke_args(*id, args) {
+                    Ok(args) => self.transform_func_invoke(scope.clone(), abi, *id, func, &args),
+                    Err(err) => {
+                        self.session.print_error(err.add_pos(pos));
+                        vec!()
+                    },
+                }
+            },
+
+            AST::Definition(id, _, _, _, ident, _, value) => {
+                self.transform_def_local(scope.clone(), *id, &ident.name, value.as_ref().unwrap())
             },
 
-            AST::Definition(id, _, _, ident, _, value) => {
-                self.transform_def_local(scope.clone(), *id, &ident.name, value)
+            AST::Const(id, _, ident, _, _) => {
+                let value = self.session.get_def(*id).unwrap().as_const().unwrap().get_value().unwrap();
+                self.add_global(LLGlobal::DefConst(*id, ident.name.clone(), self.transform_const(&value)));
+                vec!(LLExpr::GetGlobal(*id))
+            },
+
+            AST::Destructure(id, _, pat, value) => {
+                let mut exprs = vec!();
+                let valexpr = self.transform_as_result(&mut exprs, scope.clone(), value).unwrap();
+                exprs.push(LLExpr::SetValue(*id, r(valexpr)));
+                // The pattern is irrefutable, so the result of matching can be ignored
+                exprs.extend(self.transform_pattern(scope.clone(), pat, *id));
+                exprs.push(LLExpr::GetValue(*id));
+                exprs
             },
 
             AST::GetValue(id) => {
@@ -271,7 +322,15 @@ impl<'sess> Transformer<'sess> {
 
             AST::Identifier(id, _, ident) => {
                 let defid = self.session.get_ref(*id).unwrap();
-                self.transform_reference(scope.clone(), defid, &ident.name)
+                let mut exprs = self.transform_reference(scope.clone(), defid, &ident.name);
+                // A nullable variable that was compared to nil is converted to the non-nullable type it was narrowed to
+                if let (Some(Type::Nullable(_)), Some(ttype)) = (self.session.get_type(defid), self.session.get_type(*id)) {
+                    if !ttype.is_nullable() {
+                        let value = exprs.pop().unwrap();
+                        exprs.push(LLExpr::Cast(self.transform_value_type(&ttype), r(value)));
+                    }
+                }
+                exprs
             },
 
 
@@ -299,11 +358,24 @@ impl<'sess> Transformer<'sess> {
                 self.transform_record_update(scope.clone(), *id, &record, &items)
             },
 
-            AST::Enum(id, _, classspec, _) => {
-                self.transform_enum_def(scope.clone(), *id, &classspec.ident.name)
+            AST::Enum(id, _, classspec, _, body) => {
+                let mut exprs = self.transform_enum_def(scope.clone(), *id, &classspec.ident.name, body);
+                exprs.push(LLExpr::Literal(self.transform_lit(&Literal::Unit)));
+                exprs
             },
 
 
+            AST::Trait(id, _, _, _) => {
+                self.transform_trait_def(*id);
+                vec!(LLExpr::Literal(self.transform_lit(&Literal::Unit)))
+            },
+
+            AST::Impl(id, _, _, _, body) => {
+                let mut exprs = self.transform_impl_def(*id, body);
+                exprs.push(LLExpr::Literal(self.transform_lit(&Literal::Unit)));
+                exprs
+            },
+
             AST::New(id, _, _) => {
                 self.transform_new_object(*id)
             },
@@ -337,12 +409,15 @@ impl<'sess> Transformer<'sess> {
             },
 
 
-            AST::If(_, _, cond, texpr, fexpr) => {
-                self.transform_if_expr(scope.clone(), cond, texpr, fexpr)
+            AST::If(id, _, cond, texpr, fexpr) => {
+                let ltype = self.transform_value_type(&self.session.get_type(*id).unwrap());
+                self.transform_if_expr(scope.clone(), &ltype, cond, texpr, fexpr)
             },
 
-            AST::Match(_, _, cond, cases) => {
-                self.transform_match(scope.clone(), cond, cases)
+            AST::Match(id, _, cond, cases) => {
+                let ltype = self.transform_value_type(&self.session.get_type(*id).unwrap());
+                let fallback = self.create_match_error(scope.clone(), ltype.clone());
+                self.transform_match(scope.clone(), Some(&ltype), cond, cases, Some(fallback))
             },
 
             AST::SideEffect(_, _, ident, args) => {
@@ -357,10 +432,21 @@ impl<'sess> Transformer<'sess> {
 
             AST::TypeAlias(_, _, _, _) => { /* Nothing Needs To Be Done */ vec!() }
 
+            // Macros have already been expanded by the refinery
+            AST::Macro(_, _, _, _, _, _) => vec!(LLExpr::Literal(self.transform_lit(&Literal::Unit))),
+
             _ => panic!("Not Implemented: {:?}", node),
         }
     }
 
+    fn transform_const(&self, value: &ConstValue) -> LLLit {
+        match value {
+            ConstValue::Literal(lit) => self.transform_lit(lit),
+            ConstValue::Tuple(items) => LLLit::ConstStruct(items.iter().map(|item| self.transform_const(item)).collect()),
+            ConstValue::Record(items) => LLLit::ConstStruct(items.iter().map(|(_, item)| self.transform_const(item)).collect()),
+        }
+    }
+
     fn transform_lit(&self, lit: &Literal) -> LLLit {
         match lit {
             Literal::Unit => LLLit::I32(0),
@@ -370,6 +456,8 @@ impl<'sess> Transformer<'sess> {
             Literal::Real(num) => LLLit::F64(*num),
             // TODO not sure how you'll do strings yet.... maybe it shouldn't even be a literal here
             Literal::String(string) => LLLit::ConstStr(string.clone()),
+            // byte strings are always desugared by the refinery, which runs before the transform
+            Literal::ByteString(_, _) => unreachable!(),
         }
     }
 
@@ -402,7 +490,7 @@ impl<'sess> Transformer<'sess> {
         exprs
     }
 
-    fn transform_enum_def(&self, scope: ScopeRef, id: NodeID, name: &String) -> Vec<LLExpr> {
+    fn transform_enum_def(&self, scope: ScopeRef, id: NodeID, name: &String, body: &Vec<AST>) -> Vec<LLExpr> {
         let selector = LLType::I8;
         let enumdef = self.session.get_def(id).unwrap().as_enum().unwrap();
 
@@ -412,19 +500,35 @@ impl<'sess> Transformer<'sess> {
         let mut types = vec!();
         for (i, variant) in enumdef.variants.borrow().iter().enumerate() {
             let name = format!("{}_{}", name, variant.ident.name);
-            self.transform_enum_variant(variant.id, i as i8, name, selector.clone(), variant.ttype.clone());
+            self.transform_enum_variant(id, variant.id, i as i8, name, selector.clone(), variant.ttype.clone());
             if variant.ttype.is_some() {
-                types.push(self.transform_value_type(variant.ttype.as_ref().unwrap()));
+                types.push(self.transform_enum_payload_type(id, variant.ttype.as_ref().unwrap()));
             }
         }
 
         self.add_global(LLGlobal::SetStructBody(id, vec!(selector.clone(), LLType::Largest(types)), false));
-        vec!()
+
+        // Functions on an enum are called directly rather than through a vtable
+        let mut exprs = vec!();
+        let tscope = self.session.map.get(&id);
+        for node in body {
+            match node {
+                AST::Function(id, _, _, ident, _, args, _, body, abi) => {
+                    exprs.extend(self.transform_func_def(tscope.clone(), *abi, *id, Visibility::Public, ident.as_ref().map(|ident| &ident.name), args, body));
+                },
+                AST::Declare(id, _, _, ident, _, _, _) => {
+                    let ttype = self.session.get_type(*id).unwrap();
+                    exprs.extend(self.transform_func_decl(tscope.clone(), ttype.get_abi().unwrap(), *id, Visibility::Public, &ident.name, &ttype));
+                },
+                _ => panic!("Not Implemented: {:?}", node),
+            }
+        }
+        exprs
     }
 
-    fn transform_enum_variant(&self, id: NodeID, variant: i8, name: String, selector: LLType, ttype: Option<Type>) {
+    fn transform_enum_variant(&self, enum_id: NodeID, id: NodeID, variant: i8, name: String, selector: LLType, ttype: Option<Type>) {
         let struct_id = NodeID::generate();
-        self.create_enum_struct(struct_id, name.clone(), selector, ttype.clone().map(|t| self.transform_value_type(&t)));
+        self.create_enum_struct(struct_id, name.clone(), selector, ttype.clone().map(|t| self.transform_enum_payload_type(enum_id, &t)));
         self.set_type(id, LLType::Alias(struct_id));
 
         if ttype.is_some() {
@@ -436,16 +540,55 @@ impl<'sess> Transformer<'sess> {
             let mut tuple_items = vec!();
             for (i, arg) in argtypes.as_vec().iter().enumerate() {
                 let arg_id = NodeID::generate();
-                tuple_items.push(LLExpr::GetValue(arg_id));
+                tuple_items.push(self.box_enum_value(enum_id, arg, LLExpr::GetValue(arg_id)));
                 params.push((arg_id, format!("value{}", i)));
             }
 
-            let tuple_id = NodeID::generate();
             let body = vec!(LLExpr::DefStruct(NodeID::generate(), self.get_type(struct_id).unwrap(), vec!(
                 LLExpr::Literal(LLLit::I8(variant as i8)),
-                LLExpr::DefStruct(NodeID::generate(), self.transform_value_type(argtypes), tuple_items)
+                LLExpr::DefStruct(NodeID::generate(), self.transform_enum_payload_type(enum_id, argtypes), tuple_items)
             )));
-            self.add_global(LLGlobal::DefCFunc(id, LLLink::Once, name, lftype, params, body, LLCC::CCC));
+            self.add_global(LLGlobal::DefCFunc(id, LLLink::Once, name, lftype, params, body, LLCC::CCC, vec!()));
+        }
+    }
+
+    fn transform_enum_payload_type(&self, enum_id: NodeID, ttype: &Type) -> LLType {
+        // An enum that contains itself would have an infinite size, so those values are boxed instead
+        match ttype {
+            Type::Object(_, id, _) if *id == enum_id => LLType::Ptr(r(LLType::Alias(enum_id))),
+            Type::Tuple(items) => LLType::Struct(items.iter().map(|item| self.transform_enum_payload_type(enum_id, item)).collect()),
+            Type::Record(items) => LLType::Struct(items.iter().map(|item| self.transform_enum_payload_type(enum_id, &item.1)).collect()),
+            _ => self.transform_value_type(ttype),
+        }
+    }
+
+    fn box_enum_value(&self, enum_id: NodeID, ttype: &Type, value: LLExpr) -> LLExpr {
+        match ttype {
+            Type::Object(_, id, _) if *id == enum_id => LLExpr::AllocRef(NodeID::generate(), LLType::Ptr(r(LLType::Alias(enum_id))), Some(r(value))),
+            Type::Tuple(_) | Type::Record(_) if self.contains_enum(enum_id, ttype) => {
+                let items = ttype.get_item_types().iter().enumerate().map(|(i, item)| self.box_enum_value(enum_id, item, LLExpr::GetItem(r(value.clone()), i))).collect();
+                LLExpr::DefStruct(NodeID::generate(), self.transform_enum_payload_type(enum_id, ttype), items)
+            },
+            _ => value,
+        }
+    }
+
+    fn unbox_enum_value(&self, enum_id: NodeID, ttype: &Type, value: LLExpr) -> LLExpr {
+        match ttype {
+            Type::Object(_, id, _) if *id == enum_id => LLExpr::LoadRef(r(value)),
+            Type::Tuple(_) | Type::Record(_) if self.contains_enum(enum_id, ttype) => {
+                let items = ttype.get_item_types().iter().enumerate().map(|(i, item)| self.unbox_enum_value(enum_id, item, LLExpr::GetItem(r(value.clone()), i))).collect();
+                LLExpr::DefStruct(NodeID::generate(), self.transform_value_type(ttype), items)
+            },
+            _ => value,
+        }
+    }
+
+    fn contains_enum(&self, enum_id: NodeID, ttype: &Type) -> bool {
+        match ttype {
+            Type::Object(_, id, _) => *id == enum_id,
+            Type::Tuple(_) | Type::Record(_) => ttype.get_item_types().iter().any(|item| self.contains_enum(enum_id, item)),
+            _ => false,
         }
     }
 
@@ -476,7 +619,7 @@ impl<'sess> Transformer<'sess> {
         let rftype = LLType::Function(vec!(), r(LLType::I64));
         self.add_global(LLGlobal::DeclCFunc(rid, module_run_name, rftype, LLCC::FastCC));
         exprs.extend(self.create_cfunc_invoke(LLExpr::GetValue(rid), vec!()));
-        exprs.extend(self.transform_vec(scope.clone(), decls));
+        exprs.extend(self.with_context(CodeContext::Import, || self.transform_vec(scope.clone(), decls)));
         exprs
     }
 
@@ -495,7 +638,7 @@ impl<'sess> Transformer<'sess> {
 
         let exret_id = NodeID::generate();
         exprs.push(LLExpr::SetValue(exret_id, r(LLExpr::GetItem(r(LLExpr::GetLocal(exp_id)), 1))));
-        let matchblock = self.transform_match(scope.clone(), &AST::GetValue(exret_id), cases);
+        let matchblock = self.transform_match(scope.clone(), None, &AST::GetValue(exret_id), cases, None);
 
         exprs.extend(self.create_exception_block(LLExpr::GetValue(expoint_id), tryblock, matchblock));
         exprs
@@ -518,13 +661,39 @@ impl<'sess> Transformer<'sess> {
 
     fn transform_raise(&self, scope: ScopeRef, id: NodeID, valexpr: &AST) -> Vec<LLExpr> {
         let mut exprs = vec!();
-        let exp_id = self.get_exception().unwrap();
         let value = self.transform_as_result(&mut exprs, scope.clone(), valexpr).unwrap();
+        exprs.extend(self.create_raise(value));
+        exprs.push(LLExpr::Literal(LLLit::I32(0)));
+        exprs
+    }
 
+    fn create_raise(&self, value: LLExpr) -> Vec<LLExpr> {
+        let mut exprs = vec!();
+        let exp_id = self.get_exception().unwrap();
         exprs.push(LLExpr::StoreRef(r(LLExpr::AccessRef(r(LLExpr::GetValue(exp_id)), vec!(LLRef::Field(1)))), r(LLExpr::Cast(LLType::Var, r(value)))));
         exprs.push(LLExpr::CallC(r(LLExpr::GetNamed("longjmp".to_string())), vec!(LLExpr::GetValue(exp_id), LLExpr::Literal(LLLit::I32(1))), LLCC::CCC));
+        exprs
+    }
 
-        exprs.push(LLExpr::Literal(LLLit::I32(0)));
+    fn create_match_error(&self, scope: ScopeRef, ltype: LLType) -> Vec<LLExpr> {
+        // If no case matches, then raise a MatchError rather than returning a null value
+        let mut exprs = vec!();
+        let value = match scope.find_type_def(self.session, &String::from("MatchError")) {
+            Ok(Def::Class(classdef)) => {
+                let id = NodeID::generate();
+                let otype = self.session.get_type(classdef.id).unwrap();
+                exprs.push(LLExpr::AllocRef(id, self.transform_value_type(&otype), None));
+                if let Some(index) = classdef.get_struct_vtable_index() {
+                    exprs.push(LLExpr::StoreRef(r(LLExpr::AccessRef(r(LLExpr::GetValue(id)), vec!(LLRef::Field(index)))), r(LLExpr::GetLocal(classdef.vtable.id))));
+                }
+                let stype = scope.find_type(self.session, &String::from("String")).unwrap();
+                exprs.extend(self.create_method_invoke(scope.clone(), &otype, "new", vec!(stype), vec!(LLExpr::GetValue(id), LLExpr::Literal(LLLit::ConstStr(String::from("MatchError: no case matched the value"))))));
+                LLExpr::GetValue(id)
+            },
+            _ => LLExpr::Literal(LLLit::Null(LLType::Var)),
+        };
+        exprs.extend(self.create_raise(value));
+        exprs.push(LLExpr::Literal(LLLit::Null(ltype)));
         exprs
     }
 
@@ -532,9 +701,26 @@ impl<'sess> Transformer<'sess> {
 
 
 
+    fn transform_attributes(&self, id: NodeID) -> Vec<LLAttr> {
+        let mut attrs = vec!();
+        if self.session.get_attribute(id, "inline").is_some() {
+            attrs.push(LLAttr::Inline);
+        }
+        if self.session.get_attribute(id, "noinline").is_some() {
+            attrs.push(LLAttr::NoInline);
+        }
+        if self.session.get_attribute(id, "cold").is_some() {
+            attrs.push(LLAttr::Cold);
+        }
+        attrs
+    }
+
     fn transform_func_name(&self, scope: ScopeRef, name: Option<&String>, id: NodeID) -> String {
+        if let Some(name) = self.session.get_attribute(id, "export_name").and_then(|attr| attr.get_string_arg(0)) {
+            return name;
+        }
         let ftype = self.session.get_type(id).unwrap();
-        scope.get_full_name(name.map(|name| ftype.get_abi().unwrap_or(ABI::Molten).mangle_name(name, ftype.get_argtypes().unwrap(), 2)), id)
+        scope.get_full_name(name.map(|name| ftype.get_abi().unwrap_or(ABI::Molten).mangle_name(&scope.get_module_path(), name, ftype.get_argtypes().unwrap(), 2)), id)
     }
 
     pub fn transform_func_def_type(&self, abi: ABI, args: &Vec<Type>, ret: &Type) -> LLType {
@@ -569,6 +755,26 @@ impl<'sess> Transformer<'sess> {
         }
     }
 
+    fn arrange_invoke_args(&self, id: NodeID, args: &Vec<AST>) -> Result<Vec<AST>, Error> {
+        // Put keyword arguments in order, and fill in the default values of any omitted arguments, which were copied when the call was checked
+        let params = self.session.get_ref(id).ok().and_then(|defid| self.session.get_def(defid).ok()).and_then(|def| def.get_caller_args(self.session)).unwrap_or(vec!());
+        let values = args.iter().map(|arg| match arg {
+            AST::Keyword(_, _, _, expr) => Ok(*expr.clone()),
+            _ => Ok(arg.clone()),
+        }).collect();
+        let arranged = arrange_args(&params, args, values, |param| {
+            self.session.get_default_arg(id, param.id).ok_or(Error::new(format!("InternalError: the default value of {:?} wasn't checked for this call", param.ident.name)))
+        })?;
+        let mut arranged = arranged.into_iter().collect::<Result<Vec<AST>, Error>>()?;
+
+        // The trait arguments for the function's bounds come after the arguments given by the caller
+        let atypes = self.session.get_type(id).ok_or(Error::new(format!("TypeError: no type set for id {:?}", id)))?.get_argtypes()?.as_vec();
+        if split_bound_types(self.session, atypes).1.len() > 0 {
+            arranged.extend(self.session.get_trait_args(id).ok_or(Error::new(format!("InternalError: the trait arguments weren't resolved for this call")))?);
+        }
+        Ok(arranged)
+    }
+
     fn create_func_invoke(&self, abi: ABI, func: LLExpr, fargs: Vec<LLExpr>) -> Vec<LLExpr> {
         match abi {
             ABI::C | ABI::MoltenFunc => self.create_cfunc_invoke(func, fargs),
@@ -590,6 +796,18 @@ impl<'sess> Transformer<'sess> {
                         exprs.extend(self.convert_accessor(defid, LLExpr::GetValue(*oid), &ident.name, otype));
                         exprs.pop().unwrap()
                     },
+                    Type::Variable(_, vid, _) => {
+                        // Methods of a trait bound are loaded from the trait argument passed to the function
+                        let defid = self.session.get_ref(*id).unwrap();
+                        let (traitid, argid, name) = self.session.get_bounds(vid).into_iter().find(|(traitid, _, _)| {
+                            self.session.get_def(*traitid).unwrap().as_trait().unwrap().get_method(&ident.name).map(|(_, mid)| mid == defid).unwrap_or(false)
+                        }).unwrap();
+                        let (index, _) = self.session.get_def(traitid).unwrap().as_trait().unwrap().get_method(&ident.name).unwrap();
+                        let mut dexprs = self.transform_reference(scope.clone(), argid, &name);
+                        let dict = dexprs.pop().unwrap();
+                        exprs.extend(dexprs);
+                        LLExpr::LoadRef(r(LLExpr::AccessRef(r(dict), vec!(LLRef::Field(index)))))
+                    },
                     _ => self.transform_as_result(exprs, scope.clone(), func).unwrap(),
                 }
             },
@@ -600,6 +818,7 @@ impl<'sess> Transformer<'sess> {
     fn transform_vis(&self, vis: Visibility) -> LLLink {
         match vis {
             Visibility::Private => LLLink::Private,
+            Visibility::Protected |
             Visibility::Public => LLLink::Public,
         }
     }
@@ -646,7 +865,7 @@ impl<'sess> Transformer<'sess> {
         let fargs = self.transform_cfunc_def_args(args);
 
         self.with_context(CodeContext::Func(ABI::C, id), || {
-            self.add_global(LLGlobal::DefCFunc(id, self.transform_vis(vis), fname, lftype, fargs, self.transform_node(fscope.clone(), body), LLCC::CCC));
+            self.add_global(LLGlobal::DefCFunc(id, self.transform_vis(vis), fname, lftype, fargs, self.transform_node(fscope.clone(), body), LLCC::CCC, self.transform_attributes(id)));
         });
         vec!(LLExpr::GetValue(id))
     }
@@ -716,7 +935,7 @@ impl<'sess> Transformer<'sess> {
 
         self.with_context(CodeContext::Func(ABI::MoltenFunc, id), || {
             self.with_exception(exp_id, || {
-                self.add_global(LLGlobal::DefCFunc(id, self.transform_vis(vis), fname, lftype, fargs, self.transform_node(fscope.clone(), body), LLCC::FastCC));
+                self.add_global(LLGlobal::DefCFunc(id, self.transform_vis(vis), fname, lftype, fargs, self.transform_node(fscope.clone(), body), LLCC::FastCC, self.transform_attributes(id)));
             });
         });
         vec!(LLExpr::GetValue(id))
@@ -792,7 +1011,7 @@ impl<'sess> Transformer<'sess> {
         let fname = self.transform_func_name(scope.clone(), Some(name), id);
 
         let did = NodeID::generate();
-        self.add_global(LLGlobal::DefGlobal(did, LLLink::Once, fname.clone(), self.transform_value_type(ttype)));
+        self.add_global(LLGlobal::DeclGlobal(did, fname.clone(), self.transform_value_type(ttype)));
         vec!(LLExpr::SetValue(id, r(LLExpr::GetLocal(did))))
     }
 
@@ -823,13 +1042,13 @@ impl<'sess> Transformer<'sess> {
                 self.transform_node(fscope.clone(), body)
             })
         });
-        self.insert_global(index, LLGlobal::DefCFunc(cfid, self.transform_vis(vis), cfname.clone(), cftype, fargs, body, LLCC::FastCC));
+        self.insert_global(index, LLGlobal::DefCFunc(cfid, self.transform_vis(vis), cfname.clone(), cftype, fargs, body, LLCC::FastCC, self.transform_attributes(id)));
 
         let structtype = LLType::Ptr(r(self.transform_struct_def(&cl.context_struct)));
         self.insert_global(index, LLGlobal::DefType(cl.context_type_id, format!("__context_{}__", cl.context_type_id), structtype.clone()));
 
 
-        FuncDef::define(self.session, scope.clone(), cfid, cl.vis, &Some(cfname.clone()), Some(ptype)).unwrap();
+        FuncDef::define(self.session, scope.clone(), cfid, cl.vis, &Some(cfname.clone()), vec!(), Some(ptype)).unwrap();
         let mut fields = vec!();
         cl.context_struct.foreach_field(|defid, field, _| {
             let rid = NodeID::generate();
@@ -843,13 +1062,13 @@ impl<'sess> Transformer<'sess> {
 
         let mut code = vec!();
         let did = NodeID::generate();
-        code.push(AST::Definition(did, Pos::empty(), Mutability::Mutable, Ident::new(fname.clone()), None, r(AST::make_ref(Pos::empty(), AST::make_record(Pos::empty(), fields)))));
+        code.push(AST::Definition(did, Pos::empty(), Visibility::Private, Mutability::Mutable, Ident::new(fname.clone()), None, Some(r(AST::make_ref(Pos::empty(), AST::make_record(Pos::empty(), fields))))));
         // TODO I'm going back on my decision to use a tuple pair to represent the function and context reference because it can't be converted to i8* (the generics type)
         //      Once I have generics that can operate on different sized data instead of only references, I can switch back
         //code.push(AST::Tuple(NodeID::generate(), Pos::empty(), vec!(AST::make_ident_from_str(Pos::empty(), real_fname.as_str()), AST::make_ident(Pos::empty(), Ident::new(cname.clone())))));
 
         binding::NameBinder::bind_names(self.session, scope.clone(), &code);
-        typecheck::TypeChecker::check(self.session, scope.clone(), &code);
+        typecheck::TypeChecker::check(self.session, scope.clone(), &code, None);
         let mut exprs = self.transform_vec(scope.clone(), &code);
         exprs.push(LLExpr::SetValue(id, r(LLExpr::GetLocal(did))));
         exprs.push(LLExpr::GetValue(id));
@@ -893,14 +1112,25 @@ impl<'sess> Transformer<'sess> {
     fn create_reference(&self, defid: NodeID) -> Vec<LLExpr> {
         match self.session.get_def(defid) {
             Ok(Def::Var(_)) => vec!(LLExpr::GetLocal(defid)),
+            Ok(Def::Const(_)) |
+            Ok(Def::Impl(_)) => vec!(LLExpr::GetGlobal(defid)),
             Ok(_) => vec!(LLExpr::GetValue(defid)),
             Err(_) => panic!("TransformError: attempting to reference a non-existent value"),
         }
     }
 
     fn transform_reference(&self, scope: ScopeRef, defid: NodeID, name: &String) -> Vec<LLExpr> {
+        // A name in the current context can still refer to an outer definition (eg. a free name in a macro body)
+        let in_context = scope.contains_context(name) && match scope.get_var_def(name) {
+            Some(found) if found != defid => match self.session.get_def(found) {
+                Ok(Def::Overload(ol)) => ol.get_variants(self.session).contains(&defid),
+                _ => false,
+            },
+            _ => true,
+        };
+
         if
-            !scope.contains_context(name)
+            !in_context
             && !Scope::global(scope.clone()).contains(name)
             && !self.session.get_def(defid).unwrap().is_globally_accessible()
         {
@@ -986,7 +1216,7 @@ impl<'sess> Transformer<'sess> {
         let mut exprs = vec!();
         let tscope = self.session.map.get(&classdef.id);
 
-        self.add_global(LLGlobal::DefGlobal(classdef.vtable.id, LLLink::Once, format!("__{}_vtable", tscope.get_basename()), self.get_type(classdef.vtable.id).unwrap()));
+        self.add_shared_global(classdef.vtable.id, format!("__{}_vtable", tscope.get_basename()), self.get_type(classdef.vtable.id).unwrap());
         // TODO should vtables be dynamically allocated, or should we add a LLType::ElementOf() type or something to GetElement an aliased type
         exprs.push(LLExpr::SetGlobal(classdef.vtable.id, r(LLExpr::AllocRef(NodeID::generate(), self.get_type(classdef.vtable.id).unwrap(), None))));
         classdef.vtable.foreach_enumerated(|i, id, _, ttype| {
@@ -1008,15 +1238,17 @@ impl<'sess> Transformer<'sess> {
 
         for node in body {
             match node {
-                AST::Function(id, _, vis, ident, args, _, body, abi) => {
+                // Member visibility is checked by the typechecker, but methods are always linked publicly so that
+                // vtables and subclasses in other modules can refer to them
+                AST::Function(id, _, _, ident, _, args, _, body, abi) => {
                     // TODO i switched to using scope here instead of tscope because it was causing problems with references inside closures
-                    exprs.extend(self.transform_func_def(tscope.clone(), *abi, *id, *vis, ident.as_ref().map(|ident| &ident.name), args, body));
+                    exprs.extend(self.transform_func_def(tscope.clone(), *abi, *id, Visibility::Public, ident.as_ref().map(|ident| &ident.name), args, body));
                 },
-                AST::Declare(id, _, vis, ident, _) => {
+                AST::Declare(id, _, _, ident, _, _, _) => {
                     let ttype = self.session.get_type(*id).unwrap();
-                    exprs.extend(self.transform_func_decl(tscope.clone(), ttype.get_abi().unwrap(), *id, *vis, &ident.name, &ttype));
+                    exprs.extend(self.transform_func_decl(tscope.clone(), ttype.get_abi().unwrap(), *id, Visibility::Public, &ident.name, &ttype));
                 },
-                AST::Definition(_, _, _, _, _, _) => { },
+                AST::Definition(_, _, _, _, _, _, _) => { },
                 _ => panic!("Not Implemented: {:?}", node),
             }
         }
@@ -1039,6 +1271,9 @@ impl<'sess> Transformer<'sess> {
             Type::Object(_, objid, _) => {
                 let objdef = self.session.get_def(objid).unwrap();
                 match self.session.get_def(defid) {
+                    Ok(Def::Method(_)) if objdef.as_enum().is_ok() => {
+                        exprs.push(LLExpr::GetValue(defid));
+                    },
                     Ok(Def::Method(_)) => {
                         let classdef = objdef.as_class().unwrap();
                         let vindex = classdef.get_struct_vtable_index().unwrap();
@@ -1050,6 +1285,14 @@ impl<'sess> Transformer<'sess> {
                         let (index, _) = objdef.as_struct().unwrap().find_field_by_id(defid).unwrap();
                         exprs.push(LLExpr::LoadRef(r(LLExpr::AccessRef(r(objval), vec!(LLRef::Field(index))))));
                     },
+                    Ok(Def::Closure(_)) if self.find_impl_of_method(objid, defid).is_some() => {
+                        // Trait methods implemented for the type are loaded from the impl's table of methods
+                        let (implid, index, methodid) = self.find_impl_of_method(objid, defid).unwrap();
+                        let entrytype = self.transform_impl_entry_type(methodid, defid);
+                        let entry = LLExpr::LoadRef(r(LLExpr::AccessRef(r(LLExpr::GetGlobal(implid)), vec!(LLRef::Field(index)))));
+                        let entry = LLExpr::Cast(LLType::Ptr(r(entrytype)), r(entry));
+                        exprs.push(LLExpr::LoadRef(r(LLExpr::AccessRef(r(entry), vec!(LLRef::Field(1))))));
+                    },
                     Err(_) => {
                         return vec!(LLExpr::GetValue(defid));
                     },
@@ -1069,6 +1312,99 @@ impl<'sess> Transformer<'sess> {
         exprs
     }
 
+    fn find_impl_of_method(&self, typeid: NodeID, defid: NodeID) -> Option<(NodeID, usize, NodeID)> {
+        for implid in self.session.get_impls(typeid) {
+            let impldef = self.session.get_def(implid).ok()?.as_impl().ok()?;
+            let traitdef = self.session.get_def(impldef.traitid).ok()?.as_trait().ok()?;
+            for (index, (name, methodid)) in traitdef.methods.borrow().iter().enumerate() {
+                if impldef.vars.get_var_def(name) == Some(defid) {
+                    return Some((implid, index, *methodid));
+                }
+            }
+        }
+        None
+    }
+
+    fn transform_impl_entry_type(&self, methodid: NodeID, implid: NodeID) -> LLType {
+        // Each entry is a closure of the trait's method which holds the impl's method as its context
+        let ttype = self.session.get_type(methodid).unwrap();
+        let (argtypes, rettype, _) = ttype.get_function_types().unwrap();
+        let ftype = self.transform_closure_def_type(&argtypes.as_vec(), rettype);
+        LLType::Struct(vec!(LLType::Ptr(r(ftype)), self.transform_value_type(&self.session.get_type(implid).unwrap())))
+    }
+
+    fn transform_impl_entry_func(&self, name: String, methodid: NodeID, implid: NodeID) -> NodeID {
+        // The trait's methods take and return generic values, which are converted to and from the impl method's types when it's called
+        let ttype = self.session.get_type(methodid).unwrap();
+        let (argtypes, rettype, _) = ttype.get_function_types().unwrap();
+        let ftype = self.transform_closure_def_type(&argtypes.as_vec(), rettype);
+
+        let exp_id = NodeID::generate();
+        let context_id = NodeID::generate();
+        let mut fargs: Vec<(NodeID, String)> = argtypes.as_vec().iter().enumerate().map(|(i, _)| (NodeID::generate(), format!("arg{}", i))).collect();
+        let values = fargs.iter().map(|(id, _)| LLExpr::GetValue(*id)).collect();
+        fargs.push((context_id, String::from("__context__")));
+        fargs.push((exp_id, String::from(EXCEPTION_POINT_NAME)));
+
+        let entrytype = self.transform_impl_entry_type(methodid, implid);
+        let context = LLExpr::Cast(LLType::Ptr(r(entrytype)), r(LLExpr::GetValue(context_id)));
+        let method = LLExpr::LoadRef(r(LLExpr::AccessRef(r(context), vec!(LLRef::Field(1)))));
+        let body = self.with_exception(exp_id, || self.create_closure_invoke(method, values));
+
+        let id = NodeID::generate();
+        self.add_global(LLGlobal::DefCFunc(id, LLLink::Private, name, ftype, fargs, body, LLCC::FastCC, vec!()));
+        id
+    }
+
+    fn transform_trait_def(&self, id: NodeID) {
+        let traitdef = self.session.get_def(id).unwrap().as_trait().unwrap();
+
+        // A trait is a table of its methods, and each impl creates a global table for its type
+        self.add_global(LLGlobal::DefNamedStruct(id, traitdef.get_name(), true));
+        self.set_type(id, LLType::Alias(id));
+
+        let items = traitdef.methods.borrow().iter().map(|(_, methodid)| self.transform_value_type(&self.session.get_type(*methodid).unwrap())).collect();
+        self.add_global(LLGlobal::SetStructBody(id, items, true));
+    }
+
+    fn transform_impl_def(&self, id: NodeID, body: &Vec<AST>) -> Vec<LLExpr> {
+        let mut exprs = vec!();
+        let tscope = self.session.map.get(&id);
+        let impldef = self.session.get_def(id).unwrap().as_impl().unwrap();
+        let traitdef = self.session.get_def(impldef.traitid).unwrap().as_trait().unwrap();
+
+        // Methods are always linked publicly so that impls declared in other modules can refer to them
+        for node in body {
+            match node {
+                AST::Function(id, _, _, ident, _, args, _, body, abi) => {
+                    exprs.extend(self.transform_func_def(tscope.clone(), *abi, *id, Visibility::Public, ident.as_ref().map(|ident| &ident.name), args, body));
+                },
+                AST::Declare(id, _, _, ident, _, _, _) => {
+                    let ttype = self.session.get_type(*id).unwrap();
+                    exprs.extend(self.transform_func_decl(tscope.clone(), ttype.get_abi().unwrap(), *id, Visibility::Public, &ident.name, &ttype));
+                },
+                _ => panic!("Not Implemented: {:?}", node),
+            }
+        }
+
+        let ltype = self.get_type(traitdef.id).unwrap();
+        self.add_shared_global(id, format!("__{}_impl", tscope.get_basename()), ltype.clone());
+        exprs.push(LLExpr::SetGlobal(id, r(LLExpr::AllocRef(NodeID::generate(), ltype, None))));
+        for (index, (name, methodid)) in traitdef.methods.borrow().iter().enumerate() {
+            let implid = impldef.vars.get_var_def(name).unwrap();
+            let funcid = self.transform_impl_entry_func(format!("__{}_impl_{}", tscope.get_basename(), name), *methodid, implid);
+
+            let entrytype = self.transform_impl_entry_type(*methodid, implid);
+            let implcl = LLExpr::Cast(self.transform_value_type(&self.session.get_type(implid).unwrap()), r(LLExpr::GetValue(implid)));
+            let entry = LLExpr::AllocRef(NodeID::generate(), LLType::Ptr(r(entrytype.clone())), Some(r(LLExpr::DefStruct(NodeID::generate(), entrytype, vec!(LLExpr::GetValue(funcid), implcl)))));
+
+            let ltype = self.transform_value_type(&self.session.get_type(*methodid).unwrap());
+            let field = LLExpr::AccessRef(r(LLExpr::GetGlobal(id)), vec!(LLRef::Field(index)));
+            exprs.push(LLExpr::StoreRef(r(field), r(LLExpr::Cast(ltype, r(entry)))));
+        }
+        exprs
+    }
+
     fn transform_resolve(&self, id: NodeID, path: &AST, field: &String, otype: Type) -> Vec<LLExpr> {
         let defid = self.session.get_ref(id).unwrap();
         match self.session.get_def(otype.get_id().unwrap()).unwrap() {
@@ -1079,13 +1415,15 @@ impl<'sess> Transformer<'sess> {
             Def::Enum(enumdef) => {
                 match enumdef.get_variant_type_by_id(defid) {
                     Some(_) => vec!(LLExpr::GetValue(defid)),
+                    None if enumdef.get_variant_by_id(defid).is_none() => vec!(LLExpr::GetValue(defid)),
                     None => {
                         let variant = enumdef.get_variant_by_id(defid).unwrap();
                         vec!(LLExpr::DefStruct(id, self.get_type(defid).unwrap(), vec!(LLExpr::Literal(LLLit::I8(variant as i8)))))
                     },
                 }
             },
-            def @ _ => panic!("DefError: expected class or enum but found {:?}", def),
+            Def::Module(_) => self.create_reference(defid),
+            def @ _ => panic!("DefError: expected class, enum, or module but found {:?}", def),
         }
     }
 
@@ -1116,19 +1454,53 @@ impl<'sess> Transformer<'sess> {
 
 
 
-    fn transform_if_expr(&self, scope: ScopeRef, cond: &AST, texpr: &AST, fexpr: &AST) -> Vec<LLExpr> {
+    fn transform_nil_comparison(&self, scope: ScopeRef, equal: bool, value: &AST, nil: &AST) -> Vec<LLExpr> {
+        let mut exprs = vec!();
+        let valexpr = self.transform_as_result(&mut exprs, scope.clone(), value).unwrap();
+        let ltype = self.transform_value_type(&self.session.get_type(nil.get_id()).unwrap());
+        let cmp = if equal { LLCmpType::Equal } else { LLCmpType::NotEqual };
+        exprs.push(LLExpr::Cmp(cmp, r(LLExpr::Cast(ltype.clone(), r(valexpr))), r(LLExpr::Literal(LLLit::Null(ltype)))));
+        exprs
+    }
+
+    fn transform_if_expr(&self, scope: ScopeRef, ltype: &LLType, cond: &AST, texpr: &AST, fexpr: &AST) -> Vec<LLExpr> {
         let mut conds = vec!();
         conds.push(self.transform_node(scope.clone(), cond));
         conds.push(vec!(LLExpr::Literal(LLLit::I1(true))));
 
         let mut blocks = vec!();
-        blocks.push(self.transform_node(scope.clone(), texpr));
-        blocks.push(self.transform_node(scope.clone(), fexpr));
+        blocks.push(self.transform_branch(scope.clone(), Some(ltype), texpr));
+        blocks.push(self.transform_branch(scope.clone(), Some(ltype), fexpr));
 
         vec!(LLExpr::Phi(conds, blocks))
     }
 
-    fn transform_match(&self, scope: ScopeRef, cond: &AST, cases: &Vec<MatchCase>) -> Vec<LLExpr> {
+    fn transform_branch(&self, scope: ScopeRef, ltype: Option<&LLType>, node: &AST) -> Vec<LLExpr> {
+        let mut exprs = self.transform_node(scope.clone(), node);
+        // A branch that never returns still needs a value of the same type as the other branches, even though it's never used
+        if let Some(ltype) = ltype {
+            if self.is_never(node) {
+                exprs.push(LLExpr::Literal(LLLit::Null(ltype.clone())));
+            } else if let Some(value) = exprs.pop() {
+                // The branches can differ in representation when only some of them are nullable
+                exprs.push(LLExpr::Cast(ltype.clone(), r(value)));
+            }
+        }
+        exprs
+    }
+
+    fn is_never(&self, node: &AST) -> bool {
+        match node {
+            AST::Raise(_, _, _) => true,
+            AST::Block(_, _, body) => body.last().map(|node| self.is_never(node)).unwrap_or(false),
+            AST::Invoke(id, _, _, _, _) => self.session.get_type(*id).map(|ftype| ftype.get_rettype().map(|rtype| rtype.is_never()).unwrap_or(false)).unwrap_or(false),
+            AST::If(id, _, _, _, _) |
+            AST::Match(id, _, _, _) => self.session.get_type(*id).map(|ttype| ttype.is_never()).unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    fn transform_match(&self, scope: ScopeRef, ltype: Option<&LLType>, cond: &AST, cases: &Vec<MatchCase>, fallback: Option<Vec<LLExpr>>) -> Vec<LLExpr> {
         let mut exprs = vec!();
         let mut conds = vec!();
         let mut blocks = vec!();
@@ -1139,8 +1511,21 @@ impl<'sess> Transformer<'sess> {
 
         for case in cases {
             let lscope = self.session.map.get(&case.id);
-            conds.push(self.transform_pattern(lscope.clone(), &case.pat, condid));
-            blocks.push(self.transform_node(lscope.clone(), &case.body));
+            let mut cond = self.transform_pattern(lscope.clone(), &case.pat, condid);
+            if let Some(ref guard) = case.guard {
+                // The guard is only evaluated if the pattern matched, after its bindings have been assigned
+                let result_id = NodeID::generate();
+                let result = cond.pop().unwrap();
+                cond.push(LLExpr::SetValue(result_id, r(result)));
+                cond.push(LLExpr::Phi(vec!(vec!(LLExpr::GetValue(result_id))), vec!(self.transform_node(lscope.clone(), guard))));
+            }
+            conds.push(cond);
+            blocks.push(self.transform_branch(lscope.clone(), ltype, &case.body));
+        }
+
+        if let Some(fallback) = fallback {
+            conds.push(vec!(LLExpr::Literal(LLLit::I1(true))));
+            blocks.push(fallback);
         }
 
         exprs.push(LLExpr::Phi(conds, blocks));
@@ -1158,6 +1543,10 @@ impl<'sess> Transformer<'sess> {
                 let result = self.transform_as_result(&mut exprs, scope.clone(), lit).unwrap();
                 exprs.extend(self.create_func_invoke(compabi, compfunc, vec!(LLExpr::GetValue(value_id), result)));
             },
+            Pattern::Binding(id, ident) if self.session.get_const_pattern(*id).is_some() => {
+                let refid = self.session.get_const_pattern(*id).unwrap();
+                exprs.extend(self.transform_pattern(scope.clone(), &Pattern::Literal(*id, AST::Identifier(refid, Pos::empty(), ident.clone())), value_id));
+            },
             Pattern::Binding(id, ident) => {
                 exprs.extend(self.transform_def_local(scope.clone(), *id, &ident.name, &AST::GetValue(value_id)));
                 exprs.push(LLExpr::Literal(LLLit::I1(true)));
@@ -1175,21 +1564,178 @@ impl<'sess> Transformer<'sess> {
             },
             Pattern::EnumArgs(id, left, args) => {
                 let variant_id = self.session.get_ref(*id).unwrap();
+                let enumdef = self.session.get_def_from_ref(variant_id).unwrap().as_enum().unwrap();
+                let argtypes = enumdef.get_variant_type_by_id(variant_id).unwrap().as_vec();
+                let concrete = self.session.get_type(*id).unwrap().as_vec();
+
+                let mut cond = self.transform_pattern(scope.clone(), left, value_id);
+                let result = cond.pop().unwrap();
+                exprs.extend(cond);
+
+                // The arguments are only valid if the variant matched, especially any boxed values
+                let mut subexprs = vec!();
                 let item_id = NodeID::generate();
-                exprs.push(LLExpr::SetValue(item_id, r(LLExpr::GetItem(r(LLExpr::Cast(self.get_type(variant_id).unwrap(), r(LLExpr::GetValue(value_id)))), 1))));
-                for (i, arg) in args.iter().enumerate() {
-                    let arg_id = NodeID::generate();
-                    exprs.push(LLExpr::SetValue(arg_id, r(LLExpr::GetItem(r(LLExpr::GetValue(item_id)), i))));
-                    exprs.extend(self.transform_pattern(scope.clone(), &arg, arg_id));
+                subexprs.push(LLExpr::SetValue(item_id, r(LLExpr::GetItem(r(LLExpr::Cast(self.get_type(variant_id).unwrap(), r(LLExpr::GetValue(value_id)))), 1))));
+                let mut results = vec!();
+                for (i, (arg, argtype)) in args.iter().zip(argtypes.iter()).enumerate() {
+                    let mut value = self.unbox_enum_value(enumdef.id, argtype, LLExpr::GetItem(r(LLExpr::GetValue(item_id)), i));
+                    if argtype.is_variable() {
+                        // Values stored as a type param must be cast back to the concrete type they were matched as
+                        value = LLExpr::Cast(self.transform_value_type(&concrete[i]), r(value));
+                    }
+                    results.push(self.transform_subpattern(&mut subexprs, scope.clone(), arg, value));
+                }
+                subexprs.push(self.transform_pattern_results(results));
+                self.transform_conditional_subpatterns(&mut exprs, result, subexprs);
+            },
+            Pattern::Tuple(_, items) => {
+                let mut results = vec!();
+                for (i, item) in items.iter().enumerate() {
+                    results.push(self.transform_subpattern(&mut exprs, scope.clone(), item, LLExpr::GetItem(r(LLExpr::GetValue(value_id)), i)));
+                }
+                exprs.push(self.transform_pattern_results(results));
+            },
+            Pattern::Record(id, items) => {
+                let rtype = self.session.get_type(*id).unwrap();
+                let mut results = vec!();
+                for (ident, item) in items.iter() {
+                    let index = rtype.get_record_types().unwrap().iter().position(|(name, _)| *name == ident.name).unwrap();
+                    results.push(self.transform_subpattern(&mut exprs, scope.clone(), item, LLExpr::GetItem(r(LLExpr::GetValue(value_id)), index)));
+                }
+                exprs.push(self.transform_pattern_results(results));
+            },
+            Pattern::Or(_, alts) => {
+                let mut results = vec!();
+                for alt in alts.iter() {
+                    results.push(self.transform_subpattern(&mut exprs, scope.clone(), alt, LLExpr::GetValue(value_id)));
+                }
+
+                // Each alternative binds its own copies, so copy the values from whichever alternative matched into the first one's
+                let aliases: Vec<Vec<(NodeID, String)>> = alts.iter().skip(1).map(|alt| self.session.get_pattern_bindings(alt)).collect();
+                for (defid, _) in self.session.get_pattern_bindings(&alts[0]) {
+                    let conds = results.iter().map(|result| vec!(LLExpr::GetValue(*result))).collect();
+                    let mut blocks = vec!(vec!(LLExpr::GetLocal(defid)));
+                    for alias in aliases.iter() {
+                        let (aid, _) = alias.iter().find(|(aid, _)| self.session.get_ref(*aid).unwrap() == defid).unwrap();
+                        blocks.push(vec!(LLExpr::GetLocal(*aid)));
+                    }
+                    exprs.push(LLExpr::SetLocal(defid, r(LLExpr::Phi(conds, blocks))));
+                }
+
+                let conds = results.iter().map(|result| vec!(LLExpr::GetValue(*result))).collect();
+                let blocks = results.iter().map(|_| vec!(LLExpr::Literal(LLLit::I1(true)))).collect();
+                exprs.push(LLExpr::Phi(conds, blocks));
+            },
+            Pattern::Range(id, start, end, inclusive, oid) => {
+                let mut results = vec!();
+                for (compid, op, lit) in vec!((*id, ">=", start), (*oid, if *inclusive { "<=" } else { "<" }, end)) {
+                    let compfunc = self.transform_as_result(&mut exprs, scope.clone(), &AST::Identifier(compid, Pos::empty(), Ident::from_str(op))).unwrap();
+                    let compabi = self.session.get_type(compid).unwrap().get_abi().unwrap();
+                    let result = self.transform_as_result(&mut exprs, scope.clone(), lit).unwrap();
+                    let mut subexprs = self.create_func_invoke(compabi, compfunc, vec!(LLExpr::GetValue(value_id), result));
+                    let result_id = NodeID::generate();
+                    let last = subexprs.pop().unwrap();
+                    exprs.extend(subexprs);
+                    exprs.push(LLExpr::SetValue(result_id, r(last)));
+                    results.push(result_id);
+                }
+                exprs.push(self.transform_pattern_results(results));
+            },
+            Pattern::List(id, items, rest) => {
+                let ltype = self.session.get_type(*id).unwrap();
+                let itype = ltype.get_params().unwrap()[0].clone();
+                let inttype = scope.find_type(self.session, &String::from("Int")).unwrap();
+
+                let len_id = NodeID::generate();
+                let mut lenexprs = self.create_method_invoke(scope.clone(), &ltype, "len", vec!(), vec!(LLExpr::GetValue(value_id)));
+                let len = lenexprs.pop().unwrap();
+                exprs.extend(lenexprs);
+                exprs.push(LLExpr::SetValue(len_id, r(len)));
+                let cmp = if rest.is_some() { LLCmpType::GreaterOrEqual } else { LLCmpType::Equal };
+                let cond = LLExpr::Cmp(cmp, r(LLExpr::GetValue(len_id)), r(LLExpr::Literal(LLLit::I64(items.len() as i64))));
+
+                // Items after the rest pattern are fetched by their offset from the end of the list
+                let mut subexprs = vec!();
+                let mut results = vec!();
+                for (i, item) in items.iter().enumerate() {
+                    let (method, index) = match rest {
+                        Some((split, _)) if i >= *split => ("from_end", items.len() as i64 - i as i64),
+                        _ => ("get", i as i64),
+                    };
+                    let mut getexprs = self.create_method_invoke(scope.clone(), &ltype, method, vec!(inttype.clone()), vec!(LLExpr::GetValue(value_id), LLExpr::Literal(LLLit::I64(index))));
+                    let value = LLExpr::Cast(self.transform_value_type(&itype), r(getexprs.pop().unwrap()));
+                    subexprs.extend(getexprs);
+                    results.push(self.transform_subpattern(&mut subexprs, scope.clone(), item, value));
+                }
+                if let Some((split, rest)) = rest {
+                    let stop = if *split == items.len() {
+                        LLExpr::GetValue(len_id)
+                    } else {
+                        LLExpr::Literal(LLLit::I64(*split as i64 - items.len() as i64))
+                    };
+                    let mut sliceexprs = self.create_method_invoke(scope.clone(), &ltype, "slice", vec!(inttype.clone(), inttype.clone()), vec!(LLExpr::GetValue(value_id), LLExpr::Literal(LLLit::I64(*split as i64)), stop));
+                    let value = LLExpr::Cast(self.transform_value_type(&ltype), r(sliceexprs.pop().unwrap()));
+                    subexprs.extend(sliceexprs);
+                    results.push(self.transform_subpattern(&mut subexprs, scope.clone(), rest, value));
                 }
-                //let result = exprs.pop();
-                exprs.extend(self.transform_pattern(scope.clone(), left, value_id));
+                subexprs.push(self.transform_pattern_results(results));
+                // The items are only fetched if the list is long enough
+                self.transform_conditional_subpatterns(&mut exprs, cond, subexprs);
             },
             _ => panic!("Not Implemented: {:?}", pat),
         }
         exprs
     }
 
+    fn transform_conditional_subpatterns(&self, exprs: &mut Vec<LLExpr>, cond: LLExpr, subexprs: Vec<LLExpr>) {
+        // The bindings of the sub-patterns must be defined outside of the conditional block, so that they are available to the case body
+        let subexprs = subexprs.into_iter().map(|expr| match expr {
+            LLExpr::DefLocal(id, name, ltype, value) => {
+                exprs.push(LLExpr::DefLocal(id, name, ltype.clone(), r(LLExpr::Literal(LLLit::Null(ltype)))));
+                LLExpr::SetLocal(id, value)
+            },
+            _ => expr,
+        }).collect();
+        exprs.push(LLExpr::Phi(vec!(vec!(cond)), vec!(subexprs)));
+    }
+
+    fn create_method_invoke(&self, scope: ScopeRef, otype: &Type, name: &str, argtypes: Vec<Type>, fargs: Vec<LLExpr>) -> Vec<LLExpr> {
+        let vars = self.session.get_def(otype.get_id().unwrap()).unwrap().get_vars().unwrap();
+        let mut defid = vars.get_var_def(&String::from(name)).unwrap();
+        if let Ok(Def::Overload(ol)) = self.session.get_def(defid) {
+            // The variant is chosen by the argument types, the same as when the method is invoked directly
+            let mut atypes = vec!(otype.clone());
+            atypes.extend(argtypes);
+            defid = ol.find_variant(self.session, scope.clone(), Type::Tuple(atypes)).unwrap().0;
+        }
+        let abi = self.session.get_type(defid).unwrap().get_abi().unwrap();
+        let mut exprs = self.convert_accessor(defid, fargs[0].clone(), &String::from(name), otype.clone());
+        let func = exprs.pop().unwrap();
+        exprs.extend(self.create_func_invoke(abi, func, fargs));
+        exprs
+    }
+
+    fn transform_subpattern(&self, exprs: &mut Vec<LLExpr>, scope: ScopeRef, pat: &Pattern, value: LLExpr) -> NodeID {
+        let value_id = NodeID::generate();
+        exprs.push(LLExpr::SetValue(value_id, r(value)));
+        let mut subexprs = self.transform_pattern(scope.clone(), pat, value_id);
+        let result = subexprs.pop().unwrap();
+        exprs.extend(subexprs);
+
+        let result_id = NodeID::generate();
+        exprs.push(LLExpr::SetValue(result_id, r(result)));
+        result_id
+    }
+
+    fn transform_pattern_results(&self, results: Vec<NodeID>) -> LLExpr {
+        // All sub-patterns are evaluated beforehand, so that their bindings are available to the case body
+        let mut results = results.into_iter().map(|result| LLExpr::GetValue(result));
+        match results.next() {
+            None => LLExpr::Literal(LLLit::I1(true)),
+            Some(first) => results.fold(first, |acc, result| LLExpr::Phi(vec!(vec!(acc)), vec!(vec!(result)))),
+        }
+    }
+
     /*
     fn find_func(&self, scope: ScopeRef, name: &str, argtypes: &Vec<Type>) -> NodeID {
         let compid = scope.get_var_def(&String::from(name)).unwrap();
@@ -1228,6 +1774,7 @@ impl<'sess> Transformer<'sess> {
         match &ttype {
             Type::Object(name, id, _) => self.types.borrow().get(id).unwrap().clone(),
             Type::Ref(ttype) => LLType::Ptr(r(self.transform_value_type(ttype))),
+            Type::Nullable(ttype) => LLType::Nullable(r(self.transform_value_type(ttype))),
             Type::Tuple(items) => LLType::Struct(items.iter().map(|item| self.transform_value_type(&item)).collect()),
             Type::Record(items) => LLType::Struct(items.iter().map(|item| self.transform_value_type(&item.1)).collect()),
             // TODO how will you do generics
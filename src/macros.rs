@@ -0,0 +1,283 @@
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use abi::ABI;
+use types::Type;
+use misc::{ r, UniqueID };
+use ast::{ NodeID, Pos, AST, Ident, Visibility, Argument, MatchCase, Pattern, EnumVariant };
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacroDef {
+    pub id: NodeID,
+    pub pos: Pos,
+    pub args: Vec<Ident>,
+    pub body: AST,
+}
+
+impl MacroDef {
+    pub fn new(id: NodeID, pos: Pos, args: Vec<Ident>, body: AST) -> Self {
+        Self {
+            id: id,
+            pos: pos,
+            args: args,
+            body: body,
+        }
+    }
+
+    /// Returns the expanded body, and the ids of the identifiers in it that refer to names where the macro was defined
+    pub fn expand(&self, callsite: &Pos, args: Vec<AST>) -> (AST, Vec<NodeID>) {
+        let params = self.args.iter().map(|arg| arg.name.clone()).zip(args).collect();
+        let expander = Expander::new(Some((self.pos.filenum, callsite.clone())), params);
+        let expanded = expander.expand_node(self.body.clone());
+        (expanded, expander.refs.into_inner())
+    }
+}
+
+
+/// Copy the code with new node ids, so that it can be bound and checked separately from the original
+pub fn copy_node(node: AST) -> AST {
+    Expander::copy(node)
+}
+
+
+struct Expander {
+    callsite: Option<(u16, Pos)>,
+    params: HashMap<String, AST>,
+    renames: RefCell<HashMap<String, String>>,
+    refs: RefCell<Vec<NodeID>>,
+}
+
+impl Expander {
+    fn new(callsite: Option<(u16, Pos)>, params: HashMap<String, AST>) -> Self {
+        Self {
+            callsite: callsite,
+            params: params,
+            renames: RefCell::new(HashMap::new()),
+            refs: RefCell::new(vec!()),
+        }
+    }
+
+    fn copy(node: AST) -> AST {
+        // An argument can be used more than once in the body, so each use needs its own node ids
+        Expander::new(None, HashMap::new()).expand_node(node)
+    }
+
+    fn expand_pos(&self, pos: Pos) -> Pos {
+        match self.callsite {
+            Some((filenum, ref callsite)) => Pos { filenum: filenum, expansion: Some(r(callsite.clone())), ..pos },
+            None => pos,
+        }
+    }
+
+    fn bind_name(&self, ident: Ident) -> Ident {
+        // Names introduced by the macro are renamed so they can't capture or shadow the names used in its arguments
+        if self.callsite.is_none() || ident.name == "self" {
+            return ident;
+        }
+        let mut renames = self.renames.borrow_mut();
+        let name = renames.entry(ident.name.clone()).or_insert_with(|| format!("{}%{}", ident.name, UniqueID::generate()));
+        Ident::new(name.clone())
+    }
+
+    fn rename(&self, ident: Ident) -> Ident {
+        match self.renames.borrow().get(&ident.name) {
+            Some(name) => Ident::new(name.clone()),
+            None => ident,
+        }
+    }
+
+    fn expand_vec(&self, code: Vec<AST>) -> Vec<AST> {
+        code.into_iter().map(|node| self.expand_node(node)).collect()
+    }
+
+    fn expand_node(&self, node: AST) -> AST {
+        match node {
+            AST::Identifier(_, pos, ident) => {
+                match self.params.get(&ident.name) {
+                    Some(arg) => Expander::copy(arg.clone()),
+                    None => {
+                        // Names that aren't bound by the macro refer to the top level where it was defined, so they can't be captured by the call site
+                        let id = NodeID::generate();
+                        if self.callsite.is_some() && ident.name != "self" && !self.renames.borrow().contains_key(&ident.name) {
+                            self.refs.borrow_mut().push(id);
+                        }
+                        AST::Identifier(id, self.expand_pos(pos), self.rename(ident))
+                    },
+                }
+            },
+
+            AST::Literal(_, lit) => AST::Literal(NodeID::generate(), lit),
+            AST::Interpolate(_, pos, pieces) => AST::Interpolate(NodeID::generate(), self.expand_pos(pos), self.expand_vec(pieces)),
+            AST::Nil(_) => AST::Nil(NodeID::generate()),
+            AST::GetValue(id) => AST::GetValue(id),
+            AST::PtrCast(_, ttype, expr) => AST::PtrCast(NodeID::generate(), ttype, r(self.expand_node(*expr))),
+            AST::Ref(_, pos, expr) => AST::Ref(NodeID::generate(), self.expand_pos(pos), r(self.expand_node(*expr))),
+            AST::Deref(_, pos, expr) => AST::Deref(NodeID::generate(), self.expand_pos(pos), r(self.expand_node(*expr))),
+
+            AST::List(_, pos, items) => AST::List(NodeID::generate(), self.expand_pos(pos), self.expand_vec(items)),
+            AST::Tuple(_, pos, items) => AST::Tuple(NodeID::generate(), self.expand_pos(pos), self.expand_vec(items)),
+            AST::Record(_, pos, items) => {
+                AST::Record(NodeID::generate(), self.expand_pos(pos), items.into_iter().map(|(i, e)| (i, self.expand_node(e))).collect())
+            },
+            AST::RecordUpdate(_, pos, record, items) => {
+                let record = self.expand_node(*record);
+                AST::RecordUpdate(NodeID::generate(), self.expand_pos(pos), r(record), items.into_iter().map(|(i, e)| (i, self.expand_node(e))).collect())
+            },
+
+            AST::Index(_, pos, base, index) => {
+                AST::Index(NodeID::generate(), self.expand_pos(pos), r(self.expand_node(*base)), r(self.expand_node(*index)))
+            },
+            AST::Resolver(_, pos, left, right, _) => {
+                AST::Resolver(NodeID::generate(), self.expand_pos(pos), r(self.expand_node(*left)), right, NodeID::generate())
+            },
+            AST::Accessor(_, pos, left, right, _) => {
+                AST::Accessor(NodeID::generate(), self.expand_pos(pos), r(self.expand_node(*left)), right, NodeID::generate())
+            },
+
+            AST::Block(_, pos, code) => AST::Block(NodeID::generate(), self.expand_pos(pos), self.expand_vec(code)),
+            AST::Invoke(_, pos, fexpr, types, args) => {
+                let fexpr = self.expand_node(*fexpr);
+                AST::Invoke(NodeID::generate(), self.expand_pos(pos), r(fexpr), types, self.expand_vec(args))
+            },
+            AST::Keyword(_, pos, ident, expr) => AST::Keyword(NodeID::generate(), self.expand_pos(pos), ident, r(self.expand_node(*expr))),
+            AST::SideEffect(_, pos, op, args) => AST::SideEffect(NodeID::generate(), self.expand_pos(pos), op, self.expand_vec(args)),
+
+            AST::If(_, pos, cond, texpr, fexpr) => {
+                let cond = self.expand_node(*cond);
+                let texpr = self.expand_node(*texpr);
+                AST::If(NodeID::generate(), self.expand_pos(pos), r(cond), r(texpr), r(self.expand_node(*fexpr)))
+            },
+            AST::Raise(_, pos, expr) => AST::Raise(NodeID::generate(), self.expand_pos(pos), r(self.expand_node(*expr))),
+            AST::Try(_, pos, cond, cases) => {
+                let cond = self.expand_node(*cond);
+                AST::Try(NodeID::generate(), self.expand_pos(pos), r(cond), self.expand_cases(cases))
+            },
+            AST::Match(_, pos, cond, cases) => {
+                let cond = self.expand_node(*cond);
+                AST::Match(NodeID::generate(), self.expand_pos(pos), r(cond), self.expand_cases(cases))
+            },
+            AST::For(_, pos, ident, list, body) => {
+                let list = self.expand_node(*list);
+                let ident = self.bind_name(ident);
+                AST::For(NodeID::generate(), self.expand_pos(pos), ident, r(list), r(self.expand_node(*body)))
+            },
+            AST::Range(_, pos, start, end, step, inclusive) => {
+                let start = self.expand_node(*start);
+                let end = self.expand_node(*end);
+                AST::Range(NodeID::generate(), self.expand_pos(pos), r(start), r(end), step.map(|step| r(self.expand_node(*step))), inclusive)
+            },
+            AST::While(_, pos, cond, body) => {
+                let cond = self.expand_node(*cond);
+                AST::While(NodeID::generate(), self.expand_pos(pos), r(cond), r(self.expand_node(*body)))
+            },
+
+            AST::Declare(_, pos, vis, ident, params, args, ttype) => {
+                AST::Declare(NodeID::generate(), self.expand_pos(pos), vis, ident, params, self.expand_args(args, false), ttype)
+            },
+            AST::Function(_, pos, vis, ident, params, args, ret, body, abi) => {
+                let ident = ident.map(|ident| self.bind_name(ident));
+                self.expand_function(pos, vis, ident, params, args, ret, *body, abi)
+            },
+            AST::New(_, pos, classspec) => AST::New(NodeID::generate(), self.expand_pos(pos), classspec),
+            AST::Class(_, pos, classspec, parentspec, body) => {
+                AST::Class(NodeID::generate(), self.expand_pos(pos), classspec, parentspec, self.expand_members(body))
+            },
+            AST::TypeAlias(_, pos, classspec, ttype) => AST::TypeAlias(NodeID::generate(), self.expand_pos(pos), classspec, ttype),
+            AST::Enum(_, pos, classspec, variants, body) => {
+                let variants = variants.into_iter().map(|variant| EnumVariant::new(self.expand_pos(variant.pos), variant.ident, variant.ttype)).collect();
+                AST::Enum(NodeID::generate(), self.expand_pos(pos), classspec, variants, self.expand_members(body))
+            },
+            AST::Trait(_, pos, ident, body) => AST::Trait(NodeID::generate(), self.expand_pos(pos), ident, self.expand_members(body)),
+            AST::Impl(_, pos, ident, ttype, body) => AST::Impl(NodeID::generate(), self.expand_pos(pos), ident, ttype, self.expand_members(body)),
+
+            AST::Import(_, pos, ident, kind, decls) => AST::Import(NodeID::generate(), self.expand_pos(pos), ident, kind, decls),
+            AST::Module(_, pos, ident, body) => AST::Module(NodeID::generate(), self.expand_pos(pos), ident, self.expand_members(body)),
+            AST::Attributes(_, pos, attrs, node) => AST::Attributes(NodeID::generate(), self.expand_pos(pos), attrs, r(self.expand_node(*node))),
+            // A nested macro definition is only expanded when it is used
+            AST::Macro(_, pos, ident, args, text, body) => AST::Macro(NodeID::generate(), self.expand_pos(pos), ident, args, text, body),
+
+            AST::Definition(_, pos, vis, mutable, ident, ttype, value) => {
+                let value = value.map(|value| r(self.expand_node(*value)));
+                AST::Definition(NodeID::generate(), self.expand_pos(pos), vis, mutable, self.bind_name(ident), ttype, value)
+            },
+            AST::Const(_, pos, ident, ttype, value) => {
+                let value = self.expand_node(*value);
+                AST::Const(NodeID::generate(), self.expand_pos(pos), self.bind_name(ident), ttype, r(value))
+            },
+            AST::Destructure(_, pos, pat, value) => {
+                let value = self.expand_node(*value);
+                AST::Destructure(NodeID::generate(), self.expand_pos(pos), r(self.expand_pattern(*pat)), r(value))
+            },
+            AST::Assignment(_, pos, left, right, ty) => {
+                let left = self.expand_node(*left);
+                AST::Assignment(NodeID::generate(), self.expand_pos(pos), r(left), r(self.expand_node(*right)), ty)
+            },
+        }
+    }
+
+    fn expand_function(&self, pos: Pos, vis: Visibility, ident: Option<Ident>, params: Vec<Type>, args: Vec<Argument>, ret: Option<Type>, body: AST, abi: ABI) -> AST {
+        let args = self.expand_args(args, true);
+        AST::Function(NodeID::generate(), self.expand_pos(pos), vis, ident, params, args, ret, r(self.expand_node(body)), abi)
+    }
+
+    fn expand_members(&self, body: Vec<AST>) -> Vec<AST> {
+        // Members are accessed by name from outside of the body, so they keep their names
+        body.into_iter().map(|node| match node {
+            AST::Definition(_, pos, vis, mutable, ident, ttype, value) => {
+                AST::Definition(NodeID::generate(), self.expand_pos(pos), vis, mutable, ident, ttype, value.map(|value| r(self.expand_node(*value))))
+            },
+            AST::Function(_, pos, vis, ident, params, args, ret, body, abi) => {
+                self.expand_function(pos, vis, ident, params, args, ret, *body, abi)
+            },
+            AST::Attributes(_, pos, attrs, node) => {
+                AST::Attributes(NodeID::generate(), self.expand_pos(pos), attrs, r(self.expand_members(vec!(*node)).remove(0)))
+            },
+            node => self.expand_node(node),
+        }).collect()
+    }
+
+    fn expand_args(&self, args: Vec<Argument>, bind: bool) -> Vec<Argument> {
+        args.into_iter().map(|arg| {
+            let default = arg.default.map(|default| self.expand_node(default));
+            let ident = if bind { self.bind_name(arg.ident) } else { arg.ident };
+            Argument::new(self.expand_pos(arg.pos), ident, arg.ttype, default)
+        }).collect()
+    }
+
+    fn expand_cases(&self, cases: Vec<MatchCase>) -> Vec<MatchCase> {
+        cases.into_iter().map(|case| {
+            let pat = self.expand_pattern(case.pat);
+            let guard = case.guard.map(|guard| self.expand_node(guard));
+            MatchCase::new(self.expand_pos(case.pos), pat, guard, self.expand_node(case.body))
+        }).collect()
+    }
+
+    fn expand_pattern(&self, pat: Pattern) -> Pattern {
+        match pat {
+            Pattern::Wild => Pattern::Wild,
+            Pattern::Literal(_, lit) => Pattern::Literal(NodeID::generate(), self.expand_node(lit)),
+            Pattern::Binding(_, ident) => Pattern::Binding(NodeID::generate(), self.bind_name(ident)),
+            Pattern::Annotation(_, ttype, pat) => Pattern::Annotation(NodeID::generate(), ttype, r(self.expand_pattern(*pat))),
+            Pattern::Identifier(_, ident) => Pattern::Identifier(NodeID::generate(), self.rename(ident)),
+            Pattern::Resolve(_, left, ident, _) => Pattern::Resolve(NodeID::generate(), r(self.expand_pattern(*left)), ident, NodeID::generate()),
+            Pattern::EnumArgs(_, left, args) => {
+                let left = self.expand_pattern(*left);
+                Pattern::EnumArgs(NodeID::generate(), r(left), args.into_iter().map(|arg| self.expand_pattern(arg)).collect())
+            },
+            Pattern::Tuple(_, items) => Pattern::Tuple(NodeID::generate(), items.into_iter().map(|item| self.expand_pattern(item)).collect()),
+            Pattern::Record(_, items) => Pattern::Record(NodeID::generate(), items.into_iter().map(|(i, item)| (i, self.expand_pattern(item))).collect()),
+            Pattern::Or(_, alts) => Pattern::Or(NodeID::generate(), alts.into_iter().map(|alt| self.expand_pattern(alt)).collect()),
+            Pattern::Range(_, start, end, inclusive, _) => {
+                let start = self.expand_node(start);
+                Pattern::Range(NodeID::generate(), start, self.expand_node(end), inclusive, NodeID::generate())
+            },
+            Pattern::List(_, items, rest) => {
+                let items = items.into_iter().map(|item| self.expand_pattern(item)).collect();
+                Pattern::List(NodeID::generate(), items, rest.map(|(n, rest)| (n, r(self.expand_pattern(*rest)))))
+            },
+        }
+    }
+}
+
@@ -26,8 +26,11 @@ mod types;
 mod config;
 mod session;
 mod refinery;
+mod macros;
 mod binding;
 mod typecheck;
+mod coverage;
+mod constants;
 mod defs;
 mod export;
 mod llvm;
@@ -77,6 +80,10 @@ fn main() {
                 .short("G")
                 .long("no-gc")
                 .help("Disables garbage collection and uses malloc directly"))
+            .arg(Arg::with_name("test")
+                .short("t")
+                .long("test")
+                .help("Compiles the functions marked with @test, and runs them after the top level code"))
             .get_matches();
 
     build_options(&matches);
@@ -96,6 +103,7 @@ fn build_options(matches: &ArgMatches) {
     Options::as_ref().is_library = matches.occurrences_of("library") > 0;
     Options::as_ref().no_gc = matches.occurrences_of("no-gc") > 0;
     Options::as_ref().linkfile_only = matches.occurrences_of("linkfile") > 0;
+    Options::as_ref().test = matches.occurrences_of("test") > 0;
 
     Options::as_ref().format = if matches.occurrences_of("assemble") > 0 {
         EmitAs::LLIR
@@ -122,7 +130,7 @@ fn compile_file(input: &str, output: Option<&str>) {
     }
 
     binding::NameBinder::bind_names(&session, session.map.get_global(), &code);
-    typecheck::TypeChecker::check(&session, session.map.get_global(), &code);
+    typecheck::TypeChecker::check(&session, session.map.get_global(), &code, None);
 
     if Options::as_ref().debug {
         let global = session.map.get_global();
@@ -138,6 +146,9 @@ fn compile_file(input: &str, output: Option<&str>) {
     let transformer = llvm::transform::Transformer::new(&session);
     transformer.initialize();
     transformer.transform_code(session.map.get_global(), &code);
+    if session.errors.get() > 0 {
+        panic!("Exiting due to previous errors");
+    }
     if Options::as_ref().debug {
         println!("===================");
         println!("{:#?}", &transformer.globals.borrow());
@@ -32,7 +32,7 @@ use std::str::FromStr;
 use abi::ABI;
 use types::Type;
 use misc::{ r, UniqueID };
-use ast::{ Pos, NodeID, Mutability, Visibility, AssignType, Literal, Ident, Argument, ClassSpec, MatchCase, Pattern, EnumVariant, AST };
+use ast::{ Pos, NodeID, Mutability, Visibility, AssignType, ImportKind, Literal, Ident, Argument, Attribute, ClassSpec, MatchCase, Pattern, EnumVariant, AST };
 
 
 ///// Parsing Macros /////
@@ -44,6 +44,11 @@ const ERR_IN_WHILE: u32 = 43;
 const ERR_IN_FOR: u32 = 44;
 const ERR_IN_CLASS: u32 = 45;
 const ERR_IN_LIST: u32 = 46;
+const ERR_IN_STRING: u32 = 47;
+const ERR_IN_ENUM: u32 = 48;
+const ERR_IN_MODULE: u32 = 49;
+const ERR_IN_TRAIT: u32 = 50;
+const ERR_IN_IMPL: u32 = 51;
 
 pub type Span<'a> = LocatedSpan<CompleteByteSlice<'a>>;
 
@@ -160,26 +165,104 @@ named!(terminator(Span) -> Option<AST>,
 
 named!(statement(Span) -> AST,
     alt_complete!(
+        attributed |
         import |
+        module |
+        macrodef |
+        constant |
+        destructure |
         definition |
         assignment |
         whileloop |
         class |
         typealias |
         typeenum |
+        traitdef |
+        implblock |
         expression
     )
 );
 
+named!(attributed(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        a: many1!(wscom!(attribute)) >>
+        s: alt_complete!(
+            class |
+            definition |
+            declare |
+            function
+        ) >>
+        (AST::make_attributes(Pos::new(pos), a, s))
+    )
+);
+
+named!(attribute(Span) -> Attribute,
+    do_parse!(
+        pos: position!() >>
+        tag!("@") >>
+        i: identifier >>
+        a: opt!(delimited!(tag!("("), wscom!(separated_list_complete!(wscom!(tag!(",")), literal)), tag!(")"))) >>
+        (Attribute::new(Pos::new(pos), i, a.unwrap_or(vec!())))
+    )
+);
+
 named!(import(Span) -> AST,
+    alt_complete!(
+        do_parse!(
+            pos: position!() >>
+            wscom!(tag_word!("import")) >>
+            e: module_path >>
+            a: opt!(preceded!(wscom!(tag_word!("as")), identifier)) >>
+            (AST::make_import(Pos::new(pos), e, a.map(|a| ImportKind::Alias(a)).unwrap_or(ImportKind::All), vec!()))
+        ) |
+        do_parse!(
+            pos: position!() >>
+            wscom!(tag_word!("from")) >>
+            e: module_path >>
+            wscom!(tag_word!("import")) >>
+            n: separated_nonempty_list_complete!(wscom!(tag!(",")), identifier) >>
+            (AST::make_import(Pos::new(pos), e, ImportKind::Names(n), vec!()))
+        )
+    )
+);
+
+named!(module(Span) -> AST,
     do_parse!(
         pos: position!() >>
-        wscom!(tag_word!("import")) >>
-        e: recognize!(separated_list_complete!(tag!("."), identifier)) >>
-        (AST::make_import(Pos::new(pos), Ident::from_span(e), vec!()))
+        wscom!(tag_word!("module")) >>
+        i: identifier >>
+        wscom!(tag!("{")) >>
+        s: many0!(wscom!(alt_complete!(
+            attributed |
+            constant |
+            definition |
+            declare |
+            function
+        ))) >>
+        return_error!(ErrorKind::Custom(ERR_IN_MODULE), tag!("}")) >>
+        (AST::make_module(Pos::new(pos), i, s))
     )
 );
 
+named!(macrodef(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        wscom!(tag_word!("macro")) >>
+        i: identifier >>
+        a: delimited!(tag!("("), wscom!(separated_list_complete!(wscom!(tag!(",")), identifier)), tag!(")")) >>
+        wscom!(tag!("=>")) >>
+        // The source text is kept so that the macro can be exported as it was written
+        t: peek!(recognize!(expression)) >>
+        e: expression >>
+        (AST::make_macro(Pos::new(pos), i, a, String::from(str::from_utf8(&t.fragment).unwrap()), e))
+    )
+);
+
+named!(module_path(Span) -> Ident,
+    map!(recognize!(separated_list_complete!(tag!("."), identifier)), |e| Ident::from_span(e))
+);
+
 named!(definition(Span) -> AST,
     do_parse!(
         pos: position!() >>
@@ -190,16 +273,40 @@ named!(definition(Span) -> AST,
             wscom!(tag!("=")),
             expression
         )) >>
-        (AST::make_def(
+        (AST::Definition(
+            NodeID::generate(),
             Pos::new(pos),
+            Visibility::Private,
             if m.is_some() { Mutability::Mutable } else { Mutability::Immutable },
             i.1,
             i.2,
-            if e.is_some() { e.unwrap() } else { AST::make_nil() }
+            e.map(|e| r(e))
         ))
     )
 );
 
+named!(constant(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        wscom!(tag_word!("const")) >>
+        i: identifier_typed >>
+        wscom!(tag!("=")) >>
+        e: expression >>
+        (AST::make_const(Pos::new(pos), i.1, i.2, e))
+    )
+);
+
+named!(destructure(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        wscom!(tag_word!("let")) >>
+        p: map_opt!(pattern, |p: Pattern| if p.is_simple_binding() { None } else { Some(p) }) >>
+        wscom!(tag!("=")) >>
+        e: expression >>
+        (AST::make_destructure(Pos::new(pos), p, e))
+    )
+);
+
 named!(assignment(Span) -> AST,
     do_parse!(
         pos: position!() >>
@@ -230,15 +337,60 @@ named!(class(Span) -> AST,
         wscom!(tag!("{")) >>
         s: many0!(wscom!(alt_complete!(
             typealias |
-            definition |
-            declare |
-            function
+            class_member
         ))) >>
         return_error!(ErrorKind::Custom(ERR_IN_CLASS), tag!("}")) >>
         (AST::make_class(Pos::new(pos), i, p, s))
         )
     );
 
+named!(class_member(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        m: member_prefix >>
+        s: alt_complete!(
+            definition |
+            declare |
+            function
+        ) >>
+        (make_member(Pos::new(pos), m, s))
+    )
+);
+
+named!(enum_member(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        m: member_prefix >>
+        s: alt_complete!(
+            declare |
+            function
+        ) >>
+        (make_member(Pos::new(pos), m, s))
+    )
+);
+
+named!(member_prefix(Span) -> (Vec<Attribute>, Visibility),
+    do_parse!(
+        a: many0!(wscom!(attribute)) >>
+        v: opt!(wscom!(alt!(
+            value!(Visibility::Public, tag_word!("pub")) |
+            value!(Visibility::Private, tag_word!("priv")) |
+            value!(Visibility::Protected, tag_word!("protected"))
+        ))) >>
+        ((a, v.unwrap_or(Visibility::Public)))
+    )
+);
+
+fn make_member(pos: Pos, prefix: (Vec<Attribute>, Visibility), node: AST) -> AST {
+    let (attrs, vis) = prefix;
+    let node = node.with_visibility(vis);
+    if attrs.len() > 0 {
+        AST::make_attributes(pos, attrs, node)
+    } else {
+        node
+    }
+}
+
 named!(typealias(Span) -> AST,
     do_parse!(
         pos: position!() >>
@@ -258,7 +410,74 @@ named!(typeenum(Span) -> AST,
         wscom!(tag!("=")) >>
         wscom!(opt!(tag!("|"))) >>
         ev: separated_list_complete!(wscom!(tag!("|")), enum_variant) >>
-        (AST::make_type_enum(Pos::new(pos), c, ev))
+        b: opt!(preceded!(
+            wscom!(tag!("{")),
+            terminated!(
+                many0!(wscom!(enum_member)),
+                return_error!(ErrorKind::Custom(ERR_IN_ENUM), tag!("}"))
+            )
+        )) >>
+        (AST::make_type_enum(Pos::new(pos), c, ev, b.unwrap_or(vec!())))
+    )
+);
+
+named!(traitdef(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        wscom!(tag_word!("trait")) >>
+        i: identifier >>
+        wscom!(tag!("{")) >>
+        s: many0!(wscom!(trait_method)) >>
+        return_error!(ErrorKind::Custom(ERR_IN_TRAIT), tag!("}")) >>
+        (AST::make_trait(Pos::new(pos), i, s))
+    )
+);
+
+named!(trait_method(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        wscom!(tag_word!("fn")) >>
+        n: alt_complete!(identifier | any_op) >>
+        args: delimited!(tag!("("), wscom!(separated_list_complete!(wscom!(tag!(",")), identifier_typed)), tag!(")")) >>
+        ret: opt!(preceded!(wscom!(tag!("->")), type_description)) >>
+        ({
+            let args: Vec<Argument> = args.into_iter().map(|(pos, i, t)| {
+                // An untyped self argument refers to whichever type implements the trait
+                let ttype = t.map(trait_self_type).unwrap_or(Type::Variable(String::from("Self"), UniqueID(0), true));
+                Argument::new(pos, i, Some(ttype), None)
+            }).collect();
+            let types = args.iter().map(|arg| arg.ttype.clone().unwrap()).collect();
+            let ret = trait_self_type(ret.unwrap_or(Type::Object(String::from("()"), UniqueID(0), vec!())));
+            AST::make_decl(Pos::new(pos), Visibility::Public, n, vec!(), args, Type::Function(r(Type::Tuple(types)), r(ret), ABI::Molten))
+        })
+    )
+);
+
+/// Inside a trait, Self is a typevar for the type implementing the trait rather than a particular class
+fn trait_self_type(ttype: Type) -> Type {
+    match ttype {
+        Type::Object(ref name, _, ref types) if name.as_str() == "Self" && types.len() == 0 => Type::Variable(name.clone(), UniqueID(0), true),
+        Type::Object(name, id, types) => Type::Object(name, id, types.into_iter().map(trait_self_type).collect()),
+        Type::Tuple(types) => Type::Tuple(types.into_iter().map(trait_self_type).collect()),
+        Type::Record(types) => Type::Record(types.into_iter().map(|(name, ttype)| (name, trait_self_type(ttype))).collect()),
+        Type::Function(args, ret, abi) => Type::Function(r(trait_self_type(*args)), r(trait_self_type(*ret)), abi),
+        Type::Ref(ttype) => Type::Ref(r(trait_self_type(*ttype))),
+        Type::Nullable(ttype) => Type::Nullable(r(trait_self_type(*ttype))),
+        ttype => ttype,
+    }
+}
+
+named!(implblock(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        wscom!(tag_word!("impl")) >>
+        i: identifier >>
+        wscom!(tag_word!("for")) >>
+        t: type_description >>
+        wscom!(tag!("{")) >>
+        s: many0!(wscom!(enum_member)) >>
+        return_error!(ErrorKind::Custom(ERR_IN_IMPL), tag!("}")) >>
+        (AST::make_impl(Pos::new(pos), i, t, s))
     )
 );
 
@@ -277,6 +496,20 @@ named!(expression_list(Span) -> Vec<AST>,
     separated_list_complete!(wscom!(tag!(",")), expression)
 );
 
+named!(invoke_argument_list(Span) -> Vec<AST>,
+    separated_list_complete!(wscom!(tag!(",")), alt_complete!(keyword_argument | expression))
+);
+
+named!(keyword_argument(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        i: identifier >>
+        wscom!(terminated!(tag!("="), not!(tag!("=")))) >>
+        e: expression >>
+        (AST::make_keyword(Pos::new(pos), i, e))
+    )
+);
+
 named!(expression(Span) -> AST,
     alt_complete!(
         //underscore |
@@ -290,7 +523,24 @@ named!(expression(Span) -> AST,
         function |
         reference |
         annotation |
-        infix
+        range
+    )
+);
+
+named!(range(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        start: infix >>
+        r: opt!(complete!(do_parse!(
+            inclusive: wscom!(alt!(value!(true, tag!("..=")) | value!(false, tag!("..")))) >>
+            end: infix >>
+            step: opt!(complete!(preceded!(delimited!(opt!(space), tag_word!("by"), line_or_space_or_comment), infix))) >>
+            ((end, step, inclusive))
+        ))) >>
+        (match r {
+            Some((end, step, inclusive)) => AST::make_range(Pos::new(pos), start, end, step, inclusive),
+            None => start,
+        })
     )
 );
 
@@ -376,11 +626,13 @@ named!(caselist(Span) -> Vec<MatchCase>,
     //separated_list_complete!(wscom!(tag!("|")), do_parse!(
     dbg_dmp!(many1!(wscom!(do_parse!(
         //wscom!(tag!("|")) >>
+        pos: position!() >>
         c: pattern >>
+        g: opt!(preceded!(wscom!(tag_word!("if")), expression)) >>
         wscom!(tag!("=>")) >>
         e: expression >>
         //wscom!(tag!(",")) >>
-        (MatchCase::new(c, e))
+        (MatchCase::new(Pos::new(pos), c, g, e))
     ))))
 );
 
@@ -388,12 +640,20 @@ named!(forloop(Span) -> AST,
     do_parse!(
         pos: position!() >>
         wscom!(tag_word!("for")) >>
-        i: identifier >>
+        p: pattern >>
         wscom!(tag_word!("in")) >>
         l: expression >>
         line_or_space_or_comment >>
         e: return_error!(ErrorKind::Custom(ERR_IN_FOR), expression) >>
-        (AST::make_for(Pos::new(pos), i, l, e))
+        (match p {
+            Pattern::Binding(_, i) => AST::make_for(Pos::new(pos), i, l, e),
+            _ => {
+                // Bind the item to a temporary and destructure it at the start of each iteration
+                let i = Ident::new(format!("{}", NodeID::generate()));
+                let e = AST::make_block(Pos::new(pos), vec!(AST::make_destructure(Pos::new(pos), p, AST::make_ident(Pos::new(pos), i.clone())), e));
+                AST::make_for(Pos::new(pos), i, l, e)
+            },
+        })
     )
 );
 
@@ -403,7 +663,7 @@ named!(newclass(Span) -> AST,
         wscom!(tag_word!("new")) >>
         cs: class_spec >>
         a: map!(
-            delimited!(tag!("("), expression_list, tag!(")")),
+            delimited!(tag!("("), invoke_argument_list, tag!(")")),
             |mut a| { a.insert(0, AST::make_new(Pos::new(pos), cs.clone())); a }
         ) >>
         (AST::make_ptr_cast(
@@ -418,8 +678,39 @@ named!(declare(Span) -> AST,
         vis: opt!(wscom!(tag_word!("pub"))) >>
         wscom!(tag_word!("decl")) >>
         n: alt_complete!(identifier | any_op) >>
-        t: type_function >>
-        (AST::make_decl(Pos::new(pos), if vis.is_some() { Visibility::Public } else { Visibility::Private }, n, t))
+        b: opt!(type_bounds) >>
+        t: alt_complete!(
+            declare_function_named |
+            map!(type_function, |t| (vec!(), t))
+        ) >>
+        ({
+            let b = b.unwrap_or(vec!());
+            let params = make_type_params(&b);
+            let (args, ttype) = add_bound_args_to_decl(Pos::new(pos), t.0, t.1, b);
+            AST::make_decl(Pos::new(pos), if vis.is_some() { Visibility::Public } else { Visibility::Private }, n, params, args, ttype)
+        })
+    )
+);
+
+named!(declare_function_named(Span) -> (Vec<Argument>, Type),
+    do_parse!(
+        args: delimited!(tag!("("), wscom!(separated_list_complete!(wscom!(tag!(",")),
+            do_parse!(
+                pos: position!() >>
+                i: identifier >>
+                wscom!(tag!(":")) >>
+                t: type_description >>
+                d: opt!(preceded!(wscom!(tag!("=")), expression)) >>
+                (Argument::new(Pos::new(pos), i, Some(t), d))
+            )
+        )), tag!(")")) >>
+        wscom!(tag!("->")) >>
+        ret: type_description >>
+        abi: abi_specifier >>
+        ({
+            let types = args.iter().map(|arg| arg.ttype.clone().unwrap()).collect();
+            (args, Type::Function(r(Type::Tuple(types)), r(ret), abi))
+        })
     )
 );
 
@@ -433,10 +724,11 @@ named!(function(Span) -> AST,
             do_parse!(
                 n: opt!(alt_complete!(identifier | any_op)) >>
                 //n: opt!(identifier) >>
+                b: opt!(type_bounds) >>
                 l: delimited!(tag!("("), argument_list, tag!(")")) >>
-                ((n, l))
+                ((n, l, b.unwrap_or(vec!())))
             ) |
-            map!(argument_list, |l| (None, l))
+            map!(argument_list, |l| (None, l, vec!()))
         ) >>
         r: opt!(preceded!(wscom!(tag!("->")), type_description)) >>
         a: abi_specifier >>
@@ -444,10 +736,80 @@ named!(function(Span) -> AST,
             preceded!(wscom!(tag!("=>")), return_error!(ErrorKind::Custom(ERR_IN_FUNC), expression)) |
             return_error!(ErrorKind::Custom(ERR_IN_FUNC), wscoml!(block))
         ) >>
-        (AST::make_func(Pos::new(pos), if vis.is_some() { Visibility::Public } else { Visibility::Private }, l.0, l.1, r, e, a))
+        ({
+            let (mut args, e) = AST::destructure_args(Pos::new(pos), l.1, e);
+            let params = make_type_params(&l.2);
+            args.extend(make_bound_args(Pos::new(pos), l.2));
+            AST::make_func(Pos::new(pos), if vis.is_some() { Visibility::Public } else { Visibility::Private }, l.0, params, args, r, e, a)
+        })
+    )
+);
+
+named!(type_bounds(Span) -> Vec<(Ident, Vec<Ident>)>,
+    delimited!(
+        tag!("<"),
+        wscom!(separated_list_complete!(wscom!(tag!(",")),
+            do_parse!(
+                tag!("'") >>
+                v: identifier >>
+                t: opt!(preceded!(wscom!(tag!(":")), separated_list_complete!(wscom!(tag!("+")), identifier))) >>
+                ((v, t.unwrap_or(vec!())))
+            )
+        )),
+        tag!(">")
     )
 );
 
+fn make_type_params(bounds: &Vec<(Ident, Vec<Ident>)>) -> Vec<Type> {
+    bounds.iter().map(|(var, _)| Type::Variable(var.name.clone(), UniqueID(0), true)).collect()
+}
+
+/// Trait bounds are passed to a function as trailing arguments holding the trait's methods for the type,
+/// which the caller can't give, and are filled in at each call site with the implementation for the argument's type
+fn make_bound_args(pos: Pos, bounds: Vec<(Ident, Vec<Ident>)>) -> Vec<Argument> {
+    let mut args = vec!();
+    for (var, traits) in bounds {
+        for ident in traits {
+            let name = Ident::from_str(&format!("__{}_{}", ident.name, var.name));
+            let ttype = Type::Object(ident.name.clone(), UniqueID(0), vec!(Type::Variable(var.name.clone(), UniqueID(0), true)));
+            args.push(Argument::new(pos.clone(), name, Some(ttype), None));
+        }
+    }
+    args
+}
+
+fn add_bound_args_to_decl(pos: Pos, mut args: Vec<Argument>, ttype: Type, bounds: Vec<(Ident, Vec<Ident>)>) -> (Vec<Argument>, Type) {
+    let bargs = make_bound_args(pos, bounds);
+    match ttype {
+        Type::Function(argtypes, rettype, abi) if bargs.len() > 0 => {
+            let mut types = argtypes.as_vec();
+            types.extend(bargs.iter().map(|arg| arg.ttype.clone().unwrap()));
+            args.extend(bargs);
+            (args, Type::Function(r(Type::Tuple(types)), rettype, abi))
+        },
+        ttype => (args, ttype),
+    }
+}
+
+impl AST {
+    fn destructure_args(pos: Pos, args: Vec<(Argument, Option<Pattern>)>, body: AST) -> (Vec<Argument>, AST) {
+        let mut destructures = vec!();
+        let args = args.into_iter().map(|(arg, pat)| {
+            if let Some(pat) = pat {
+                destructures.push(AST::make_destructure(arg.pos.clone(), pat, AST::make_ident(arg.pos.clone(), arg.ident.clone())));
+            }
+            arg
+        }).collect();
+
+        if destructures.len() == 0 {
+            (args, body)
+        } else {
+            destructures.push(body);
+            (args, AST::make_block(pos, destructures))
+        }
+    }
+}
+
 named!(reference(Span) -> AST,
     do_parse!(
         pos: position!() >>
@@ -466,12 +828,21 @@ named!(annotation(Span) -> AST,
     )
 );
 
-named!(argument_list(Span) -> Vec<Argument>,
+named!(argument_list(Span) -> Vec<(Argument, Option<Pattern>)>,
     separated_list_complete!(wscom!(tag!(",")),
-        do_parse!(
-            i: identifier_typed >>
-            d: opt!(preceded!(wscom!(tag!("=")), expression)) >>
-            (Argument::new(i.0, i.1, i.2, d))
+        alt_complete!(
+            do_parse!(
+                i: identifier_typed >>
+                d: opt!(preceded!(wscom!(tag!("=")), expression)) >>
+                ((Argument::new(i.0, i.1, i.2, d), None))
+            ) |
+            do_parse!(
+                pos: position!() >>
+                p: alt_complete!(pattern_tuple | pattern_record) >>
+                t: opt!(preceded!(wscom!(tag!(":")), type_description)) >>
+                // The argument is given a generated name, and destructured at the start of the function body
+                ((Argument::new(Pos::new(pos), Ident::new(format!("{}", NodeID::generate())), t, None), Some(p)))
+            )
         )
     )
 );
@@ -602,7 +973,8 @@ named!(subatomic_operation(Span) -> AST,
         left: subatomic >>
         operations: many0!(alt_complete!(
             map!(delimited!(tag!("["), tuple!(position!(), wscom!(expression)), tag!("]")), |(p, e)| SubOP::Index(Pos::new(p), e)) |
-            map!(delimited!(tag!("("), tuple!(position!(), wscom!(expression_list)), tag!(")")), |(p, e)| SubOP::Invoke(Pos::new(p), e)) |
+            map!(delimited!(tag!("("), tuple!(position!(), wscom!(invoke_argument_list)), tag!(")")), |(p, e)| SubOP::Invoke(Pos::new(p), vec!(), e)) |
+            map!(pair!(preceded!(tag!("::"), type_arguments), delimited!(tag!("("), tuple!(position!(), wscom!(invoke_argument_list)), tag!(")"))), |(t, (p, e))| SubOP::Invoke(Pos::new(p), t, e)) |
             map!(preceded!(tag!("."), tuple!(position!(), alt!(identifier | map!(digit, |s| Ident::from_span(s))))), |(p, s)| SubOP::Accessor(Pos::new(p), s)) |
             map!(preceded!(tag!("::"), tuple!(position!(), identifier)), |(p, s)| SubOP::Resolver(Pos::new(p), s))
         )) >>
@@ -610,9 +982,18 @@ named!(subatomic_operation(Span) -> AST,
     )
 );
 
+// The types are preceded by `::`, like `swap::<Int, String>(p)`, so they aren't confused with comparisons, as in `f(a < b, c > (d))`
+named!(type_arguments(Span) -> Vec<Type>,
+    delimited!(
+        tag!("<"),
+        wscom!(separated_list_complete!(wscom!(tag!(",")), type_description)),
+        tag!(">")
+    )
+);
+
 enum SubOP {
     Index(Pos, AST),
-    Invoke(Pos, Vec<AST>),
+    Invoke(Pos, Vec<Type>, Vec<AST>),
     Accessor(Pos, Ident),
     Resolver(Pos, Ident),
 }
@@ -623,7 +1004,7 @@ impl AST {
         for op in operations {
             match op {
                 SubOP::Index(p, e) => ret = AST::make_index(p, ret, e),
-                SubOP::Invoke(p, e) => ret = AST::make_invoke(p, ret, e),
+                SubOP::Invoke(p, t, e) => ret = AST::make_invoke_with_types(p, ret, t, e),
                 SubOP::Accessor(p, name) => ret = AST::make_access(p, ret, name.clone()),
                 SubOP::Resolver(p, name) => ret = AST::make_resolve(p, ret, name.clone()),
             }
@@ -715,14 +1096,18 @@ pub fn parse_type(s: &str) -> Option<Type> {
 }
 
 named!(type_description(Span) -> Type,
-    alt_complete!(
-        type_function |
-        type_unit |
-        type_ref |
-        type_tuple |
-        type_record |
-        type_variable |
-        type_object
+    do_parse!(
+        t: alt_complete!(
+            type_function |
+            type_unit |
+            type_ref |
+            type_tuple |
+            type_record |
+            type_variable |
+            type_object
+        ) >>
+        n: opt!(complete!(tag!("?"))) >>
+        (if n.is_some() { Type::Nullable(r(t)) } else { t })
     )
 );
 
@@ -804,6 +1189,8 @@ named!(literal(Span) -> AST,
         unit |
         nil |
         boolean |
+        raw_string |
+        byte_string |
         string |
         character |
         number |
@@ -828,42 +1215,202 @@ named!(boolean(Span) -> AST,
     )
 );
 
-named!(escaped_character(Span) -> &[u8],
+named!(escaped_character(Span) -> char,
     alt!(
-        tag!("\\") => { |_| &b"\\"[..] } |
-        tag!("\"") => { |_| &b"\""[..] } |
-        tag!("n")  => { |_| &b"\n"[..] } |
-        tag!("r")  => { |_| &b"\r"[..] } |
-        tag!("t")  => { |_| &b"\t"[..] } |
-        tag!("0")  => { |_| &b"\0"[..] }
+        tag!("\\") => { |_| '\\' } |
+        tag!("\"") => { |_| '"' } |
+        tag!("$")  => { |_| '$' } |
+        tag!("n")  => { |_| '\n' } |
+        tag!("r")  => { |_| '\r' } |
+        tag!("t")  => { |_| '\t' } |
+        tag!("0")  => { |_| '\0' } |
+        unicode_escape
     )
 );
 
-named!(string_contents(Span) -> AST,
-    map!(
-        escaped_transform!(is_not!("\"\\"), '\\', escaped_character),
-        |s| AST::make_lit(Literal::String(String::from_utf8_lossy(&s).into_owned()))
+named!(unicode_escape(Span) -> char,
+    map_opt!(
+        delimited!(tag!("u{"), hex_digit, tag!("}")),
+        |s: Span| u32::from_str_radix(str::from_utf8(&s.fragment).unwrap(), 16).ok().and_then(std::char::from_u32)
+    )
+);
+
+enum StringPiece {
+    Text(Vec<u8>),
+    Escape(char),
+    Expr(AST),
+}
+
+named!(string_piece(Span) -> StringPiece,
+    alt_complete!(
+        map!(delimited!(
+            tag!("${"),
+            wscom!(expression),
+            return_error!(ErrorKind::Custom(ERR_IN_STRING), tag!("}"))
+        ), |e| StringPiece::Expr(e)) |
+        map!(preceded!(tag!("\\"), escaped_character), |c| StringPiece::Escape(c)) |
+        map!(terminated!(tag!("$"), not!(peek!(tag!("{")))), |s| StringPiece::Text(s.fragment.to_vec())) |
+        map!(is_not!("\"\\$"), |s| StringPiece::Text(s.fragment.to_vec()))
     )
 );
 
 named!(string(Span) -> AST,
-    delimited!(
-        tag!("\""),
-        alt!(
-            string_contents |
-            value!(AST::make_lit(Literal::String(String::new())), tag!(""))
-        ),
-        tag!("\"")
+    alt_complete!(
+        multiline_string |
+        do_parse!(
+            pos: position!() >>
+            tag!("\"") >>
+            l: many0!(string_piece) >>
+            tag!("\"") >>
+            (AST::fold_string(Pos::new(pos), l))
+        )
     )
 );
 
+named!(multiline_string(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        tag!("\"\"\"") >>
+        l: many0!(alt_complete!(
+            string_piece |
+            map!(terminated!(tag!("\""), not!(peek!(tag!("\"\"")))), |s| StringPiece::Text(s.fragment.to_vec()))
+        )) >>
+        return_error!(ErrorKind::Custom(ERR_IN_STRING), tag!("\"\"\"")) >>
+        (AST::fold_string(Pos::new(pos), strip_indent(l)))
+    )
+);
+
+named!(raw_string(Span) -> AST,
+    do_parse!(
+        tag!("r\"") >>
+        s: opt!(is_not!("\"")) >>
+        tag!("\"") >>
+        (AST::make_lit(Literal::String(s.map(|s| String::from_utf8_lossy(&s.fragment).into_owned()).unwrap_or(String::new()))))
+    )
+);
+
+named!(byte_string(Span) -> AST,
+    do_parse!(
+        pos: position!() >>
+        tag!("b\"") >>
+        l: many0!(alt_complete!(
+            map!(preceded!(tag!("\\"), escaped_character), |c| StringPiece::Escape(c)) |
+            map!(is_not!("\"\\"), |s| StringPiece::Text(s.fragment.to_vec()))
+        )) >>
+        tag!("\"") >>
+        (AST::make_lit(Literal::ByteString(Pos::new(pos), join_text(l))))
+    )
+);
+
+fn join_text(pieces: Vec<StringPiece>) -> Vec<u8> {
+    let mut text = vec!();
+    for piece in pieces {
+        match piece {
+            StringPiece::Text(s) => text.extend(s),
+            StringPiece::Escape(c) => text.extend(c.to_string().as_bytes()),
+            StringPiece::Expr(_) => panic!("InternalError: unexpected expression in string text"),
+        }
+    }
+    text
+}
+
+fn strip_indent(mut pieces: Vec<StringPiece>) -> Vec<StringPiece> {
+    // Drop the line break that directly follows the opening quotes, and the last line if it only holds the closing quotes' indentation
+    let mut count = None;
+    if let Some(StringPiece::Text(ref mut text)) = pieces.first_mut() {
+        let skip = if text.starts_with(b"\n") { 1 } else if text.starts_with(b"\r\n") { 2 } else { 0 };
+        if skip > 0 {
+            text.drain(..skip);
+            count = Some(0);
+        }
+    }
+    let first_line = count.is_some();
+    if let Some(StringPiece::Text(ref mut text)) = pieces.last_mut() {
+        if let Some(i) = text.iter().rposition(|c| *c == b'\n') {
+            if text[i + 1..].iter().all(|c| *c == b' ' || *c == b'\t') {
+                let i = if i > 0 && text[i - 1] == b'\r' { i - 1 } else { i };
+                text.truncate(i);
+            }
+        }
+    }
+
+    // Find the smallest indentation of all the lines that aren't blank
+    let mut indent = usize::max_value();
+    for piece in pieces.iter() {
+        match piece {
+            StringPiece::Text(text) => {
+                for ch in text.iter() {
+                    count = match (count, *ch) {
+                        (_, b'\n') => Some(0),
+                        (Some(n), b' ') | (Some(n), b'\t') => Some(n + 1),
+                        (Some(n), b'\r') => Some(n),
+                        (Some(n), _) => { indent = indent.min(n); None },
+                        (None, _) => None,
+                    };
+                }
+            },
+            _ => if let Some(n) = count.take() { indent = indent.min(n); },
+        }
+    }
+
+    // Remove that much leading whitespace from the start of each line
+    let indent = if indent == usize::max_value() { 0 } else { indent };
+    let mut at_start = if first_line { Some(indent) } else { None };
+    let mut stripped = vec!();
+    for piece in pieces {
+        match piece {
+            StringPiece::Text(text) => {
+                let mut result = vec!();
+                for ch in text {
+                    at_start = match (at_start, ch) {
+                        (_, b'\n') => { result.push(ch); Some(indent) },
+                        (Some(n), b' ') | (Some(n), b'\t') if n > 0 => Some(n - 1),
+                        _ => { result.push(ch); None },
+                    };
+                }
+                stripped.push(StringPiece::Text(result));
+            },
+            piece @ _ => { at_start = None; stripped.push(piece); },
+        }
+    }
+    stripped
+}
+
+impl AST {
+    fn fold_string(pos: Pos, pieces: Vec<StringPiece>) -> Self {
+        let mut parts = vec!();
+        let mut text: Vec<u8> = vec!();
+        for piece in pieces {
+            match piece {
+                StringPiece::Text(s) => text.extend(s),
+                StringPiece::Escape(c) => text.extend(c.to_string().as_bytes()),
+                StringPiece::Expr(e) => {
+                    if text.len() > 0 {
+                        parts.push(AST::make_lit(Literal::String(String::from_utf8_lossy(&text).into_owned())));
+                        text.clear();
+                    }
+                    parts.push(e);
+                },
+            }
+        }
+
+        if parts.len() == 0 {
+            return AST::make_lit(Literal::String(String::from_utf8_lossy(&text).into_owned()));
+        }
+        if text.len() > 0 {
+            parts.push(AST::make_lit(Literal::String(String::from_utf8_lossy(&text).into_owned())));
+        }
+        AST::make_interpolate(pos, parts)
+    }
+}
+
 
 named!(character(Span) -> AST,
     map!(
         delimited!(
             tag!("\'"),
             alt!(
-                map!(preceded!(tag!("\\"), escaped_character), |c| c[0] as i32) |
+                map!(preceded!(tag!("\\"), escaped_character), |c| c as i32) |
                 map!(anychar, |c| c as i32)
             ),
             tag!("\'")
@@ -993,7 +1540,9 @@ named!(not_reserved(Span) -> (),
 
 named!(pattern(Span) -> Pattern,
     do_parse!(
-        p: pattern_atomic >>
+        p: map!(separated_nonempty_list_complete!(wscom!(tag!("|")), pattern_atomic), |mut l| {
+            if l.len() == 1 { l.remove(0) } else { Pattern::Or(NodeID::generate(), l) }
+        }) >>
         a: opt!(preceded!(wscom!(tag!(":")), type_description)) >>
         (match a {
             Some(ty) => Pattern::Annotation(NodeID::generate(), ty, r(p)),
@@ -1005,10 +1554,12 @@ named!(pattern(Span) -> Pattern,
 named!(pattern_atomic(Span) -> Pattern,
     alt_complete!(
         value!(Pattern::Wild, tag!("_")) |
+        pattern_range |
         pattern_literal |
         pattern_enum_variant |
         pattern_tuple |
         pattern_record |
+        pattern_list |
         pattern_binding
     )
 );
@@ -1018,12 +1569,22 @@ named!(pattern_literal(Span) -> Pattern,
         unit |
         nil |
         boolean |
+        raw_string |
         string |
         character |
         number
     ), |l| Pattern::Literal(NodeID::generate(), l))
 );
 
+named!(pattern_range(Span) -> Pattern,
+    do_parse!(
+        s: alt_complete!(character | number) >>
+        i: wscom!(alt!(value!(true, tag!("..=")) | value!(false, tag!("..")))) >>
+        e: alt_complete!(character | number) >>
+        (Pattern::Range(NodeID::generate(), s, e, i, NodeID::generate()))
+    )
+);
+
 named!(pattern_binding(Span) -> Pattern,
     map!(identifier, |i| Pattern::Binding(NodeID::generate(), i))
 );
@@ -1038,7 +1599,11 @@ named!(pattern_resolve(Span) -> Pattern,
 
 named!(pattern_enum_variant(Span) -> Pattern,
     do_parse!(
-        p: pattern_resolve >>
+        p: alt_complete!(
+            pattern_resolve |
+            // A bare name with arguments refers to the variant of the same name in the enum of that name
+            map!(terminated!(identifier, peek!(tag!("("))), |i| Pattern::Resolve(NodeID::generate(), r(Pattern::Identifier(NodeID::generate(), i.clone())), i, NodeID::generate()))
+        ) >>
         o: opt!(delimited!(
             tag!("("),
             separated_list_complete!(wscom!(tag!(",")), pattern),
@@ -1062,6 +1627,32 @@ named!(pattern_tuple(Span) -> Pattern,
     )
 );
 
+named!(pattern_list(Span) -> Pattern,
+    map_opt!(
+        delimited!(
+            tag!("["),
+            wscom!(separated_list_complete!(wscom!(tag!(",")), alt_complete!(
+                map!(preceded!(tag!(".."), opt!(pattern_binding)), |b| (true, b.unwrap_or(Pattern::Wild))) |
+                map!(pattern, |p| (false, p))
+            ))),
+            tag!("]")
+        ),
+        |l: Vec<(bool, Pattern)>| {
+            // Only one rest pattern is allowed, which matches the items between those before and after it
+            let mut items = vec!();
+            let mut rest = None;
+            for (is_rest, pat) in l {
+                match (is_rest, rest.is_some()) {
+                    (true, true) => return None,
+                    (true, false) => rest = Some((items.len(), r(pat))),
+                    (false, _) => items.push(pat),
+                }
+            }
+            Some(Pattern::List(NodeID::generate(), items, rest))
+        }
+    )
+);
+
 named!(pattern_record(Span) -> Pattern,
     do_parse!(
         l: delimited!(
@@ -1076,9 +1667,9 @@ named!(pattern_record(Span) -> Pattern,
 named!(pattern_record_field_assignments(Span) -> Vec<(Ident, Pattern)>,
     separated_list_complete!(wscom!(tag!(",")), do_parse!(
         i: identifier >>
-        wscom!(tag!("=")) >>
-        p: pattern >>
-        ((i, p))
+        p: opt!(preceded!(wscom!(tag!("=")), pattern)) >>
+        // A field name on its own binds the field to a variable of the same name
+        ((i.clone(), p.unwrap_or_else(|| Pattern::Binding(NodeID::generate(), i))))
     ))
 );
 
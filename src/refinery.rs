@@ -1,5 +1,5 @@
 
-use std::cell::RefCell;
+use std::cell::{ Cell, RefCell };
 
 use rand;
 
@@ -7,8 +7,10 @@ use abi::ABI;
 use types::Type;
 //use hcode::{ HExpr };
 use misc::{ r, UniqueID };
+use config::Options;
+use macros::MacroDef;
 use session::{ Session, Error };
-use ast::{ NodeID, AST, Mutability, Visibility, AssignType, Ident, ClassSpec, Argument, MatchCase, Pattern, Literal };
+use ast::{ NodeID, Pos, AST, Mutability, Visibility, AssignType, Ident, ClassSpec, Argument, Attribute, ImportKind, MatchCase, Pattern, Literal };
 
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -21,19 +23,41 @@ enum CodeContext {
 pub struct Refinery<'sess> {
     pub session: &'sess Session,
     context: RefCell<Vec<CodeContext>>,
+    tests: RefCell<Vec<(Pos, Ident)>>,
+    // The number of lists of code that the current node is nested in, which is 1 at the top level of the file
+    depth: Cell<usize>,
+    // The names bound by the enclosing functions and blocks, which shadow any macro with the same name
+    locals: RefCell<Vec<Vec<String>>>,
+    filenum: u16,
+    expansions: Cell<usize>,
 }
 
+// The number of nested macro expansions allowed before a macro is assumed to be endlessly recursive
+const MAX_EXPANSIONS: usize = 64;
+
 impl<'sess> Refinery<'sess> {
     pub fn refine(session: &'sess Session, code: Vec<AST>) -> Vec<AST> {
         let refinery = Refinery {
             session: session,
             context: RefCell::new(vec!()),
+            tests: RefCell::new(vec!()),
+            depth: Cell::new(0),
+            locals: RefCell::new(vec!()),
+            filenum: (session.files.borrow().len() - 1) as u16,
+            expansions: Cell::new(0),
         };
 
         //vec!(AST::make_func(Pos::empty(), Some(Ident::new(Pos::empty(), format!("init.{}", "test"))), vec!(), None,
         //    r(AST::make_block(Pos::empty(), refine_vec(code))),
         //ABI::Molten))
-        let refined = refinery.refine_vec(code);
+        let mut refined = refinery.refine_vec(code);
+
+        // Call each of the test functions after the top level code has run
+        for (pos, ident) in refinery.tests.borrow().iter() {
+            refined.push(AST::make_invoke(pos.clone(), AST::make_ident(pos.clone(), ident.clone()), vec!()));
+            refined.push(AST::make_invoke(pos.clone(), AST::make_ident_from_str(pos.clone(), "println"), vec!(AST::make_lit(Literal::String(format!("test {} ... ok", ident.name))))));
+        }
+
         if session.errors.get() > 0 {
             panic!("Exiting due to previous errors");
         }
@@ -47,6 +71,24 @@ impl<'sess> Refinery<'sess> {
         ret
     }
 
+    fn with_locals<F, R>(&self, names: Vec<String>, f: F) -> R where F: FnOnce() -> R {
+        self.locals.borrow_mut().push(names);
+        let ret = f();
+        self.locals.borrow_mut().pop();
+        ret
+    }
+
+    fn add_local(&self, name: &String) {
+        // Names at the top level aren't tracked, because macros can only be defined there
+        if let Some(names) = self.locals.borrow_mut().last_mut() {
+            names.push(name.clone());
+        }
+    }
+
+    fn is_local(&self, name: &String) -> bool {
+        self.locals.borrow().iter().any(|names| names.contains(name))
+    }
+
     fn get_context(&self) -> Option<CodeContext> {
         self.context.borrow().last().map(|c| *c)
     }
@@ -60,51 +102,159 @@ impl<'sess> Refinery<'sess> {
 
     pub fn refine_vec(&self, code: Vec<AST>) -> Vec<AST> {
         let mut block = vec!();
+        self.depth.set(self.depth.get() + 1);
         for node in code {
             let pos = node.get_pos();
             match self.refine_node(node) {
                 Ok(refined) => block.push(refined),
-                Err(err) => self.session.print_error(err.add_pos(&pos)),
+                // Errors that already point at a more specific part of the node keep their position
+                Err(err) => self.session.print_error(if err.pos.is_some() { err } else { err.add_pos(&pos) }),
             }
         }
+        self.depth.set(self.depth.get() - 1);
         block
     }
 
+    fn refine_attributes(&self, node: AST) -> Result<AST, Error> {
+        match node {
+            AST::Attributes(_, _, attrs, node) => {
+                for attr in attrs.iter() {
+                    self.check_attribute(attr, &node)?;
+                }
+                self.session.set_attributes(node.get_id(), attrs);
+                Ok(*node)
+            },
+            node => Ok(node),
+        }
+    }
+
+    fn check_attribute(&self, attr: &Attribute, node: &AST) -> Result<(), Error> {
+        let (targets, nargs) = match attr.ident.name.as_str() {
+            "inline" | "noinline" | "cold" => ("functions", 0),
+            "export_name" => ("functions or declarations", 1),
+            "deprecated" => ("functions, declarations, classes, or definitions", attr.args.len().min(1)),
+            "test" => ("top level functions without arguments", 0),
+            _ => return Err(Error::new_pos(&attr.pos, format!("SyntaxError: unknown attribute @{}", attr.ident.name))),
+        };
+
+        let valid = match (attr.ident.name.as_str(), node) {
+            ("test", AST::Function(_, _, _, Some(_), _, args, _, _, _)) => args.len() == 0 && self.context.borrow().len() == 0 && self.depth.get() == 1,
+            ("test", _) => false,
+            ("export_name", AST::Declare(_, _, _, _, _, _, _)) => true,
+            ("deprecated", AST::Declare(_, _, _, _, _, _, _)) |
+            ("deprecated", AST::Class(_, _, _, _, _)) |
+            ("deprecated", AST::Definition(_, _, _, _, _, _, _)) => true,
+            (_, AST::Function(_, _, _, Some(_), _, _, _, _, _)) => true,
+            _ => false,
+        };
+        if !valid {
+            return Err(Error::new_pos(&attr.pos, format!("SyntaxError: the attribute @{} can only be used on {}", attr.ident.name, targets)));
+        }
+
+        if attr.args.len() != nargs {
+            return Err(Error::new_pos(&attr.pos, format!("SyntaxError: the attribute @{} expects {} arguments but found {}", attr.ident.name, nargs, attr.args.len())));
+        }
+        if (0 .. nargs).any(|i| attr.get_string_arg(i).is_none()) {
+            return Err(Error::new_pos(&attr.pos, format!("SyntaxError: the arguments to @{} must be string literals", attr.ident.name)));
+        }
+        Ok(())
+    }
+
     pub fn refine_node(&self, node: AST) -> Result<AST, Error> {
         Ok(match node {
+            node @ AST::Attributes(_, _, _, _) => {
+                let node = self.refine_attributes(node)?;
+                if self.session.get_attribute(node.get_id(), "test").is_some() {
+                    // Test functions are only compiled when building the tests
+                    if !Options::as_ref().test {
+                        return Ok(AST::make_lit(Literal::Unit));
+                    }
+                    if let AST::Function(_, ref pos, _, Some(ref ident), _, _, _, _, _) = node {
+                        self.tests.borrow_mut().push((pos.clone(), ident.clone()));
+                    }
+                }
+                self.refine_node(node)?
+            },
+
             AST::Block(id, pos, mut code) => {
                 if code.len() == 0 {
                     code.push(AST::make_lit(Literal::Unit))
                 }
-                AST::Block(id, pos, self.refine_vec(code))
+                // Functions can be used before they're defined in the block, but variables can't
+                let names = code.iter().filter_map(|node| match node {
+                    AST::Function(_, _, _, Some(ref ident), _, _, _, _, _) => Some(ident.name.clone()),
+                    _ => None,
+                }).collect();
+                AST::Block(id, pos, self.with_locals(names, || self.refine_vec(code)))
             },
 
-            AST::Definition(id, pos, mutable, ident, ttype, code) => {
-                AST::Definition(id, pos, mutable, ident, ttype, r(self.refine_node(*code)?))
+            AST::Definition(id, pos, vis, mutable, ident, ttype, code) => {
+                // Only class fields can leave out the initializer, because they're assigned by the constructor instead
+                if code.is_none() && self.get_context() != Some(CodeContext::ClassBody) {
+                    return Err(Error::new(format!("SyntaxError: the definition of {:?} must have an initializer", ident.name)));
+                }
+                let code = match code {
+                    Some(code) => Some(r(self.refine_node(*code)?)),
+                    None => None,
+                };
+                if self.get_context() != Some(CodeContext::ClassBody) {
+                    self.add_local(&ident.name);
+                }
+                AST::Definition(id, pos, vis, mutable, ident, ttype, code)
+            },
+
+            AST::Const(id, pos, ident, ttype, code) => {
+                AST::Const(id, pos, ident, ttype, r(self.refine_node(*code)?))
+            },
+
+            AST::Destructure(id, pos, pat, code) => {
+                let code = self.refine_node(*code)?;
+                pat.get_bindings().iter().for_each(|(_, name)| self.add_local(name));
+                AST::Destructure(id, pos, r(self.refine_pattern(*pat)?), r(code))
             },
 
-            AST::Declare(id, pos, vis, ident, ttype) => {
-                AST::Declare(id, pos, vis, ident, ttype)
+            AST::Declare(id, pos, vis, ident, params, args, ttype) => {
+                AST::Declare(id, pos, vis, ident, params, self.refine_args(args)?, ttype)
             },
 
-            AST::Function(id, pos, vis, ident, args, ret, body, abi) => {
-                // TODO visibility is forced here so I don't have to add 'pub' keywords yet
-                let vis = if self.top_level() {
-                    Visibility::Public
-                } else {
-                    Visibility::Private
+            AST::Function(id, pos, vis, ident, params, args, ret, body, abi) => {
+                // Methods keep their member visibility, and functions inside of other functions are always private
+                let vis = match self.get_context() {
+                    Some(CodeContext::ClassBody) => vis,
+                    _ if self.top_level() => vis,
+                    _ => Visibility::Private,
                 };
 
+                if let (Some(ref ident), false) = (ident.as_ref(), self.get_context() == Some(CodeContext::ClassBody)) {
+                    self.add_local(&ident.name);
+                }
+
+                let args = self.refine_args(args)?;
+                let names = args.iter().map(|arg| arg.ident.name.clone()).collect();
                 self.with_context(CodeContext::Func(abi), || {
-                    Ok(AST::Function(id, pos, vis, ident, args, ret, r(self.refine_node(*body)?), abi))
+                    let body = self.with_locals(names, || self.refine_node(*body))?;
+                    Ok(AST::Function(id, pos, vis, ident, params, args, ret, r(body), abi))
                 })?
             },
 
-            AST::Invoke(id, pos, fexpr, mut args) => {
+            AST::Invoke(id, pos, fexpr, types, mut args) => {
+                if let AST::Identifier(ref iid, ref ipos, ref ident) = *fexpr {
+                    // A local name shadows a macro, unless the call comes from the body of another macro
+                    if let Some(macrodef) = self.session.get_macro(&ident.name) {
+                        if !self.is_local(&ident.name) || self.session.is_macro_ref(*iid) {
+                            return self.refine_macro_call(ipos.clone(), ident, macrodef, args);
+                        }
+                    }
+                }
                 if let AST::Accessor(_, _, ref expr, _, _) = *fexpr {
                     args.insert(0, *expr.clone());
                 }
-                AST::Invoke(id, pos, r(self.refine_node(*fexpr)?), self.refine_vec(args))
+                if let Some(i) = args.iter().position(|arg| arg.is_keyword()) {
+                    if args[i..].iter().any(|arg| !arg.is_keyword()) {
+                        return Err(Error::new(format!("SyntaxError: positional arguments cannot follow keyword arguments")));
+                    }
+                }
+                AST::Invoke(id, pos, r(self.refine_node(*fexpr)?), types, self.refine_vec(args))
             },
 
             AST::SideEffect(id, pos, op, args) => {
@@ -140,6 +290,10 @@ impl<'sess> Refinery<'sess> {
             },
 
             AST::For(id, pos, ident, list, body) => {
+                if let AST::Range(_, _, start, end, step, inclusive) = *list {
+                    return self.refine_range_loop(id, pos, ident, *start, *end, step.map(|step| *step), inclusive, *body);
+                }
+
                 let mut block = vec!();
                 let mut cond_block = vec!();
                 let mut body_block = vec!();
@@ -166,7 +320,7 @@ impl<'sess> Refinery<'sess> {
                 body_block.push(AST::make_def(pos.clone(), Mutability::Immutable, ident.clone(), None,
                     self.refine_node(invoke(access_list_field("get"), vec!(access_iter())))?));
 
-                body_block.push(self.refine_node(*body)?);
+                body_block.push(self.with_locals(vec!(ident.name.clone()), || self.refine_node(*body))?);
 
                 // increment the iterator index variable
                 body_block.push(AST::make_assign(pos.clone(), access_iter(), self.refine_node(invoke(AST::make_ident(pos.clone(), Ident::from_str("+")),
@@ -176,6 +330,15 @@ impl<'sess> Refinery<'sess> {
                 AST::make_block(pos.clone(), block)
             },
 
+            AST::Range(_, pos, start, end, step, inclusive) => {
+                let step = step.map(|step| *step).unwrap_or(AST::make_lit(Literal::Integer(1)));
+                let classspec = ClassSpec::new(pos.clone(), Ident::from_str("Range"), vec!());
+                let args = vec!(AST::make_new(pos.clone(), classspec.clone()), *start, *end, step, AST::make_lit(Literal::Boolean(inclusive)));
+                self.refine_node(AST::make_ptr_cast(
+                    Type::from_spec(classspec.clone(), UniqueID(0)),
+                    AST::make_invoke(pos.clone(), AST::make_resolve(pos.clone(), AST::make_ident(pos.clone(), classspec.ident.clone()), Ident::from_str("new")), args)))?
+            },
+
             AST::Ref(id, pos, expr) => { AST::Ref(id, pos, r(self.refine_node(*expr)?)) },
             AST::Deref(id, pos, expr) => { AST::Deref(id, pos, r(self.refine_node(*expr)?)) },
 
@@ -232,11 +395,24 @@ impl<'sess> Refinery<'sess> {
                 let mut has_new = false;
                 let mut has_init = false;
                 let mut newbody = vec!();
+                // Fields without an initializer must be assigned by every constructor, unless they can be nil
+                let unassigned: Vec<String> = body.iter().filter_map(|node| match node {
+                    AST::Definition(_, _, _, _, ident, ttype, None) => match ttype {
+                        Some(Type::Nullable(_)) => None,
+                        _ => Some(ident.name.clone()),
+                    },
+                    _ => None,
+                }).collect();
                 for node in body {
-                    let node = match node {
-                        AST::Function(id, pos, vis, ident, args, ret, mut body, abi) => {
+                    let node = match self.with_context(CodeContext::ClassBody, || self.refine_attributes(node))? {
+                        AST::Function(id, pos, vis, ident, params, args, ret, mut body, abi) => {
                             if ident.as_ref().map(|i| i.name.as_str()) == Some("new") {
                                 has_new = true;
+                                let mut assigned = vec!();
+                                collect_assigned_fields(&body, &mut assigned);
+                                if let Some(name) = unassigned.iter().find(|name| !assigned.contains(name)) {
+                                    return Err(Error::new_pos(&pos, format!("SyntaxError: the field {:?} has no initializer, so it must be assigned by the \"new\" method", name)));
+                                }
                                 if args.len() > 0 && args[0].ident.as_str() == "self" {
                                     body = r(AST::Block(NodeID::generate(), pos.clone(), vec!(*body, AST::Identifier(NodeID::generate(), pos.clone(), Ident::new(String::from("self"))))));
                                 } else {
@@ -244,16 +420,16 @@ impl<'sess> Refinery<'sess> {
                                 }
                             }
                             ident.as_ref().map(|ref ident| if ident.as_str() == "__init__" { has_init = true; });
-                            AST::Function(id, pos, vis, ident, args, ret, body, abi)
+                            AST::Function(id, pos, vis, ident, params, args, ret, body, abi)
                         },
-                        AST::Declare(id, pos, vis, ident, ttype) => {
+                        AST::Declare(id, pos, vis, ident, params, args, ttype) => {
                             if ident.as_str() == "new" {
                                 has_new = true;
                             }
                             if ident.as_str() == "__init__" { has_init = true; }
-                            AST::Declare(id, pos, vis, ident, ttype)
+                            AST::Declare(id, pos, vis, ident, params, args, ttype)
                         },
-                        _ => node
+                        node => node
                     };
                     newbody.push(node);
                 }
@@ -267,7 +443,8 @@ impl<'sess> Refinery<'sess> {
                     let mut init = vec!();
                     for node in &newbody {
                         match node {
-                            AST::Definition(_, _, _, ident, _, value) => {
+                            // Fields without an initializer are left as they were allocated, until they're assigned by the constructor
+                            AST::Definition(_, _, _, _, ident, _, Some(value)) => {
                                 init.push(AST::make_assign(pos.clone(),
                                     AST::make_access(pos.clone(), AST::make_ident_from_str(pos.clone(), "self"), ident.clone()),
                                     *value.clone(),
@@ -285,7 +462,7 @@ impl<'sess> Refinery<'sess> {
                             vec!(AST::make_ident(pos.clone(), Ident::from_str("self")))));
                     }
                     init.push(AST::make_ident_from_str(pos.clone(), "self"));
-                    let initcode = AST::Function(initid, pos.clone(), Visibility::Public, Some(Ident::from_str("__init__")), iargs, None, r(AST::make_block(pos.clone(), init)), ABI::Molten);
+                    let initcode = AST::Function(initid, pos.clone(), Visibility::Public, Some(Ident::from_str("__init__")), vec!(), iargs, None, r(AST::make_block(pos.clone(), init)), ABI::Molten);
                     newbody.push(initcode);
                 }
 
@@ -296,7 +473,7 @@ impl<'sess> Refinery<'sess> {
             },
 
             AST::Index(id, pos, base, index) => {
-                self.refine_node(AST::Invoke(id, pos.clone(), r(AST::Accessor(NodeID::generate(), pos.clone(), base, Ident::new(String::from("[]")), NodeID::generate())), vec!(*index)))?
+                self.refine_node(AST::Invoke(id, pos.clone(), r(AST::Accessor(NodeID::generate(), pos.clone(), base, Ident::new(String::from("[]")), NodeID::generate())), vec!(), vec!(*index)))?
             },
 
             AST::Resolver(id, pos, left, right, oid) => {
@@ -321,46 +498,317 @@ impl<'sess> Refinery<'sess> {
                         AST::Assignment(id, pos, r(self.refine_node(left)?), r(self.refine_node(*right)?), ty)
                     },
                     AST::Index(iid, ipos, base, index) => {
-                        self.refine_node(AST::Invoke(id, pos, r(AST::Accessor(iid, ipos.clone(), base, Ident::new(String::from("[]")), NodeID::generate())), vec!(*index, *right)))?
+                        self.refine_node(AST::Invoke(id, pos, r(AST::Accessor(iid, ipos.clone(), base, Ident::new(String::from("[]")), NodeID::generate())), vec!(), vec!(*index, *right)))?
+                    },
+                    AST::Tuple(_, _, _) |
+                    AST::Record(_, _, _) => {
+                        self.refine_multiple_assign(pos, left, *right)?
                     },
                     _ => return Err(Error::new(format!("SyntaxError: assignment to to an invalid element: {:?}", left))),
                 }
             },
 
-            AST::Import(id, pos, ident, _) => {
+            AST::Import(id, pos, ident, kind, _) => {
                 let path = ident.name.replace(".", "/") + ".dec";
-                let decls = self.session.parse_file(path.as_str(), true);
-                AST::Import(id, pos, ident, self.refine_vec(decls))
+                let mut decls = self.session.parse_file(path.as_str(), true);
+                if kind == ImportKind::All {
+                    // Private functions would otherwise be added to the importing scope, so they're only recorded, for reporting when they're used
+                    decls.retain(|decl| match decl {
+                        AST::Declare(_, _, Visibility::Private, dident, _, _, _) => {
+                            self.session.add_private_import(&dident.name, &ident.name);
+                            false
+                        },
+                        _ => true,
+                    });
+                }
+                AST::Import(id, pos, ident, kind, self.refine_vec(decls))
+            },
+
+            AST::Module(id, pos, ident, body) => {
+                AST::Module(id, pos, ident, self.refine_vec(body))
+            },
+
+            AST::Macro(id, mut pos, ident, args, text, body) => {
+                if self.context.borrow().len() > 0 || self.depth.get() > 1 {
+                    return Err(Error::new_pos(&pos, format!("SyntaxError: macros can only be defined at the top level")));
+                }
+                // Imported declarations are refined again by the importing file, but belong to the file they came from
+                if self.session.get_macro(&ident.name).map(|macrodef| macrodef.id) != Some(id) {
+                    pos.filenum = self.filenum;
+                    self.session.set_macro(&ident.name, MacroDef::new(id, pos.clone(), args.clone(), *body.clone()));
+                }
+                AST::Macro(id, pos, ident, args, text, body)
             },
 
             AST::PtrCast(id, ttype, value) => {
                 AST::PtrCast(id, ttype, r(self.refine_node(*value)?))
             },
 
+            AST::Keyword(id, pos, ident, expr) => {
+                AST::Keyword(id, pos, ident, r(self.refine_node(*expr)?))
+            },
+
+            AST::Interpolate(_, pos, pieces) => {
+                // Convert each embedded expression using the str() overloads, and join the pieces together with +
+                let mut result = None;
+                for piece in pieces {
+                    let piece = match piece {
+                        AST::Literal(_, Literal::String(_)) => piece,
+                        _ => {
+                            // Point errors at the embedded expression inside the literal, where possible
+                            let epos = match piece.get_pos() {
+                                epos if epos.line > 0 => epos,
+                                _ => pos.clone(),
+                            };
+                            AST::make_invoke(epos.clone(), AST::make_ident_from_str(epos, "str"), vec!(piece))
+                        },
+                    };
+                    result = Some(match result {
+                        None => piece,
+                        Some(left) => AST::make_invoke(pos.clone(), AST::make_ident_from_str(pos.clone(), "+"), vec!(left, piece)),
+                    });
+                }
+                self.refine_node(result.unwrap())?
+            },
+
+            AST::Literal(_, Literal::ByteString(pos, bytes)) => {
+                // Byte strings are copied into a newly allocated buffer, one byte at a time
+                let mut block = vec!();
+                let tmpbuf = format!("{}", UniqueID::generate());
+                let access_buf = || AST::make_ident(pos.clone(), Ident::new(tmpbuf.clone()));
+                let invoke = |name, args| AST::make_invoke(pos.clone(), AST::make_ident_from_str(pos.clone(), name), args);
+
+                block.push(AST::make_def(pos.clone(), Mutability::Immutable, Ident::new(tmpbuf.clone()), None,
+                    invoke("bufalloc", vec!(AST::make_lit(Literal::Integer(bytes.len() as isize))))));
+                for (i, byte) in bytes.iter().enumerate() {
+                    block.push(invoke("bufset", vec!(access_buf(), AST::make_lit(Literal::Integer(i as isize)), invoke("byte", vec!(AST::make_lit(Literal::Integer(*byte as isize)))))));
+                }
+                block.push(access_buf());
+
+                let buftype = Type::Object(String::from("Buffer"), UniqueID(0), vec!(Type::Object(String::from("Byte"), UniqueID(0), vec!())));
+                AST::make_ptr_cast(buftype, AST::make_block(pos.clone(), self.refine_vec(block)))
+            },
+
             AST::GetValue(_) => { node },
             AST::Literal(_, _) => { node },
             AST::Nil(_) => { node },
             AST::Identifier(_, _, _) => { node },
             AST::TypeAlias(_, _, _, _) => { node },
-            AST::Enum(_, _, _, _) => { node },
+            AST::Enum(id, pos, classspec, variants, body) => {
+                let body = self.with_context(CodeContext::ClassBody, || {
+                    self.refine_vec(body)
+                });
+                AST::Enum(id, pos, classspec, variants, body)
+            },
+            AST::Trait(_, _, _, _) => { node },
+            AST::Impl(id, pos, ident, ttype, body) => {
+                let body = self.with_context(CodeContext::ClassBody, || {
+                    self.refine_vec(body)
+                });
+                AST::Impl(id, pos, ident, ttype, body)
+            },
 
             //node @ _ => { node }
         })
     }
 
+    pub fn refine_macro_call(&self, pos: Pos, ident: &Ident, macrodef: MacroDef, args: Vec<AST>) -> Result<AST, Error> {
+        if args.iter().any(|arg| arg.is_keyword()) {
+            return Err(Error::new_pos(&pos, format!("SyntaxError: keyword arguments cannot be used with the macro {:?}", ident.name)));
+        }
+        if args.len() != macrodef.args.len() {
+            return Err(Error::new_pos(&pos, format!("SyntaxError: the macro {:?} expects {} arguments but {} were given", ident.name, macrodef.args.len(), args.len())));
+        }
+        if self.expansions.get() >= MAX_EXPANSIONS {
+            // The error is reported where the first expansion started, instead of through every nested expansion
+            let mut outer = &pos;
+            while let Some(ref callsite) = outer.expansion {
+                outer = callsite;
+            }
+            return Err(Error::new_pos(outer, format!("SyntaxError: the macro {:?} was expanded too many times, it might be recursive", ident.name)));
+        }
+
+        let mut callsite = pos.clone();
+        callsite.filenum = self.filenum;
+        let (expanded, refs) = macrodef.expand(&callsite, args);
+        for id in refs {
+            self.session.add_macro_ref(id);
+        }
+
+        self.expansions.set(self.expansions.get() + 1);
+        let result = self.refine_node(expanded);
+        self.expansions.set(self.expansions.get() - 1);
+        result
+    }
+
+    pub fn refine_multiple_assign(&self, pos: Pos, left: AST, right: AST) -> Result<AST, Error> {
+        let mut block = vec!();
+
+        // Each target can only be assigned once, or the result would depend on the order of the assignments
+        let mut targets = vec!();
+        collect_assign_targets(&left, &mut targets)?;
+
+        let mut names = vec!();
+        let mut bind = |target: AST| {
+            let name = Ident::new(format!("{}", NodeID::generate()));
+            names.push((target, name.clone()));
+            Pattern::Binding(NodeID::generate(), name)
+        };
+
+        // the whole value is unpacked into temporaries before anything is assigned, so that swaps are correct
+        let pat = match left {
+            AST::Tuple(id, _, items) => Pattern::Tuple(id, items.into_iter().map(|item| bind(item)).collect()),
+            AST::Record(id, _, items) => Pattern::Record(id, items.into_iter().map(|(ident, item)| (ident, bind(item))).collect()),
+            _ => return Err(Error::new(format!("SyntaxError: assignment to to an invalid element: {:?}", left))),
+        };
+        block.push(AST::make_destructure(pos.clone(), pat, self.refine_node(right)?));
+
+        for (target, name) in names {
+            let value = AST::make_ident(pos.clone(), name);
+            block.push(match target {
+                // a local variable is only a valid target when it is an element of a tuple or record
+                AST::Identifier(_, _, _) => AST::make_assign(pos.clone(), target, value, AssignType::Update),
+                _ => self.refine_node(AST::make_assign(pos.clone(), target, value, AssignType::Update))?,
+            });
+        }
+
+        block.push(AST::make_lit(Literal::Unit));
+        Ok(AST::make_block(pos.clone(), block))
+    }
+
+    pub fn refine_range_loop(&self, id: NodeID, pos: Pos, ident: Ident, start: AST, end: AST, step: Option<AST>, inclusive: bool, body: AST) -> Result<AST, Error> {
+        let mut block = vec!();
+        let mut body_block = vec!();
+
+        let iter = format!("{}", NodeID::generate());
+        let endname = format!("{}", NodeID::generate());
+        let stepname = format!("{}", NodeID::generate());
+
+        let access = |name: &String| AST::make_ident(pos.clone(), Ident::new(name.clone()));
+        let invoke = |op, args| AST::make_invoke(pos.clone(), AST::make_ident_from_str(pos.clone(), op), args);
+        let compare = |ascending| match (ascending, inclusive) {
+            (true, false) => invoke("<", vec!(access(&iter), access(&endname))),
+            (true, true) => invoke("<=", vec!(access(&iter), access(&endname))),
+            (false, false) => invoke(">", vec!(access(&iter), access(&endname))),
+            (false, true) => invoke(">=", vec!(access(&iter), access(&endname))),
+        };
+
+        // define the counter, the end value, and the step, so that each is only evaluated once
+        block.push(AST::make_def(pos.clone(), Mutability::Mutable, Ident::new(iter.clone()), None, self.refine_node(start)?));
+        block.push(AST::make_def(pos.clone(), Mutability::Immutable, Ident::new(endname.clone()), None, self.refine_node(end)?));
+        let step = step.unwrap_or(AST::make_lit(Literal::Integer(1)));
+        block.push(AST::make_def(pos.clone(), Mutability::Immutable, Ident::new(stepname.clone()), None, self.refine_node(step.clone())?));
+
+        // the direction of the comparison is only known at runtime if the step isn't a literal
+        let cond = match step {
+            AST::Literal(_, Literal::Integer(0)) => return Err(Error::new(format!("SyntaxError: the step of a range cannot be zero"))),
+            AST::Literal(_, Literal::Integer(num)) => compare(num > 0),
+            _ => {
+                let zero = || AST::make_lit(Literal::Integer(0));
+                AST::make_side_effect(pos.clone(), Ident::from_str("or"), vec!(
+                    AST::make_side_effect(pos.clone(), Ident::from_str("and"), vec!(invoke(">", vec!(access(&stepname), zero())), compare(true))),
+                    AST::make_side_effect(pos.clone(), Ident::from_str("and"), vec!(invoke("<", vec!(access(&stepname), zero())), compare(false)))
+                ))
+            },
+        };
+
+        // assign the counter to the item variable, and increment it after the body
+        body_block.push(AST::make_def(pos.clone(), Mutability::Immutable, ident.clone(), None, access(&iter)));
+        body_block.push(self.with_locals(vec!(ident.name.clone()), || self.refine_node(body))?);
+        body_block.push(AST::make_assign(pos.clone(), access(&iter), self.refine_node(invoke("+", vec!(access(&iter), access(&stepname))))?, AssignType::Update));
+
+        block.push(AST::While(id, pos.clone(), r(self.refine_node(cond)?), r(AST::make_block(pos.clone(), body_block))));
+        Ok(AST::make_block(pos.clone(), block))
+    }
+
+    pub fn refine_args(&self, args: Vec<Argument>) -> Result<Vec<Argument>, Error> {
+        let mut refined = vec!();
+        for mut arg in args {
+            arg.default = match arg.default {
+                Some(default) => Some(self.refine_node(default)?),
+                None => None,
+            };
+            refined.push(arg);
+        }
+        Ok(refined)
+    }
+
     pub fn refine_cases(&self, cases: Vec<MatchCase>) -> Result<Vec<MatchCase>, Error> {
         let mut refined = vec!();
         for case in cases {
-            let pat = self.refine_pattern(case.pat)?;
-            let body = self.refine_node(case.body)?;
-            refined.push(MatchCase::new(pat, body));
+            let MatchCase { pos, pat, guard, body, .. } = case;
+            let names = pat.get_bindings().into_iter().map(|(_, name)| name).collect();
+            let pat = self.refine_pattern(pat)?;
+            let (guard, body) = self.with_locals(names, || {
+                let guard = match guard {
+                    Some(guard) => Some(self.refine_node(guard)?),
+                    None => None,
+                };
+                Ok((guard, self.refine_node(body)?))
+            })?;
+            refined.push(MatchCase::new(pos, pat, guard, body));
         }
         Ok(refined)
     }
 
     pub fn refine_pattern(&self, pat: Pattern) -> Result<Pattern, Error> {
-        // TODO refine the pattern, if needed
-        Ok(pat)
+        let refine_vec = |pats: Vec<Pattern>| pats.into_iter().map(|pat| self.refine_pattern(pat)).collect::<Result<Vec<Pattern>, Error>>();
+        match pat {
+            Pattern::Literal(_, AST::Interpolate(_, _, _)) => Err(Error::new(format!("SyntaxError: string interpolation cannot be used in a pattern"))),
+            Pattern::Annotation(id, ttype, pat) => Ok(Pattern::Annotation(id, ttype, r(self.refine_pattern(*pat)?))),
+            Pattern::EnumArgs(id, left, args) => Ok(Pattern::EnumArgs(id, left, refine_vec(args)?)),
+            Pattern::Tuple(id, items) => Ok(Pattern::Tuple(id, refine_vec(items)?)),
+            Pattern::Record(id, items) => {
+                let mut refined = vec!();
+                for (ident, item) in items {
+                    refined.push((ident, self.refine_pattern(item)?));
+                }
+                Ok(Pattern::Record(id, refined))
+            },
+            Pattern::List(id, items, rest) => {
+                let rest = match rest {
+                    Some((index, rest)) => Some((index, r(self.refine_pattern(*rest)?))),
+                    None => None,
+                };
+                Ok(Pattern::List(id, refine_vec(items)?, rest))
+            },
+            Pattern::Or(id, alts) => Ok(Pattern::Or(id, refine_vec(alts)?)),
+            _ => Ok(pat),
+        }
+    }
+}
+
+fn collect_assign_targets(node: &AST, targets: &mut Vec<String>) -> Result<(), Error> {
+    match node {
+        AST::Tuple(_, _, items) => items.iter().map(|item| collect_assign_targets(item, targets)).collect(),
+        AST::Record(_, _, items) => items.iter().map(|(_, item)| collect_assign_targets(item, targets)).collect(),
+        _ => match get_target_path(node) {
+            Some(path) if targets.contains(&path) => Err(Error::new_pos(&node.get_pos(), format!("SyntaxError: {:?} is assigned more than once in the same assignment", path))),
+            Some(path) => { targets.push(path); Ok(()) },
+            None => Ok(()),
+        },
+    }
+}
+
+// Only the assignments that are always run count, so any inside of a condition or loop are skipped
+fn collect_assigned_fields(node: &AST, fields: &mut Vec<String>) {
+    match node {
+        AST::Block(_, _, code) => code.iter().for_each(|node| collect_assigned_fields(node, fields)),
+        AST::Assignment(_, _, left, _, _) => {
+            let mut targets = vec!();
+            if collect_assign_targets(left, &mut targets).is_ok() {
+                fields.extend(targets.iter().filter_map(|path| path.strip_prefix("self.")).map(|name| name.to_string()));
+            }
+        },
+        _ => { },
     }
 }
 
+fn get_target_path(node: &AST) -> Option<String> {
+    match node {
+        AST::Identifier(_, _, ident) => Some(ident.name.clone()),
+        AST::Accessor(_, _, left, field, _) => get_target_path(left).map(|path| format!("{}.{}", path, field.name)),
+        AST::Deref(_, _, value) => get_target_path(value).map(|path| format!("*{}", path)),
+        _ => None,
+    }
+}
@@ -31,6 +31,7 @@ pub struct BindInfo {
 pub struct Scope {
     pub context: Cell<Context>,
     pub basename: RefCell<String>,
+    pub modname: RefCell<Option<String>>,
     pub names: RefCell<HashMap<String, BindInfo>>,
     pub types: RefCell<HashMap<String, BindInfo>>,
     pub parent: Option<ScopeRef>,
@@ -48,6 +49,7 @@ impl Scope {
         Scope {
             context: Cell::new(Context::Local),
             basename: RefCell::new(String::from("")),
+            modname: RefCell::new(None),
             names: RefCell::new(HashMap::new()),
             types: RefCell::new(HashMap::new()),
             parent: parent,
@@ -285,6 +287,17 @@ impl Scope {
         }
     }
 
+    pub fn is_ancestor(ancestor: ScopeRef, scope: ScopeRef) -> bool {
+        if Rc::ptr_eq(&ancestor, &scope) {
+            true
+        } else {
+            match scope.parent {
+                Some(ref parent) => Scope::is_ancestor(ancestor, parent.clone()),
+                None => false,
+            }
+        }
+    }
+
     pub fn global(scope: ScopeRef) -> ScopeRef {
         if scope.is_global() {
             scope
@@ -340,6 +353,7 @@ impl Scope {
             Type::Record(types) => Type::Record(types.into_iter().map(|(n, t)| (n, self.map_typevars(session, varmap, t))).collect()),
             Type::Ambiguous(variants) => Type::Ambiguous(self.map_typevars_vec(session, varmap, variants)),
             Type::Ref(ttype) => Type::Ref(r(self.map_typevars(session, varmap, *ttype))),
+            Type::Nullable(ttype) => Type::Nullable(r(self.map_typevars(session, varmap, *ttype))),
             Type::Object(name, id, types) => Type::Object(name.clone(), id, self.map_typevars_vec(session, varmap, types)),
         }
     }
@@ -379,6 +393,16 @@ impl Scope {
         *self.basename.borrow_mut() = name;
     }
 
+    pub fn set_module_name(&self, name: String) {
+        *self.modname.borrow_mut() = Some(name);
+    }
+
+    pub fn get_module_path(&self) -> Vec<String> {
+        let mut path = self.parent.as_ref().map_or(vec!(), |parent| parent.get_module_path());
+        path.extend(self.modname.borrow().clone());
+        path
+    }
+
     pub fn get_full_name(&self, name: Option<String>, id: UniqueID) -> String {
         let mut base = self.get_basename();
         if base.as_str() != "" {
@@ -6,13 +6,14 @@ use std::path::Path;
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::io::prelude::*;
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
 
 use parser;
 use types::Type;
 use config::Options;
 use refinery::Refinery;
-use ast::{ NodeID, Pos, AST };
+use macros::MacroDef;
+use ast::{ NodeID, Pos, Attribute, Pattern, AST };
 use defs::{ Def };
 use scope::{ ScopeRef, ScopeMapRef };
 
@@ -27,6 +28,16 @@ pub struct Session {
     pub defs: RefCell<HashMap<NodeID, Def>>,
     pub refs: RefCell<HashMap<NodeID, NodeID>>,
     pub types: RefCell<HashMap<NodeID, Type>>,
+    pub attributes: RefCell<HashMap<NodeID, Vec<Attribute>>>,
+    pub macros: RefCell<HashMap<String, MacroDef>>,
+    pub const_patterns: RefCell<HashMap<NodeID, NodeID>>,
+    pub bounds: RefCell<HashMap<NodeID, Vec<(NodeID, NodeID, String)>>>,
+    pub impls: RefCell<HashMap<NodeID, Vec<NodeID>>>,
+    pub type_params: RefCell<HashMap<NodeID, Vec<Type>>>,
+    pub macro_refs: RefCell<HashSet<NodeID>>,
+    pub private_imports: RefCell<HashMap<String, String>>,
+    pub default_args: RefCell<HashMap<(NodeID, NodeID), AST>>,
+    pub trait_args: RefCell<HashMap<NodeID, Vec<AST>>>,
 }
 
 
@@ -41,6 +52,16 @@ impl Session {
             defs: RefCell::new(HashMap::new()),
             refs: RefCell::new(HashMap::new()),
             types: RefCell::new(HashMap::new()),
+            attributes: RefCell::new(HashMap::new()),
+            macros: RefCell::new(HashMap::new()),
+            const_patterns: RefCell::new(HashMap::new()),
+            bounds: RefCell::new(HashMap::new()),
+            impls: RefCell::new(HashMap::new()),
+            type_params: RefCell::new(HashMap::new()),
+            macro_refs: RefCell::new(HashSet::new()),
+            private_imports: RefCell::new(HashMap::new()),
+            default_args: RefCell::new(HashMap::new()),
+            trait_args: RefCell::new(HashMap::new()),
         }
     }
 
@@ -81,8 +102,11 @@ impl Session {
     pub fn write_link_file(&self) {
         let mut link_text = String::new();
         for (ref file, _) in self.files.borrow().iter() {
+            // A module can be imported more than once, but only needs to be linked once
             let source = file.rsplitn(2, '.').collect::<Vec<&str>>()[1];
-            link_text = link_text + source + "\n";
+            if !link_text.lines().any(|line| line == source) {
+                link_text = link_text + source + "\n";
+            }
         }
 
         let mut link_file = File::create(format!("{}.l", self.target)).expect("Error creating link file");
@@ -93,13 +117,31 @@ impl Session {
 
     pub fn print_error(&self, err: Error) {
         self.errors.set(self.errors.get() + 1);
+        println!("{}", self.format_message(err, 31));
+    }
+
+    pub fn print_warning(&self, err: Error) {
+        eprintln!("{}", self.format_message(err, 33));
+    }
+
+    fn format_message(&self, err: Error, colour: u8) -> String {
         let fborrow = self.files.borrow();
         if let Some(ref pos) = err.pos {
             let filename = &fborrow[pos.filenum as usize].0;
             let exerpt = pos.exerpt(fborrow[pos.filenum as usize].1.as_bytes());
-            println!("\x1B[1;31m{}:{:?}: {}\n\tat {}\x1B[0m", filename, pos, err.msg, exerpt);
+            let mut text = format!("\x1B[1;{}m{}:{:?}: {}\n\tat {}", colour, filename, pos, err.msg, exerpt);
+
+            // Code produced by a macro also points at each of the places where it was expanded
+            let mut expansion = pos.expansion.as_ref();
+            while let Some(callsite) = expansion {
+                let filename = &fborrow[callsite.filenum as usize].0;
+                let exerpt = callsite.exerpt(fborrow[callsite.filenum as usize].1.as_bytes());
+                text.push_str(&format!("\n{}:{:?}: in the expansion of a macro\n\tat {}", filename, callsite, exerpt));
+                expansion = callsite.expansion.as_ref();
+            }
+            format!("{}\x1B[0m", text)
         } else {
-            println!("\x1B[1;31m{}\n\x1B[0m", err.msg);
+            format!("\x1B[1;{}m{}\n\x1B[0m", colour, err.msg)
         }
     }
 
@@ -173,6 +215,103 @@ debug!("SET REF: {:?} -> {:?}", id, defid);
         Ok(())
     }
 
+    pub fn set_attributes(&self, id: NodeID, attrs: Vec<Attribute>) {
+        self.attributes.borrow_mut().insert(id, attrs);
+    }
+
+    pub fn get_attribute(&self, id: NodeID, name: &str) -> Option<Attribute> {
+        self.attributes.borrow().get(&id).and_then(|attrs| attrs.iter().find(|attr| attr.ident.name == name).cloned())
+    }
+
+    pub fn set_macro(&self, name: &str, macrodef: MacroDef) {
+        self.macros.borrow_mut().insert(String::from(name), macrodef);
+    }
+
+    pub fn get_macro(&self, name: &str) -> Option<MacroDef> {
+        self.macros.borrow().get(name).cloned()
+    }
+
+    /// Record that the identifier came from the body of a macro, and refers to a name at the top level instead of at the call site
+    pub fn add_macro_ref(&self, id: NodeID) {
+        self.macro_refs.borrow_mut().insert(id);
+    }
+
+    pub fn is_macro_ref(&self, id: NodeID) -> bool {
+        self.macro_refs.borrow().contains(&id)
+    }
+
+    /// Record that the function was declared as private by an imported module, so it was left out of the importing scope
+    pub fn add_private_import(&self, name: &str, module: &str) {
+        self.private_imports.borrow_mut().insert(String::from(name), String::from(module));
+    }
+
+    pub fn get_private_import(&self, name: &str) -> Option<String> {
+        self.private_imports.borrow().get(name).cloned()
+    }
+
+    /// Record the checked copy of an argument's default value, which is passed by the call in place of the omitted argument
+    pub fn set_default_arg(&self, invid: NodeID, argid: NodeID, value: AST) {
+        self.default_args.borrow_mut().insert((invid, argid), value);
+    }
+
+    pub fn get_default_arg(&self, invid: NodeID, argid: NodeID) -> Option<AST> {
+        self.default_args.borrow().get(&(invid, argid)).cloned()
+    }
+
+    /// Record that the binding pattern names a constant, and is compared against the identifier which refers to it
+    pub fn set_const_pattern(&self, id: NodeID, refid: NodeID) {
+        self.const_patterns.borrow_mut().insert(id, refid);
+    }
+
+    pub fn get_const_pattern(&self, id: NodeID) -> Option<NodeID> {
+        self.const_patterns.borrow().get(&id).cloned()
+    }
+
+    /// Get the names bound by the pattern, leaving out the names of constants
+    pub fn get_pattern_bindings(&self, pat: &Pattern) -> Vec<(NodeID, String)> {
+        pat.get_bindings().into_iter().filter(|(id, _)| self.get_const_pattern(*id).is_none()).collect()
+    }
+
+    /// Record that the typevar is bound by the trait, with the given argument holding the trait's methods
+    pub fn add_bound(&self, varid: NodeID, traitid: NodeID, argid: NodeID, argname: &String) {
+        self.bounds.borrow_mut().entry(varid).or_insert(vec!()).push((traitid, argid, argname.clone()));
+    }
+
+    pub fn get_bounds(&self, varid: NodeID) -> Vec<(NodeID, NodeID, String)> {
+        self.bounds.borrow().get(&varid).cloned().unwrap_or(vec!())
+    }
+
+    /// Check if the argument was added to hold the methods of a trait bound, rather than being given by the caller
+    pub fn is_bound_arg(&self, argid: NodeID) -> bool {
+        self.bounds.borrow().values().any(|bounds| bounds.iter().any(|(_, id, _)| *id == argid))
+    }
+
+    /// Record the values passed for the trait bounds of the function called by the invoke, once its types are resolved
+    pub fn set_trait_args(&self, invid: NodeID, values: Vec<AST>) {
+        self.trait_args.borrow_mut().insert(invid, values);
+    }
+
+    pub fn get_trait_args(&self, invid: NodeID) -> Option<Vec<AST>> {
+        self.trait_args.borrow().get(&invid).cloned()
+    }
+
+    /// Record the type parameters explicitly declared by a function, or the types given for them at a call site
+    pub fn set_type_params(&self, id: NodeID, params: Vec<Type>) {
+        self.type_params.borrow_mut().insert(id, params);
+    }
+
+    pub fn get_type_params(&self, id: NodeID) -> Option<Vec<Type>> {
+        self.type_params.borrow().get(&id).cloned()
+    }
+
+    pub fn add_impl(&self, typeid: NodeID, implid: NodeID) {
+        self.impls.borrow_mut().entry(typeid).or_insert(vec!()).push(implid);
+    }
+
+    pub fn get_impls(&self, typeid: NodeID) -> Vec<NodeID> {
+        self.impls.borrow().get(&typeid).cloned().unwrap_or(vec!())
+    }
+
     pub fn resolve_types(&self) {
         use types;
 
@@ -1,38 +1,59 @@
 
 
+use std::cell::{ Cell, RefCell };
+use std::collections::HashMap;
+
 use defs::Def;
+use defs::functions::arrange_args;
+use defs::traits::{ ImplDefRef, split_bound_types };
 use session::{ Session, Error };
 use scope::{ Scope, ScopeRef };
-use ast::{ NodeID, AssignType, ClassSpec, Literal, Pattern, AST };
-use types::{ Type, Check, ABI, expect_type, resolve_type, check_type_params };
-use misc::{ r };
+use ast::{ NodeID, Pos, Ident, Visibility, AssignType, ClassSpec, ImportKind, Literal, Pattern, AST };
+use types::{ Type, Check, ABI, expect_type, check_type, resolve_type, check_type_params };
+use coverage::CoverageChecker;
+use constants;
+use macros;
+use binding::NameBinder;
+use misc::{ r, UniqueID };
 
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct TypeChecker<'sess> {
     pub session: &'sess Session,
     //pub context: RefCell<Vec<CodeContext>>,
+    pub bounds: RefCell<Vec<(Pos, NodeID, Vec<Type>)>>,
+    pub deferred: RefCell<Vec<(ScopeRef, AST, Vec<Type>, Type)>>,
+    pub can_defer: Cell<bool>,
+    pub narrowed: RefCell<Vec<(NodeID, Type)>>,
 }
 
 
 impl<'sess> TypeChecker<'sess> {
-    pub fn check(session: &'sess Session, scope: ScopeRef, code: &Vec<AST>) -> Type {
+    pub fn check(session: &'sess Session, scope: ScopeRef, code: &Vec<AST>, expected: Option<Type>) -> Type {
         let typechecker = TypeChecker {
             session: session,
             //context: RefCell::new(vec!()),
+            bounds: RefCell::new(vec!()),
+            deferred: RefCell::new(vec!()),
+            can_defer: Cell::new(true),
+            narrowed: RefCell::new(vec!()),
         };
 
-        let ttype = typechecker.check_vec(scope, code);
+        let ttype = typechecker.check_vec(scope, code, expected);
+        typechecker.check_deferred();
+        typechecker.resolve_trait_args();
         if session.errors.get() > 0 {
             panic!("Exiting due to previous errors");
         }
         ttype
     }
 
-    pub fn check_vec(&self, scope: ScopeRef, code: &Vec<AST>) -> Type {
+    pub fn check_vec(&self, scope: ScopeRef, code: &Vec<AST>, expected: Option<Type>) -> Type {
         let mut last: Type = scope.make_obj(self.session, String::from("()"), vec!()).unwrap();
-        for node in code {
-            last = self.check_node(scope.clone(), node, None);
+        for (i, node) in code.iter().enumerate() {
+            // Only the last expression gives the value of the block, so only it can use the expected type
+            let etype = if i == code.len() - 1 { expected.clone() } else { None };
+            last = self.check_node(scope.clone(), node, etype);
         }
         last
     }
@@ -58,19 +79,29 @@ impl<'sess> TypeChecker<'sess> {
                     Literal::Integer(_) => scope.make_obj(self.session, String::from("Int"), vec!())?,
                     Literal::Real(_) => scope.make_obj(self.session, String::from("Real"), vec!())?,
                     Literal::String(_) => scope.make_obj(self.session, String::from("String"), vec!())?,
+                    Literal::ByteString(ref pos, _) => return Err(Error::new_pos(pos, format!("InternalError: byte strings should have been desugared before type checking"))),
                 }
             },
 
-            AST::Function(ref id, _, _, ref ident, ref args, _, ref body, ref abi) => {
+            AST::Function(ref id, _, _, ref ident, _, ref args, _, ref body, ref abi) => {
                 let fscope = self.session.map.get(id);
                 let dftype = self.session.get_type(*id).unwrap();
                 let rtype = dftype.get_rettype()?;
 
+                // An anonymous function used where a function type is expected, such as an argument, takes its untyped argument types from it
+                let etypes = match expected.clone().map(|ttype| resolve_type(self.session, ttype, false)) {
+                    Some(Ok(Type::Function(ref eargs, _, _))) if ident.is_none() && eargs.as_vec().len() == args.len() => eargs.as_vec(),
+                    _ => vec!(),
+                };
+
                 let mut argtypes = vec!();
-                for arg in args.iter() {
+                for (i, arg) in args.iter().enumerate() {
                     let ttype = self.session.get_type(arg.id);
                     let vtype = arg.default.clone().map(|ref vexpr| self.check_node(scope.clone(), vexpr, ttype.clone()));
                     let mut atype = expect_type(self.session, fscope.clone(), ttype, vtype, Check::Def)?;
+                    if let (Some(etype), Type::Variable(_, _, false)) = (etypes.get(i).filter(|etype| !has_universal_typevars(etype)), resolve_type(self.session, atype.clone(), false)?) {
+                        atype = expect_type(self.session, fscope.clone(), Some(atype), Some(etype.clone()), Check::Def)?;
+                    }
                     if &arg.ident.name[..] == "self" {
                         let stype = fscope.find_type(self.session, &String::from("Self")).unwrap();
                         atype = expect_type(self.session, fscope.clone(), Some(atype), Some(stype), Check::Def)?;
@@ -103,63 +134,99 @@ impl<'sess> TypeChecker<'sess> {
                 nftype
             },
 
-            AST::Invoke(ref id, _, ref fexpr, ref args) => {
+            AST::Invoke(_, _, _, _, _) if node.get_nil_comparison().is_some() => {
+                let (_, value, nil) = node.get_nil_comparison().unwrap();
+                let vtype = self.check_node(scope.clone(), value, None);
+                let ntype = self.check_node(scope.clone(), nil, Some(Type::Nullable(r(scope.new_typevar(self.session, false)))));
+                expect_type(self.session, scope.clone(), Some(ntype), Some(vtype), Check::Def)?;
+                scope.make_obj(self.session, String::from("Bool"), vec!())?
+            },
+
+            AST::Invoke(_, _, ref fexpr, _, ref args) => {
+                // Arguments that depend on the expected type are checked after the others, using the argument types of the function being called
+                let mut known = vec!();
+                for arg in args {
+                    known.push(if needs_expected_type(arg) { None } else { Some(self.check_node(scope.clone(), arg, None)) });
+                }
+                let etypes = if known.iter().any(|atype| atype.is_none()) { self.get_expected_arg_types(scope.clone(), fexpr.as_ref(), args, &known) } else { vec!() };
                 let mut atypes = vec!();
-                for ref mut value in args {
-                    atypes.push(self.check_node(scope.clone(), value, None));
+                for (i, (arg, atype)) in args.iter().zip(known.into_iter()).enumerate() {
+                    atypes.push(atype.unwrap_or_else(|| self.check_node(scope.clone(), arg, etypes.get(i).cloned().unwrap_or(None))));
                 }
-                let atypes = Type::Tuple(atypes);
-
-                let tscope = Scope::new_ref(Some(scope.clone()));
-                let dtype = self.session_find_variant(tscope.clone(), *id, fexpr.as_ref(), &atypes)?;
-                debug!("INVOKE TYPE: {:?} has type {:?}", id, dtype);
-                let etype = match dtype {
-                    Type::Variable(_, _, _) => dtype.clone(),
-                    _ => tscope.map_all_typevars(self.session, dtype.clone()),
-                };
-
-                let ftype = match etype {
-                    Type::Function(_, _, ref abi) => {
-                        //let ftype = expect_type(self.session, tscope.clone(), Some(etype.clone()), Some(Type::Function(r(atypes), r(expected.unwrap_or_else(|| tscope.new_typevar(self.session, false))), abi)), Check::Update)?;
-                        let ftype = expect_type(self.session, tscope.clone(), Some(etype.clone()), Some(Type::Function(r(atypes), r(etype.get_rettype()?.clone()), *abi)), Check::Def)?;
-                        // TODO should this actually be another expect, so type resolutions that occur in later args affect earlier args?  Might not be needed unless you add typevar constraints
-                        let ftype = resolve_type(self.session, ftype, false)?;        // NOTE This ensures the early arguments are resolved despite typevars not being assigned until later in the signature
-
-                        ftype
-                    },
-                    Type::Variable(_, ref vid, _) => {
-                        let ftype = Type::Function(r(atypes), r(expected.unwrap_or_else(|| tscope.new_typevar(self.session, false))), ABI::Unknown);
-                        self.session.update_type(tscope, *vid, ftype.clone())?;
-                        ftype
-                    },
-                    _ => return Err(Error::new(format!("NotAFunction: {:?}", fexpr))),
-                };
 
-                self.session.update_type(scope.clone(), *id, ftype.clone())?;
-                ftype.get_rettype()?.clone()
+                self.check_invoke_variant(scope.clone(), node, atypes, expected)?
             },
-
-            AST::SideEffect(_, _, _, ref args) => {
+            AST::SideEffect(_, _, ref ident, ref args) => {
+                // The later arguments are only evaluated if the first one is true (for and) or false (for or)
                 let mut ltype = None;
+                let mut narrowed = vec!();
                 for ref expr in args {
-                    ltype = Some(expect_type(self.session, scope.clone(), ltype.clone(), Some(self.check_node(scope.clone(), expr, ltype.clone())), Check::List)?);
+                    let etype = self.with_narrowed(narrowed.clone(), || self.check_node(scope.clone(), expr, ltype.clone()));
+                    ltype = Some(expect_type(self.session, scope.clone(), ltype.clone(), Some(etype), Check::List)?);
+                    narrowed.extend(self.get_narrowed(expr, ident.as_str() == "and"));
                 }
                 ltype.unwrap()
             },
 
-            AST::Definition(ref id, _, _, _, _, ref body) => {
+            AST::Definition(ref id, _, _, _, _, _, ref body) => {
+                let dtype = self.session.get_type(*id);
+                let ctype = body.as_ref().map(|body| self.check_node(scope.clone(), body, dtype.clone()));
+                let btype = expect_type(self.session, scope.clone(), dtype.clone(), ctype, Check::Def)?;
+                self.session.update_type(scope.clone(), *id, btype.clone())?;
+                btype
+            },
+
+            AST::Const(ref id, _, _, _, ref body) => {
+                let errors = self.session.errors.get();
                 let dtype = self.session.get_type(*id);
                 let btype = expect_type(self.session, scope.clone(), dtype.clone(), Some(self.check_node(scope.clone(), body, dtype)), Check::Def)?;
                 self.session.update_type(scope.clone(), *id, btype.clone())?;
+
+                // The value is only evaluated if the expression type checked, to avoid reporting the same error twice
+                if self.session.errors.get() == errors {
+                    let value = constants::evaluate(self.session, body)?;
+                    self.session.get_def(*id)?.as_const()?.set_value(value);
+                }
                 btype
             },
 
-            AST::Declare(ref id, _, _, _, _) => {
-                self.session.get_type(*id).unwrap()
+            AST::Destructure(_, _, ref pat, ref body) => {
+                let btype = self.check_node(scope.clone(), body, None);
+                let ptype = self.check_pattern(scope.clone(), pat, Some(btype.clone()))?;
+                if !self.is_irrefutable(pat)? {
+                    return Err(Error::new(format!("TypeError: refutable pattern in definition, which could fail to match a value of type {}; use a match expression instead", btype)));
+                }
+                ptype
+            },
+
+            AST::Declare(ref id, _, _, _, _, ref args, _) => {
+                let dtype = self.session.get_type(*id).unwrap();
+                if let Type::Function(ref argtypes, _, _) = dtype {
+                    if let Type::Tuple(ref argtypes) = **argtypes {
+                        for (arg, atype) in args.iter().zip(argtypes.iter()) {
+                            if let Some(ref default) = arg.default {
+                                expect_type(self.session, scope.clone(), Some(atype.clone()), Some(self.check_node(scope.clone(), default, Some(atype.clone()))), Check::Def)?;
+                            }
+                        }
+                    }
+                }
+                dtype
+            },
+
+            AST::Keyword(_, _, _, ref expr) => {
+                self.check_node(scope, expr, expected)
             },
 
             AST::Identifier(ref id, _, ref ident) => {
-                if let Ok(ttype) = self.session.get_type_from_ref(*id) {
+                if let Ok(defid) = self.session.get_ref(*id) {
+                    self.check_deprecated(node, defid);
+                }
+                let narrowed = self.session.get_ref(*id).ok().and_then(|defid| self.narrowed.borrow().iter().rev().find(|(nid, _)| *nid == defid).map(|(_, ntype)| ntype.clone()));
+                if let Some(ntype) = narrowed {
+                    // The type of the reference is recorded so the value can be converted from its nullable representation
+                    self.session.set_type(*id, ntype.clone());
+                    ntype
+                } else if let Ok(ttype) = self.session.get_type_from_ref(*id) {
                     ttype
                 } else {
                     match scope.get_var_def(&ident.name) {
@@ -172,26 +239,35 @@ impl<'sess> TypeChecker<'sess> {
                 }
             },
 
-            AST::Block(_, _, ref body) => self.check_vec(scope, body),
+            AST::Block(ref id, _, ref body) => self.check_vec(self.session.map.get(id), body, expected),
 
-            AST::If(_, _, ref cond, ref texpr, ref fexpr) => {
+            AST::If(ref id, _, ref cond, ref texpr, ref fexpr) => {
                 // TODO should this require the cond type to be Bool?
                 self.check_node(scope.clone(), cond, None);
-                let ttype = self.check_node(scope.clone(), texpr, None);
-                let ftype = self.check_node(scope.clone(), fexpr, Some(ttype.clone()));
-                expect_type(self.session, scope, Some(ttype), Some(ftype), Check::List)?
+                let ttype = self.with_narrowed(self.get_narrowed(cond, true), || self.check_node(scope.clone(), texpr, expected.clone()));
+                let ftype = self.with_narrowed(self.get_narrowed(cond, false), || self.check_node(scope.clone(), fexpr, Some(ttype.clone())));
+                let rtype = expect_type(self.session, scope, Some(ttype), Some(ftype), Check::List)?;
+                self.session.set_type(*id, rtype.clone());
+                rtype
             },
 
-            AST::Match(_, _, ref cond, ref cases) => {
+            AST::Match(ref id, _, ref cond, ref cases) => {
                 let mut ctype = self.check_node(scope.clone(), cond, None);
 
                 let mut rtype = None;
                 for ref case in cases {
                     let lscope = self.session.map.get(&case.id);
                     ctype = expect_type(self.session, lscope.clone(), Some(ctype.clone()), Some(self.check_pattern(lscope.clone(), &case.pat, Some(ctype.clone()))?), Check::List)?;
-                    rtype = Some(expect_type(self.session, lscope.clone(), rtype.clone(), Some(self.check_node(lscope.clone(), &case.body, rtype.clone())), Check::List)?);
+                    if let Some(ref guard) = case.guard {
+                        let btype = scope.make_obj(self.session, String::from("Bool"), vec!())?;
+                        let gtype = self.check_node(lscope.clone(), guard, Some(btype.clone()));
+                        expect_type(self.session, lscope.clone(), Some(btype), Some(gtype), Check::Def)?;
+                    }
+                    rtype = Some(expect_type(self.session, lscope.clone(), rtype.clone(), Some(self.check_node(lscope.clone(), &case.body, rtype.clone().or(expected.clone()))), Check::List)?);
                 }
 
+                CoverageChecker::check_match(self.session, cases)?;
+                self.session.set_type(*id, rtype.clone().unwrap());
                 rtype.unwrap()
             },
 
@@ -203,7 +279,12 @@ impl<'sess> TypeChecker<'sess> {
                 for ref case in cases {
                     let lscope = self.session.map.get(&case.id);
                     ctype = expect_type(self.session, lscope.clone(), Some(ctype.clone()), Some(self.check_pattern(lscope.clone(), &case.pat, Some(ctype.clone()))?), Check::List)?;
-                    rtype = Some(expect_type(self.session, lscope.clone(), rtype.clone(), Some(self.check_node(lscope.clone(), &case.body, rtype.clone())), Check::List)?);
+                    if let Some(ref guard) = case.guard {
+                        let btype = scope.make_obj(self.session, String::from("Bool"), vec!())?;
+                        let gtype = self.check_node(lscope.clone(), guard, Some(btype.clone()));
+                        expect_type(self.session, lscope.clone(), Some(btype), Some(gtype), Check::Def)?;
+                    }
+                    rtype = Some(expect_type(self.session, lscope.clone(), rtype.clone(), Some(self.check_node(lscope.clone(), &case.body, rtype.clone().or(expected.clone()))), Check::List)?);
                 }
 
                 expect_type(self.session, scope.clone(), Some(btype.clone()), rtype.clone(), Check::List)?;
@@ -214,7 +295,7 @@ impl<'sess> TypeChecker<'sess> {
                 // TODO should you check for a special error/exception type?
                 let extype = scope.make_obj(self.session, String::from("Exception"), vec!())?;
                 expect_type(self.session, scope.clone(), Some(extype.clone()), Some(self.check_node(scope.clone(), expr, Some(extype))), Check::Def)?;
-                scope.make_obj(self.session, String::from("()"), vec!())?
+                scope.make_obj(self.session, String::from("Never"), vec!())?
             },
 
             AST::While(_, _, ref cond, ref body) => {
@@ -225,7 +306,12 @@ impl<'sess> TypeChecker<'sess> {
             },
 
             AST::Nil(ref id) => {
-                let ttype = expected.unwrap_or_else(|| scope.new_typevar(self.session, false));
+                // Nil is always nullable, so a non-nullable expected type is only a hint for the checks done by the caller
+                let ttype = match expected.map(|etype| resolve_type(self.session, etype, false)).transpose()? {
+                    Some(etype @ Type::Nullable(_)) => etype,
+                    Some(Type::Variable(_, _, false)) | None => Type::Nullable(r(scope.new_typevar(self.session, false))),
+                    Some(etype) => Type::Nullable(r(etype)),
+                };
                 self.session.set_type(*id, ttype.clone());
                 ttype
             },
@@ -256,29 +342,29 @@ impl<'sess> TypeChecker<'sess> {
             },
 
             AST::Tuple(ref id, _, ref items) => {
-                // TODO would this not be a bug if the expected type was for some reason a variable?
-                //let etypes = match expected {
-                //    Some(ref e) => e.get_types()?.iter().map(|i| Some(i.clone())).collect(),
-                //    None => vec![None; items.len()]
-                //};
-                let etypes = vec![None; items.len()];
-
-                if etypes.len() != items.len() {
-                    return Err(Error::new(format!("TypeError: number of tuple items don't match: expected {:?} with {} items but found {} items", expected, etypes.len(), items.len())));
-                }
+                // The expected item types are only used to check the items, and any mismatch is reported by whatever expected the tuple
+                let etypes = match expected.clone().map(|ttype| resolve_type(self.session, ttype, false)) {
+                    Some(Ok(Type::Tuple(ref etypes))) if etypes.len() == items.len() => etypes.iter().map(|etype| Some(etype.clone())).collect(),
+                    _ => vec![None; items.len()],
+                };
 
                 let mut types = vec!();
-                for (ref expr, etype) in items.iter().zip(etypes.iter()) {
-                    types.push(expect_type(self.session, scope.clone(), etype.clone(), Some(self.check_node(scope.clone(), expr, etype.clone())), Check::List)?);
+                for (ref expr, etype) in items.iter().zip(etypes.into_iter()) {
+                    types.push(self.check_node(scope.clone(), expr, etype));
                 }
                 self.session.set_type(*id, Type::Tuple(types.clone()));
                 Type::Tuple(types)
             },
 
             AST::Record(ref id, _, ref items) => {
+                let etype = expected.clone().map(|ttype| resolve_type(self.session, ttype, false));
                 let mut types = vec!();
                 for (ref ident, ref expr) in items {
-                    types.push((ident.name.clone(), self.check_node(scope.clone(), expr, None)));
+                    let itype = match etype {
+                        Some(Ok(ref etype @ Type::Record(_))) => etype.get_record_field(ident.name.as_str()).ok().cloned(),
+                        _ => None,
+                    };
+                    types.push((ident.name.clone(), self.check_node(scope.clone(), expr, itype)));
                 }
                 self.session.set_type(*id, Type::Record(types.clone()));
                 Type::Record(types)
@@ -296,8 +382,22 @@ impl<'sess> TypeChecker<'sess> {
                 rtype
             },
 
-            AST::Enum(_, _, _, _) |
-            AST::TypeAlias(_, _, _, _) => {
+            AST::Enum(ref id, _, _, _, ref body) => {
+                let tscope = self.session.map.get(id);
+                self.check_vec(tscope.clone(), body, None);
+                scope.make_obj(self.session, String::from("()"), vec!())?
+            },
+
+            AST::Impl(ref id, _, _, _, ref body) => {
+                let tscope = self.session.map.get(id);
+                self.check_vec(tscope.clone(), body, None);
+                self.check_impl_methods(tscope, self.session.get_def(*id)?.as_impl()?)?;
+                scope.make_obj(self.session, String::from("()"), vec!())?
+            },
+
+            AST::Trait(_, _, _, _) |
+            AST::TypeAlias(_, _, _, _) |
+            AST::Macro(_, _, _, _, _, _) => {
                 scope.make_obj(self.session, String::from("()"), vec!())?
             },
 
@@ -310,6 +410,7 @@ impl<'sess> TypeChecker<'sess> {
             },
 
             AST::New(ref id, _, _) => {
+                self.check_deprecated(node, self.session.get_ref(*id)?);
                 let classtype = self.session.get_type(*id).unwrap();
                 let dtype = self.session.get_type_from_ref(*id)?;
                 let tscope = Scope::new_ref(Some(scope.clone()));
@@ -320,15 +421,22 @@ impl<'sess> TypeChecker<'sess> {
 
             AST::Class(ref id, _, _, _, ref body) => {
                 let tscope = self.session.map.get(id);
-                self.check_vec(tscope.clone(), body);
+                self.check_vec(tscope.clone(), body, None);
                 scope.make_obj(self.session, String::from("()"), vec!())?
             },
 
-            AST::Resolver(_, _, _, _, _) |
-            AST::Accessor(_, _, _, _, _) => {
+            AST::Resolver(_, _, _, ref field, _) |
+            AST::Accessor(_, _, _, ref field, _) => {
                 let (refid, defid) = self.get_access_ids(scope.clone(), node)?.unwrap();
+                if let Ok(Def::Overload(_)) = self.session.get_def(defid) {
+                    return Err(Error::new(format!("TypeError: the reference {:?} has no type or has an ambiguous type", field.name)));
+                }
+                self.check_deprecated(node, defid);
                 self.session.set_ref(refid, defid);
-                self.get_type_or_new_typevar(scope, defid, expected)
+                match self.instantiate_enum_variant(scope.clone(), node) {
+                    Some(ttype) => expect_type(self.session, scope, Some(ttype), expected, Check::Def)?,
+                    None => self.get_type_or_new_typevar(scope, defid, expected),
+                }
             },
 
             AST::Assignment(_, _, ref left, ref right, ref ty) => {
@@ -338,6 +446,13 @@ impl<'sess> TypeChecker<'sess> {
                         if *ty == AssignType::Update && !self.session.get_def(defid).map(|d| d.is_mutable()).unwrap_or(false) {
                             return Err(Error::new(format!("MutableError: attempting to assign to an immutable variable")));
                         }
+
+                        // Functions and closures capture local variables by value, so an assignment inside them would be lost
+                        if let AST::Identifier(_, _, ref ident) = **left {
+                            if !scope.contains_context(&ident.name) && !Scope::global(scope.clone()).contains(&ident.name) {
+                                return Err(Error::new(format!("MutableError: the local variable {:?} is captured by value, and can't be assigned from inside a function or closure", ident.name)));
+                            }
+                        }
                     },
                     None => { }
                 }
@@ -347,14 +462,24 @@ impl<'sess> TypeChecker<'sess> {
                 expect_type(self.session, scope, Some(ltype), Some(rtype), Check::Def)?
             },
 
-            AST::Import(_, _, _, ref decls) => {
-                self.check_vec(scope.clone(), decls);
+            AST::Import(ref id, _, _, ref kind, ref decls) => {
+                let mscope = if *kind == ImportKind::All { scope.clone() } else { self.session.map.get(id) };
+                self.check_vec(mscope, decls, None);
+                scope.make_obj(self.session, String::from("()"), vec!())?
+            },
+
+            AST::Module(ref id, _, _, ref body) => {
+                let mscope = self.session.map.get(id);
+                self.check_vec(mscope, body, None);
                 scope.make_obj(self.session, String::from("()"), vec!())?
             },
 
             AST::GetValue(_) |
+            AST::Attributes(_, _, _, _) |
+            AST::Interpolate(_, _, _) |
             AST::List(_, _, _) |
             AST::For(_, _, _, _, _) |
+            AST::Range(_, _, _, _, _, _) |
             AST::Index(_, _, _, _) => { panic!("InternalError: ast element shouldn't appear at this late phase: {:?}", node) }
         };
 
@@ -367,11 +492,19 @@ impl<'sess> TypeChecker<'sess> {
             Pattern::Wild => Ok(expected.unwrap_or_else(|| scope.new_typevar(self.session, false))),
             Pattern::Literal(ref id, ref node) => {
                 let ltype = self.check_node_or_error(scope.clone(), node, expected)?;
-                self.link_comparison_func(scope.clone(), *id, &ltype)?;
+                self.link_comparison_func(scope.clone(), *id, "==", &ltype)?;
                 Ok(ltype)
             },
+            Pattern::Binding(ref id, ref ident) if self.session.get_const_pattern(*id).is_some() => {
+                let refid = self.session.get_const_pattern(*id).unwrap();
+                self.check_pattern(scope, &Pattern::Literal(*id, AST::Identifier(refid, Pos::empty(), ident.clone())), expected)
+            },
             Pattern::Binding(ref id, _) => {
-                let btype = expected.unwrap_or_else(|| scope.new_typevar(self.session, false));
+                let mut btype = expected.unwrap_or_else(|| scope.new_typevar(self.session, false));
+                // A binding in a later alternative of an or-pattern must have the same type as in the first one
+                if let Ok(defid) = self.session.get_ref(*id) {
+                    btype = expect_type(self.session, scope.clone(), self.session.get_type(defid), Some(btype), Check::Def)?;
+                }
                 self.session.update_type(scope.clone(), *id, btype.clone())?;
                 Ok(btype)
             },
@@ -386,11 +519,14 @@ impl<'sess> TypeChecker<'sess> {
                 let vars = self.session.get_def(ltype.get_id()?)?.get_vars()?;
                 let defid = vars.get_var_def(&field.name).ok_or(Error::new(format!("VarError: definition not set for {:?}", field.name)))?;
                 self.session.set_ref(*id, defid);
-                let ttype = self.session.get_type(defid).ok_or(Error::new(format!("TypeError: no type set for id {:?}", defid)))?;
+                let ttype = match self.session.get_def(ltype.get_id()?)? {
+                    Def::Enum(ref enumdef) if enumdef.get_variant_by_id(defid).is_some() => enumdef.instantiate_variant_type(self.session, scope.clone(), defid).unwrap(),
+                    _ => self.session.get_type(defid).ok_or(Error::new(format!("TypeError: no type set for id {:?}", defid)))?,
+                };
                 expect_type(self.session, scope, Some(ttype), expected, Check::Def)
             },
             Pattern::EnumArgs(id, left, args) => {
-                self.check_pattern(scope.clone(), left, None)?;
+                let ltype = self.check_pattern(scope.clone(), left, None)?;
                 let variant_id = self.session.get_ref(left.get_id())?;
                 self.session.set_ref(*id, variant_id);
                 let enumdef = self.session.get_def(self.session.get_ref(variant_id)?)?.as_enum()?;
@@ -414,22 +550,140 @@ impl<'sess> TypeChecker<'sess> {
                             argtypes.push(self.check_pattern(scope.clone(), &arg, Some(ttype.clone()))?);
                         }
 
-                        expect_type(self.session, scope.clone(), Some(rtype), Some(Type::Tuple(argtypes)), Check::Def)?;
-                        Ok(enumdef.deftype.clone())
+                        let rtype = expect_type(self.session, scope.clone(), Some(rtype), Some(Type::Tuple(argtypes)), Check::Def)?;
+                        expect_type(self.session, scope.clone(), Some(ltype.clone()), Some(Type::Function(r(rtype.clone()), r(ctype.clone()), ltype.get_abi()?)), Check::Def)?;
+                        // The concrete types of the arguments are needed to unpack any values stored as type params
+                        self.session.set_type(*id, rtype);
+                        Ok(ctype)
                     },
                 }
             },
-            // TODO finish implementing these, and in transform as well
-            //Pattern::Tuple(id, items) => { },
-            //Pattern::Record(id, items) => { },
-            _ => panic!("Not Implemented: {:?}", pat),
+            Pattern::Tuple(ref id, ref items) => {
+                let etypes = match expected.clone().map(|ttype| resolve_type(self.session, ttype, false)) {
+                    Some(Ok(Type::Tuple(ref etypes))) if etypes.len() == items.len() => etypes.iter().map(|etype| Some(etype.clone())).collect(),
+                    _ => vec![None; items.len()],
+                };
+
+                let mut types = vec!();
+                for (item, etype) in items.iter().zip(etypes.into_iter()) {
+                    types.push(self.check_pattern(scope.clone(), item, etype)?);
+                }
+                let ttype = expect_type(self.session, scope.clone(), expected, Some(Type::Tuple(types)), Check::Def)?;
+                self.session.set_type(*id, ttype.clone());
+                Ok(ttype)
+            },
+            Pattern::Record(ref id, ref items) => {
+                let ttype = match expected.clone().map(|ttype| resolve_type(self.session, ttype, false)) {
+                    // A record pattern only needs to name some of the fields of a known record type
+                    Some(Ok(rtype @ Type::Record(_))) => {
+                        for (ident, item) in items.iter() {
+                            let ftype = rtype.get_record_field(ident.as_str())?.clone();
+                            let itype = self.check_pattern(scope.clone(), item, Some(ftype.clone()))?;
+                            expect_type(self.session, scope.clone(), Some(ftype), Some(itype), Check::Def)?;
+                        }
+                        rtype
+                    },
+                    _ => {
+                        let mut types = vec!();
+                        for (ident, item) in items.iter() {
+                            types.push((ident.name.clone(), self.check_pattern(scope.clone(), item, None)?));
+                        }
+                        expect_type(self.session, scope.clone(), expected, Some(Type::Record(types)), Check::Def)?
+                    },
+                };
+                self.session.set_type(*id, ttype.clone());
+                Ok(ttype)
+            },
+            Pattern::Or(_, ref alts) => {
+                let mut ttype = expected;
+                for alt in alts {
+                    let atype = self.check_pattern(scope.clone(), alt, ttype.clone())?;
+                    ttype = Some(expect_type(self.session, scope.clone(), ttype, Some(atype), Check::Def)?);
+                }
+                Ok(ttype.unwrap())
+            },
+            Pattern::Range(ref id, ref start, ref end, ref inclusive, ref oid) => {
+                let stype = self.check_node_or_error(scope.clone(), start, expected)?;
+                let etype = self.check_node_or_error(scope.clone(), end, Some(stype.clone()))?;
+                let ttype = resolve_type(self.session, expect_type(self.session, scope.clone(), Some(stype), Some(etype), Check::Def)?, false)?;
+                match ttype.get_name()?.as_str() {
+                    "Int" | "Char" | "Real" => { },
+                    _ => return Err(Error::new(format!("TypeError: range patterns can only be used with Int, Char or Real, but found {}", ttype))),
+                }
+                self.link_comparison_func(scope.clone(), *id, ">=", &ttype)?;
+                self.link_comparison_func(scope.clone(), *oid, if *inclusive { "<=" } else { "<" }, &ttype)?;
+                Ok(ttype)
+            },
+            Pattern::List(ref id, ref items, ref rest) => {
+                let ltype = scope.make_obj(self.session, String::from("List"), vec!(scope.new_typevar(self.session, false)))?;
+                let ttype = resolve_type(self.session, expect_type(self.session, scope.clone(), expected, Some(ltype), Check::Def)?, false)?;
+                let itype = ttype.get_params()?[0].clone();
+                for item in items {
+                    self.check_pattern(scope.clone(), item, Some(itype.clone()))?;
+                }
+                if let Some((_, ref rest)) = rest {
+                    self.check_pattern(scope.clone(), rest, Some(ttype.clone()))?;
+                }
+                self.session.set_type(*id, ttype.clone());
+                Ok(ttype)
+            },
+            Pattern::Identifier(_, ref ident) => Err(Error::new(format!("SyntaxError: unexpected identifier {:?} in pattern", ident.name))),
+        }
+    }
+
+    pub fn is_irrefutable(&self, pat: &Pattern) -> Result<bool, Error> {
+        match pat {
+            Pattern::Binding(ref id, _) => Ok(self.session.get_const_pattern(*id).is_none()),
+            Pattern::Wild => Ok(true),
+            Pattern::Literal(_, _) |
+            Pattern::Identifier(_, _) => Ok(false),
+            Pattern::Annotation(_, _, ref pat) => self.is_irrefutable(pat),
+            Pattern::Resolve(ref id, _, _, _) => {
+                // A variant can only fail to match if its enum has other variants
+                let enumdef = self.session.get_def(self.session.get_ref(self.session.get_ref(*id)?)?)?.as_enum()?;
+                let count = enumdef.variants.borrow().len();
+                Ok(count == 1)
+            },
+            Pattern::EnumArgs(_, ref left, ref args) => {
+                let mut result = self.is_irrefutable(left)?;
+                for arg in args {
+                    result = result && self.is_irrefutable(arg)?;
+                }
+                Ok(result)
+            },
+            Pattern::Tuple(_, ref items) => {
+                let mut result = true;
+                for item in items {
+                    result = result && self.is_irrefutable(item)?;
+                }
+                Ok(result)
+            },
+            Pattern::Record(_, ref items) => {
+                let mut result = true;
+                for (_, item) in items {
+                    result = result && self.is_irrefutable(item)?;
+                }
+                Ok(result)
+            },
+            Pattern::Or(_, ref alts) => {
+                let mut result = false;
+                for alt in alts {
+                    result = result || self.is_irrefutable(alt)?;
+                }
+                Ok(result)
+            },
+            Pattern::Range(_, _, _, _, _) => Ok(false),
+            Pattern::List(_, ref items, ref rest) => match rest {
+                Some((_, ref rest)) if items.len() == 0 => self.is_irrefutable(rest),
+                _ => Ok(false),
+            },
         }
     }
 
     #[must_use]
-    pub fn link_comparison_func(&self, scope: ScopeRef, refid: NodeID, ctype: &Type) -> Result<(), Error> {
-        match scope.get_var_def(&String::from("==")) {
-            None => return Err(Error::new(format!("NameError: no \"==\" function defined for type {:?}", ctype))),
+    pub fn link_comparison_func(&self, scope: ScopeRef, refid: NodeID, op: &str, ctype: &Type) -> Result<(), Error> {
+        match scope.get_var_def(&String::from(op)) {
+            None => return Err(Error::new(format!("NameError: no {:?} function defined for type {:?}", op, ctype))),
             Some(defid) => {
                 let (fid, ftype) = self.session_find_variant_id(scope.clone(), defid, &Type::Tuple(vec!(ctype.clone(), ctype.clone())))?;
                 self.session.set_ref(refid, fid);
@@ -449,7 +703,9 @@ impl<'sess> TypeChecker<'sess> {
                 let ltype = self.session.get_type_from_ref(*oid).unwrap();
 
                 let vars = self.session.get_def(ltype.get_id()?)?.get_vars()?;
-                Ok(Some((*id, vars.get_var_def(&field.name).ok_or(Error::new(format!("VarError: definition not set for {:?}", field.name)))?)))
+                let defid = vars.get_var_def(&field.name).ok_or(Error::new(format!("VarError: definition not set for {:?}", field.name)))?;
+                self.check_member_access(scope, &ltype, &field.name, defid)?;
+                Ok(Some((*id, defid)))
             },
             AST::Accessor(ref id, _, ref left, ref field, ref oid) => {
                 let ltype = resolve_type(self.session, self.check_node(scope.clone(), left, None), false)?;
@@ -458,7 +714,15 @@ impl<'sess> TypeChecker<'sess> {
                 match ltype {
                     Type::Object(_, _, _) => {
                         let vars = self.session.get_def(ltype.get_id()?)?.get_vars()?;
-                        Ok(Some((*id, vars.get_var_def(&field.name).ok_or(Error::new(format!("VarError: definition not set for {:?}", field.name)))?)))
+                        let defid = match vars.get_var_def(&field.name) {
+                            Some(defid) => defid,
+                            None => self.find_impl_method(ltype.get_id()?, &field.name).ok_or(Error::new(format!("VarError: definition not set for {:?}", field.name)))?,
+                        };
+                        self.check_member_access(scope, &ltype, &field.name, defid)?;
+                        Ok(Some((*id, defid)))
+                    },
+                    Type::Variable(_, ref vid, _) if self.find_bound_method(*vid, &field.name).is_some() => {
+                        Ok(Some((*id, self.find_bound_method(*vid, &field.name).unwrap())))
                     },
                     Type::Record(ref items) => {
                         let defid = NodeID::generate();
@@ -472,6 +736,7 @@ impl<'sess> TypeChecker<'sess> {
                         self.session.set_type(defid, items[index].clone());
                         Ok(Some((*id, defid)))
                     },
+                    Type::Nullable(_) => Err(Error::new(format!("TypeError: attempting to access {:?} of a value of type {}, which could be nil", field.name, ltype))),
                     _ => Err(Error::new(format!("TypeError: attempting to access within a non-accessible value: {:?}", ltype)))
                 }
             },
@@ -479,19 +744,392 @@ impl<'sess> TypeChecker<'sess> {
         }
     }
 
-    pub fn session_find_variant(&self, scope: ScopeRef, invid: NodeID, fexpr: &AST, argtypes: &Type) -> Result<Type, Error> {
+    pub fn check_member_access(&self, scope: ScopeRef, ltype: &Type, name: &String, defid: NodeID) -> Result<(), Error> {
+        let vis = self.session.get_def(defid).map(|def| def.get_vis()).unwrap_or(Visibility::Public);
+        if vis == Visibility::Public {
+            return Ok(());
+        }
+
+        // Find the class that declared the member, which might be a parent of the object's class
+        let (ownerid, ownername) = match self.session.get_def(ltype.get_id()?)? {
+            Def::Class(ref classdef) => match classdef.find_member_class(self.session, name) {
+                Some(owner) => (owner.id, owner.classname.clone()),
+                None => (classdef.id, classdef.classname.clone()),
+            },
+            _ => (ltype.get_id()?, ltype.get_name()?),
+        };
+
+        // Private members can only be used inside the declaring class, and protected members inside its subclasses as well
+        let current = scope.get_type_def(&String::from("Self"));
+        let allowed = match (vis, current) {
+            (_, Some(id)) if id == ownerid => true,
+            (Visibility::Protected, Some(id)) => self.session.get_def(id)?.as_class().map(|classdef| classdef.is_subclass_of(self.session, ownerid)).unwrap_or(false),
+            _ => false,
+        };
+
+        if !allowed {
+            let kind = if vis == Visibility::Private { "private" } else { "protected" };
+            return Err(Error::new(format!("VisibilityError: {:?} is {} to class {:?}, and can't be used here", name, kind, ownername)));
+        }
+        Ok(())
+    }
+
+    pub fn find_impl_method(&self, typeid: NodeID, name: &String) -> Option<NodeID> {
+        // Only the trait's own methods can be called on the type, and not any other functions defined in the impl
+        self.session.get_impls(typeid).iter().filter_map(|implid| {
+            let impldef = self.session.get_def(*implid).ok()?.as_impl().ok()?;
+            self.session.get_def(impldef.traitid).ok()?.as_trait().ok()?.get_method(name)?;
+            impldef.vars.get_var_def(name)
+        }).next()
+    }
+
+    pub fn find_bound_method(&self, varid: NodeID, name: &String) -> Option<NodeID> {
+        self.session.get_bounds(varid).iter().filter_map(|(traitid, _, _)| Some(self.session.get_def(*traitid).ok()?.as_trait().ok()?.get_method(name)?.1)).next()
+    }
+
+    pub fn check_impl_methods(&self, scope: ScopeRef, impldef: ImplDefRef) -> Result<(), Error> {
+        let traitdef = self.session.get_def(impldef.traitid)?.as_trait()?;
+        for (name, methodid) in traitdef.methods.borrow().iter() {
+            let implid = impldef.vars.get_var_def(name).ok_or(Error::new(format!("TraitError: the impl of {} for {} is missing the method {:?}", traitdef.get_name(), impldef.impltype, name)))?;
+
+            // The trait's method must match the impl's method with Self replaced by the implementing type
+            let mut varmap = Scope::map_new();
+            varmap.insert(traitdef.selfid, impldef.impltype.clone());
+            let ttype = Scope::new_ref(Some(scope.clone())).map_typevars(self.session, &mut varmap, self.session.get_type(*methodid).unwrap());
+            let itype = self.session.get_type(implid).unwrap();
+            if check_type(self.session, scope.clone(), Some(ttype.clone()), Some(itype.clone()), Check::Def, false).is_err() {
+                return Err(Error::new(format!("TraitError: the method {:?} in the impl of {} for {} should have type {} but found {}", name, traitdef.get_name(), impldef.impltype, ttype, itype)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_trait_arg_types(&self, scope: ScopeRef, fid: NodeID, ftype: &Type, atypes: Type) -> Result<Type, Error> {
+        // The trait arguments aren't given by the caller, so their types are only known once the call is unified with the function's type
+        let (params, count) = match self.session.get_def(fid).ok().and_then(|def| def.get_args()) {
+            Some(params) => (params.len(), params.iter().filter(|param| self.session.is_bound_arg(param.id)).count()),
+            None => match resolve_type(self.session, ftype.clone(), false) {
+                Ok(Type::Function(ref argtypes, _, _)) => (argtypes.as_vec().len(), split_bound_types(self.session, argtypes.as_vec()).1.len()),
+                _ => (0, 0),
+            },
+        };
+
+        let mut atypes = atypes.as_vec();
+        if count > 0 && atypes.len() > params - count {
+            return Err(Error::new(format!("TypeError: expected at most {} arguments but found {}", params - count, atypes.len())));
+        }
+        atypes.extend((0..count).map(|_| scope.new_typevar(self.session, false)));
+        Ok(Type::Tuple(atypes))
+    }
+
+    pub fn add_trait_bounds(&self, pos: &Pos, invid: NodeID, ftype: &Type) {
+        // The trait arguments are filled in after type checking, so the types they're used with are checked once they are fully resolved
+        let atypes = ftype.get_argtypes().map(|atypes| atypes.as_vec()).unwrap_or(vec!());
+        let (_, bounds) = split_bound_types(self.session, atypes);
+        if bounds.len() > 0 {
+            self.bounds.borrow_mut().push((pos.clone(), invid, bounds));
+        }
+    }
+
+    pub fn resolve_trait_args(&self) {
+        for (pos, invid, bounds) in self.bounds.borrow().iter() {
+            match bounds.iter().map(|bound| self.create_trait_dict_arg(bound)).collect::<Result<Vec<AST>, Error>>() {
+                Ok(values) => self.session.set_trait_args(*invid, values),
+                Err(err) => self.session.print_error(err.add_pos(pos)),
+            }
+        }
+    }
+
+    pub fn create_trait_dict_arg(&self, ttype: &Type) -> Result<AST, Error> {
+        // A concrete type passes its impl of the trait, and a bound typevar passes on the trait argument it was given
+        let traitdef = self.session.get_def(ttype.get_id()?)?.as_trait()?;
+        let ttype = resolve_type(self.session, ttype.get_params()?[0].clone(), false)?;
+        let (defid, name) = match ttype {
+            Type::Object(_, ref id, _) if traitdef.get_impl(*id).is_some() => (traitdef.get_impl(*id).unwrap(), traitdef.get_name()),
+            Type::Variable(_, ref id, _) => match self.session.get_bounds(*id).into_iter().find(|(traitid, _, _)| *traitid == traitdef.id) {
+                Some((_, argid, name)) => (argid, name),
+                None => return Err(Error::new(format!("TraitError: {} must be bound by the trait {} to be used here", ttype, traitdef.get_name()))),
+            },
+            _ => return Err(Error::new(format!("TraitError: {} does not implement the trait {}", ttype, traitdef.get_name()))),
+        };
+
+        let rid = NodeID::generate();
+        self.session.set_ref(rid, defid);
+        Ok(AST::Identifier(rid, Pos::empty(), Ident::new(name)))
+    }
+
+    pub fn get_expected_arg_types(&self, scope: ScopeRef, fexpr: &AST, args: &Vec<AST>, known: &Vec<Option<Type>>) -> Vec<Option<Type>> {
+        let defid = match fexpr {
+            // The object of a method call is also its first argument, so it's not checked again here
+            AST::Accessor(_, _, _, ref field, _) => {
+                let ltype = match known.get(0).cloned().unwrap_or(None).map(|ltype| resolve_type(self.session, ltype, false)) {
+                    Some(Ok(ltype @ Type::Object(_, _, _))) => ltype,
+                    _ => return vec!(),
+                };
+                let typeid = match ltype.get_id() { Ok(typeid) => typeid, _ => return vec!() };
+                match self.session.get_def(typeid).and_then(|def| def.get_vars()).ok().and_then(|vars| vars.get_var_def(&field.name)) {
+                    Some(defid) => defid,
+                    None => match self.find_impl_method(typeid, &field.name) { Some(defid) => defid, None => return vec!() },
+                }
+            },
+            AST::Identifier(_, _, _) |
+            AST::Resolver(_, _, _, _, _) => match self.get_access_ids(scope.clone(), fexpr) {
+                Ok(Some((_, defid))) => defid,
+                _ => return vec!(),
+            },
+            _ => return vec!(),
+        };
+
+        // An overloaded function only gives expected types if a single variant matches the arguments known so far
+        let fid = match self.session.get_def(defid) {
+            Ok(Def::Overload(ref ol)) => {
+                let tscope = Scope::new_ref(Some(scope.clone()));
+                let atypes = known.iter().map(|atype| atype.clone().unwrap_or_else(|| tscope.new_typevar(self.session, true))).collect();
+                let (mut found, _) = ol.find_all_variants(self.session, tscope, Type::Tuple(atypes));
+                if found.len() != 1 {
+                    return vec!();
+                }
+                found.remove(0).0
+            },
+            _ => defid,
+        };
+        let params = self.session.get_def(fid).ok().and_then(|def| def.get_caller_args(self.session)).unwrap_or(vec!());
+        let ptypes = match self.session.get_type(fid).map(|ftype| resolve_type(self.session, ftype, false)) {
+            Some(Ok(Type::Function(ref ptypes, _, _))) => ptypes.as_vec(),
+            _ => return vec!(),
+        };
+
+        // Find which parameter each argument is given for, and use the known arguments to fill in the parameters' type variables.  This
+        // doesn't unify anything, so the call itself is still checked as usual afterwards
+        let indices = match arrange_args(&params, args, (0..args.len()).map(|i| Some(i)).collect(), |_| None) {
+            Ok(indices) => indices,
+            Err(_) => return vec!(),
+        };
+        let mut varmap = HashMap::new();
+        for (ptype, index) in ptypes.iter().zip(indices.iter()) {
+            if let Some(Some(ref atype)) = index.map(|i| known[i].clone()) {
+                if let Ok(atype) = resolve_type(self.session, atype.clone(), false) {
+                    match_typevars(&mut varmap, ptype, &atype);
+                }
+            }
+        }
+
+        let mut etypes = vec![None; args.len()];
+        for (ptype, index) in ptypes.iter().zip(indices.iter()) {
+            if let Some(i) = *index {
+                // Function types can still be partly known, since a closure only takes the types of its arguments from them
+                etypes[i] = match replace_typevars(&varmap, ptype.clone()) {
+                    _ if known[i].is_some() => None,
+                    ftype @ Type::Function(_, _, _) => Some(ftype),
+                    ttype => if has_universal_typevars(&ttype) { None } else { Some(ttype) },
+                };
+            }
+        }
+        etypes
+    }
+
+    pub fn is_overloaded(&self, fexpr: &AST) -> bool {
+        match fexpr {
+            AST::Identifier(ref id, _, _) => match self.session.get_def_from_ref(*id) {
+                Ok(Def::Overload(_)) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    pub fn has_unknown_types(&self, atypes: &Vec<Type>) -> bool {
+        atypes.iter().any(|atype| match resolve_type(self.session, atype.clone(), false) {
+            Ok(Type::Variable(_, _, _)) => true,
+            _ => false,
+        })
+    }
+
+    pub fn with_narrowed<F, R>(&self, narrowed: Vec<(NodeID, Type)>, f: F) -> R where F: FnOnce() -> R {
+        let len = self.narrowed.borrow().len();
+        self.narrowed.borrow_mut().extend(narrowed);
+        let ret = f();
+        self.narrowed.borrow_mut().truncate(len);
+        ret
+    }
+
+    // Returns the immutable variables that can't be nil when the condition evaluates to the given value
+    pub fn get_narrowed(&self, cond: &AST, value: bool) -> Vec<(NodeID, Type)> {
+        match *cond {
+            AST::SideEffect(_, _, ref ident, ref args) if (ident.as_str() == "and") == value => {
+                args.iter().flat_map(|arg| self.get_narrowed(arg, value)).collect()
+            },
+            _ => match cond.get_nil_comparison() {
+                Some((equal, AST::Identifier(ref id, _, _), _)) if equal != value => {
+                    let defid = match self.session.get_ref(*id) {
+                        Ok(defid) => defid,
+                        Err(_) => return vec!(),
+                    };
+                    let mutable = self.session.get_def(defid).map(|def| def.is_mutable()).unwrap_or(true);
+                    match self.session.get_type(defid).map(|ttype| resolve_type(self.session, ttype, false)) {
+                        Some(Ok(Type::Nullable(ttype))) if !mutable => vec!((defid, *ttype)),
+                        _ => vec!(),
+                    }
+                },
+                _ => vec!(),
+            },
+        }
+    }
+
+    pub fn check_deferred(&self) {
+        // Overloaded calls whose variant depended on unknown argument types have their variant chosen again, now that the rest of the code has been checked
+        // The arguments were already checked, so only the argument types found at the time are used
+        self.can_defer.set(false);
+        let deferred = self.deferred.replace(vec!());
+        for (scope, node, atypes, rtype) in deferred {
+            let result = self.check_invoke_variant(scope.clone(), &node, atypes, Some(rtype.clone()))
+                .and_then(|ttype| expect_type(self.session, scope, Some(rtype), Some(ttype), Check::Def));
+            if let Err(err) = result {
+                self.session.print_error(err.add_pos(&node.get_pos()));
+            }
+        }
+    }
+
+    pub fn check_invoke_variant(&self, scope: ScopeRef, node: &AST, atypes: Vec<Type>, expected: Option<Type>) -> Result<Type, Error> {
+        let (id, pos, fexpr, args) = match *node {
+            AST::Invoke(ref id, ref pos, ref fexpr, _, ref args) => (*id, pos, fexpr.as_ref(), args),
+            _ => panic!("InternalError: expected an invoke node: {:?}", node),
+        };
+
+        let tscope = Scope::new_ref(Some(scope.clone()));
+        let (dtype, atypes) = match self.session_find_variant(tscope.clone(), id, fexpr, args, atypes.clone()) {
+            Ok(result) => result,
+            Err(_) if self.can_defer.get() && self.is_overloaded(fexpr) && self.has_unknown_types(&atypes) => {
+                // The variant can't be chosen until more is known about the arguments, so it's chosen again after everything else
+                let rtype = scope.new_typevar(self.session, false);
+                self.deferred.borrow_mut().push((scope.clone(), node.clone(), atypes, rtype.clone()));
+                return Ok(rtype);
+            },
+            Err(err) => return Err(err),
+        };
+
+        debug!("INVOKE TYPE: {:?} has type {:?}", id, dtype);
+        let etype = match dtype {
+            // A function that isn't known yet has no declared type params for the type arguments to replace
+            Type::Variable(_, _, _) if self.session.get_type_params(id).is_some() => return Err(Error::new(format!("TypeError: type arguments can't be given to a function whose type isn't known"))),
+            Type::Variable(_, _, _) => dtype.clone(),
+            _ => {
+                let mut varmap = self.get_type_args(id, fexpr)?;
+                tscope.map_typevars(self.session, &mut varmap, dtype.clone())
+            },
+        };
+
+        let ftype = match etype {
+            Type::Function(_, _, ref abi) => {
+                //let ftype = expect_type(self.session, tscope.clone(), Some(etype.clone()), Some(Type::Function(r(atypes), r(expected.unwrap_or_else(|| tscope.new_typevar(self.session, false))), abi)), Check::Update)?;
+                let ftype = expect_type(self.session, tscope.clone(), Some(etype.clone()), Some(Type::Function(r(atypes), r(etype.get_rettype()?.clone()), *abi)), Check::Def)?;
+                // TODO should this actually be another expect, so type resolutions that occur in later args affect earlier args?  Might not be needed unless you add typevar constraints
+                let ftype = resolve_type(self.session, ftype, false)?;        // NOTE This ensures the early arguments are resolved despite typevars not being assigned until later in the signature
+
+                ftype
+            },
+            Type::Variable(_, ref vid, _) => {
+                let ftype = Type::Function(r(atypes), r(expected.unwrap_or_else(|| tscope.new_typevar(self.session, false))), ABI::Unknown);
+                self.session.update_type(tscope, *vid, ftype.clone())?;
+                ftype
+            },
+            _ => return Err(Error::new(format!("NotAFunction: {:?}", fexpr))),
+        };
+
+        self.session.update_type(scope.clone(), id, ftype.clone())?;
+        self.add_trait_bounds(pos, id, &ftype);
+        self.check_default_args(id, args, &ftype)?;
+        Ok(ftype.get_rettype()?.clone())
+    }
+
+    pub fn check_default_args(&self, invid: NodeID, args: &Vec<AST>, ftype: &Type) -> Result<(), Error> {
+        // Each call gets its own copy of the default values of the omitted arguments, checked against the argument types of this call
+        let params = self.session.get_def_from_ref(invid).ok().and_then(|def| def.get_caller_args(self.session)).unwrap_or(vec!());
+        if params.iter().all(|param| param.default.is_none()) {
+            return Ok(());
+        }
+
+        let indices = arrange_args(&params, args, (0..args.len()).map(|i| Some(i)).collect(), |_| None)?;
+        let atypes = ftype.get_argtypes()?.as_vec();
+        for ((param, index), atype) in params.iter().zip(indices.iter()).zip(atypes.into_iter()) {
+            match (index, param.default.as_ref()) {
+                (None, Some(default)) => {
+                    let dscope = self.session.map.get(&param.id);
+                    let value = macros::copy_node(default.clone());
+                    NameBinder::bind_default_copy(self.session, dscope.clone(), &value)?;
+                    let vtype = self.check_node_or_error(dscope.clone(), &value, Some(atype.clone()))?;
+                    expect_type(self.session, dscope, Some(atype), Some(vtype), Check::Def)?;
+                    self.session.set_default_arg(invid, param.id, value);
+                },
+                _ => { },
+            }
+        }
+        Ok(())
+    }
+
+    pub fn session_find_variant(&self, scope: ScopeRef, invid: NodeID, fexpr: &AST, args: &Vec<AST>, atypes: Vec<Type>) -> Result<(Type, Type), Error> {
         let (refid, defid) = match self.get_access_ids(scope.clone(), fexpr)? {
             Some(ids) => ids,
-            None => { return self.check_node_or_error(scope.clone(), fexpr, None); },
+            None => {
+                let atypes = arrange_args(&vec!(), args, atypes, |_| scope.new_typevar(self.session, false))?;
+                return Ok((self.check_node_or_error(scope.clone(), fexpr, None)?, Type::Tuple(atypes)));
+            },
         };
 
+        // Keyword arguments and omitted arguments are matched against the declared arguments of each variant
+        let has_keywords = args.iter().any(|arg| arg.is_keyword());
+        let (fid, ftype, atypes) = match self.session.get_def(defid) {
+            Ok(Def::Overload(ref ol)) => {
+                let plain = Type::Tuple(atypes.clone());
+                match ol.find_variant(self.session, scope.clone(), plain.clone()) {
+                    Ok((fid, ftype)) if !has_keywords => (fid, ftype, plain),
+                    result => {
+                        let mut found = vec!();
+                        for vid in ol.get_variants(self.session) {
+                            let params = self.session.get_def(vid).ok().and_then(|def| def.get_caller_args(self.session)).unwrap_or(vec!());
+                            if let Ok(arranged) = arrange_args(&params, args, atypes.clone(), |_| scope.new_typevar(self.session, false)) {
+                                let arranged = Type::Tuple(arranged);
+                                let (mut variants, _) = ol.find_variants_of(vec!(vid), self.session, scope.clone(), arranged.clone());
+                                if variants.len() > 0 {
+                                    let (fid, ftype) = variants.remove(0);
+                                    found.push((fid, ftype, arranged));
+                                }
+                            }
+                        }
 
-        let (fid, ftype) = self.session_find_variant_id(scope.clone(), defid, argtypes)?;
+                        match found.len() {
+                            1 => found.remove(0),
+                            0 => return Err(match result {
+                                Err(err) => err,
+                                Ok(_) => Error::new(format!("OverloadError: No valid variant found for {}", plain)),
+                            }),
+                            _ => return Err(Error::new(format!("OverloadError: Ambiguous {}\n\tvariants found [{}]", plain, found.iter().map(|(_, t, _)| format!("{}", t)).collect::<Vec<String>>().join(", ")))),
+                        }
+                    },
+                }
+            },
+            def => {
+                let params = def.ok().and_then(|def| def.get_caller_args(self.session)).unwrap_or(vec!());
+                let atypes = arrange_args(&params, args, atypes, |_| scope.new_typevar(self.session, false))?;
+                (defid, self.get_type_or_new_typevar(scope.clone(), defid, None), Type::Tuple(atypes))
+            },
+        };
+
+        // An overloaded member's visibility depends on which of its variants was chosen
+        if let Ok(Def::Overload(_)) = self.session.get_def(defid) {
+            match *fexpr {
+                AST::Resolver(_, _, _, ref field, ref oid) => self.check_member_access(scope.clone(), &self.session.get_type_from_ref(*oid)?, &field.name, fid)?,
+                AST::Accessor(_, _, _, ref field, ref oid) => self.check_member_access(scope.clone(), &self.session.get_type(*oid).unwrap(), &field.name, fid)?,
+                _ => { },
+            }
+        }
+
+        let atypes = self.add_trait_arg_types(scope.clone(), fid, &ftype, atypes)?;
         self.session.set_ref(refid, fid);
         self.session.set_ref(invid, fid);
-
+        self.check_deprecated(fexpr, fid);
         debug!("CHECK VARIANT: {:?} {:?}", ftype, fexpr);
-        Ok(ftype)
+        Ok((ftype, atypes))
     }
 
     pub fn session_find_variant_id(&self, scope: ScopeRef, defid: NodeID, argtypes: &Type) -> Result<(NodeID, Type), Error> {
@@ -501,6 +1139,54 @@ impl<'sess> TypeChecker<'sess> {
         }
     }
 
+    pub fn get_type_args(&self, invid: NodeID, fexpr: &AST) -> Result<HashMap<UniqueID, Type>, Error> {
+        // Types given at the call site replace the function's declared type params, instead of new typevars
+        let mut varmap = Scope::map_new();
+        if let Some(types) = self.session.get_type_params(invid) {
+            let name = match *fexpr {
+                AST::Identifier(_, _, ref ident) |
+                AST::Resolver(_, _, _, ref ident, _) |
+                AST::Accessor(_, _, _, ref ident, _) => ident.name.clone(),
+                _ => String::from("function"),
+            };
+            let params = self.session.get_ref(invid).ok().and_then(|fid| self.session.get_type_params(fid)).unwrap_or(vec!());
+            if params.len() == 0 {
+                return Err(Error::new(format!("TypeError: type arguments were given for {}, which doesn't declare any type parameters", name)));
+            } else if params.len() != types.len() {
+                return Err(Error::new(format!("TypeError: expected {} type arguments for {} but found {}", params.len(), name, types.len())));
+            }
+
+            for (param, ttype) in params.iter().zip(types.into_iter()) {
+                varmap.insert(param.get_id()?, ttype);
+            }
+        }
+        Ok(varmap)
+    }
+
+    pub fn check_deprecated(&self, node: &AST, defid: NodeID) {
+        if let Some(attr) = self.session.get_attribute(defid, "deprecated") {
+            let name = match *node {
+                AST::Identifier(_, _, ref ident) |
+                AST::Resolver(_, _, _, ref ident, _) |
+                AST::Accessor(_, _, _, ref ident, _) => ident.name.clone(),
+                AST::New(_, _, ref classspec) => classspec.ident.name.clone(),
+                _ => String::from("definition"),
+            };
+            let reason = attr.get_string_arg(0).map(|msg| format!(": {}", msg)).unwrap_or(String::new());
+            self.session.print_warning(Error::new_pos(&node.get_pos(), format!("Warning: {:?} is deprecated{}", name, reason)));
+        }
+    }
+
+    pub fn instantiate_enum_variant(&self, scope: ScopeRef, node: &AST) -> Option<Type> {
+        match node {
+            AST::Resolver(ref id, _, _, _, ref oid) => {
+                let enumdef = self.session.get_def_from_ref(*oid).and_then(|def| def.as_enum()).ok()?;
+                enumdef.instantiate_variant_type(self.session, scope, self.session.get_ref(*id).ok()?)
+            },
+            _ => None,
+        }
+    }
+
     pub fn get_type_or_new_typevar(&self, scope: ScopeRef, defid: NodeID, expected: Option<Type>) -> Type {
         match self.session.get_type(defid) {
             Some(ttype) => ttype,
@@ -513,4 +1199,62 @@ impl<'sess> TypeChecker<'sess> {
     }
 }
 
+fn needs_expected_type(node: &AST) -> bool {
+    match node {
+        AST::Function(_, _, _, None, _, _, _, _, _) |
+        AST::Nil(_) => true,
+        AST::Keyword(_, _, _, ref expr) => needs_expected_type(expr),
+        _ => false,
+    }
+}
+
+fn has_universal_typevars(ttype: &Type) -> bool {
+    match ttype {
+        Type::Variable(_, _, universal) => *universal,
+        Type::Object(_, _, types) |
+        Type::Tuple(types) |
+        Type::Ambiguous(types) => types.iter().any(has_universal_typevars),
+        Type::Record(types) => types.iter().any(|(_, ttype)| has_universal_typevars(ttype)),
+        Type::Ref(ttype) |
+        Type::Nullable(ttype) => has_universal_typevars(ttype),
+        Type::Function(args, ret, _) => has_universal_typevars(args) || has_universal_typevars(ret),
+    }
+}
+
+pub fn match_typevars(varmap: &mut HashMap<UniqueID, Type>, ptype: &Type, atype: &Type) {
+    match (ptype, atype) {
+        (Type::Variable(_, id, true), _) => { varmap.entry(*id).or_insert(atype.clone()); },
+        (Type::Object(_, _, ptypes), Type::Object(_, _, atypes)) |
+        (Type::Tuple(ptypes), Type::Tuple(atypes)) => {
+            for (ptype, atype) in ptypes.iter().zip(atypes.iter()) {
+                match_typevars(varmap, ptype, atype);
+            }
+        },
+        (Type::Record(ptypes), Type::Record(atypes)) => {
+            for ((_, ptype), (_, atype)) in ptypes.iter().zip(atypes.iter()) {
+                match_typevars(varmap, ptype, atype);
+            }
+        },
+        (Type::Ref(ptype), Type::Ref(atype)) |
+        (Type::Nullable(ptype), Type::Nullable(atype)) => match_typevars(varmap, ptype, atype),
+        (Type::Nullable(ptype), _) => match_typevars(varmap, ptype, atype),
+        (Type::Function(pargs, pret, _), Type::Function(aargs, aret, _)) => {
+            match_typevars(varmap, pargs, aargs);
+            match_typevars(varmap, pret, aret);
+        },
+        _ => { },
+    }
+}
 
+fn replace_typevars(varmap: &HashMap<UniqueID, Type>, ttype: Type) -> Type {
+    match ttype {
+        Type::Variable(_, ref id, true) if varmap.contains_key(id) => varmap[id].clone(),
+        Type::Object(name, id, types) => Type::Object(name, id, types.into_iter().map(|ttype| replace_typevars(varmap, ttype)).collect()),
+        Type::Tuple(types) => Type::Tuple(types.into_iter().map(|ttype| replace_typevars(varmap, ttype)).collect()),
+        Type::Record(types) => Type::Record(types.into_iter().map(|(name, ttype)| (name, replace_typevars(varmap, ttype))).collect()),
+        Type::Ref(ttype) => Type::Ref(r(replace_typevars(varmap, *ttype))),
+        Type::Nullable(ttype) => Type::Nullable(r(replace_typevars(varmap, *ttype))),
+        Type::Function(args, ret, abi) => Type::Function(r(replace_typevars(varmap, *args)), r(replace_typevars(varmap, *ret)), abi),
+        ttype => ttype,
+    }
+}
@@ -16,6 +16,7 @@ pub enum Type {
     Tuple(Vec<Type>),
     Record(Vec<(String, Type)>),
     Ref(R<Type>),
+    Nullable(R<Type>),
     Function(R<Type>, R<Type>, ABI),
 
     Variable(String, UniqueID, bool),
@@ -55,6 +56,14 @@ impl Type {
         }
     }
 
+    pub fn get_item_types(&self) -> Vec<Type> {
+        match self {
+            &Type::Tuple(ref types) => types.clone(),
+            &Type::Record(ref types) => types.iter().map(|(_, ttype)| ttype.clone()).collect(),
+            _ => vec!(),
+        }
+    }
+
 
     pub fn is_record(&self) -> bool {
         match *self {
@@ -129,6 +138,20 @@ impl Type {
         }
     }
 
+    pub fn is_never(&self) -> bool {
+        match *self {
+            Type::Object(ref name, _, ref types) => name.as_str() == "Never" && types.len() == 0,
+            _ => false
+        }
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        match *self {
+            Type::Nullable(_) => true,
+            _ => false
+        }
+    }
+
     pub fn get_varname(&self) -> Result<String, Error> {
         match self {
             &Type::Variable(ref name, _, _) => Ok(name.clone()),
@@ -219,6 +242,9 @@ impl fmt::Display for Type {
             Type::Ref(ref ttype) => {
                 write!(f, "ref {}", ttype)
             }
+            Type::Nullable(ref ttype) => {
+                write!(f, "{}?", ttype)
+            }
             Type::Ambiguous(ref variants) => {
                 let varstr: Vec<String> = variants.iter().map(|v| format!("{}", v)).collect();
                 write!(f, "Ambiguous[{}]", varstr.join(", "))
@@ -263,6 +289,20 @@ pub fn check_type(session: &Session, scope: ScopeRef, odtype: Option<Type>, octy
         let ctype = resolve_type(session, octype.unwrap(), false)?;
 
         debug!("CHECK TYPE {:?} {:?} {:?}", dtype, ctype, update);
+        // An expression that never returns a value can be used in place of any type, but it's still
+        // the type of an inferred variable that nothing else is known about, such as the return type of a function that only raises.
+        // Nothing but Never fits where Never is expected, except when joining branches, where an earlier branch that raises gives way to the next
+        match (&dtype, &ctype) {
+            (_, _) if dtype.is_never() && mode == Check::List => return Ok(ctype),
+            (&Type::Variable(_, _, false), _) => { },
+            (_, _) if ctype.is_never() => return Ok(dtype),
+            // A type variable could be substituted with a non-nullable type, so it can't hold a nullable value except when joining branches
+            (&Type::Variable(_, _, true), &Type::Nullable(_)) if mode != Check::List => {
+                return Err(Error::new(format!("TypeError: type mismatch, expected {} but found {}", dtype, ctype)));
+            },
+            _ => { },
+        }
+
         if let Type::Variable(ref _dname, ref did, dex) = dtype {
 
             if let Type::Variable(ref cname, ref cid, cex) = ctype {
@@ -329,6 +369,19 @@ pub fn check_type(session: &Session, scope: ScopeRef, odtype: Option<Type>, octy
                     let ttype = check_type(session, scope.clone(), Some(*atype.clone()), Some(*btype.clone()), mode, update)?;
                     Ok(Type::Ref(r(ttype)))
                 },
+                (Type::Nullable(ref atype), Type::Nullable(ref btype)) => {
+                    let ttype = check_type(session, scope.clone(), Some(*atype.clone()), Some(*btype.clone()), mode, update)?;
+                    Ok(Type::Nullable(r(ttype)))
+                },
+                // A non-nullable value can always be used as a nullable one, but the reverse only when joining branches
+                (Type::Nullable(ref atype), _) => {
+                    let ttype = check_type(session, scope.clone(), Some(*atype.clone()), Some(ctype.clone()), mode, update)?;
+                    Ok(Type::Nullable(r(ttype)))
+                },
+                (_, Type::Nullable(ref btype)) if mode == Check::List => {
+                    let ttype = check_type(session, scope.clone(), Some(dtype.clone()), Some(*btype.clone()), mode, update)?;
+                    Ok(Type::Nullable(r(ttype)))
+                },
                 (_, Type::Ambiguous(_)) |
                 (Type::Ambiguous(_), _) => Err(Error::new(format!("TypeError: overloaded types are not allowed here..."))),
                 _ => {
@@ -353,6 +406,20 @@ fn is_subclass_of(session: &Session, scope: ScopeRef, adef: (&String, UniqueID,
 
     loop {
         let mut class = session.get_type(adef.1).unwrap();
+        // A type's own params are only mapped through the scope when they're defined in it (eg. inside a class body).  Otherwise,
+        // such as for enum variants, trait bounds, or a class used outside of its body, they are mapped directly to the given params
+        // so that checking them doesn't update the definition's own typevars
+        let params = class.get_params()?;
+        if params.len() == adef.2.len() {
+            for (param, ptype) in params.iter().zip(adef.2.iter()) {
+                if let Type::Variable(ref name, ref vid, _) = *param {
+                    match tscope.find_type(session, name) {
+                        Some(Type::Variable(_, ref eid, _)) if eid == vid => { },
+                        _ => { names.entry(*vid).or_insert(ptype.clone()); },
+                    }
+                }
+            }
+        }
         class = tscope.map_typevars(session, &mut names, class);
         adef.2 = check_type_params(session, tscope.clone(), &class.get_params()?, &adef.2, mode, true)?;
 
@@ -442,6 +509,12 @@ pub fn resolve_type(session: &Session, ttype: Type, require_resolve: bool) -> Re
         Type::Ref(ref ttype) => {
             Ok(Type::Ref(r(resolve_type(session, *ttype.clone(), require_resolve)?)))
         },
+        Type::Nullable(ref ttype) => {
+            match resolve_type(session, *ttype.clone(), require_resolve)? {
+                ttype @ Type::Nullable(_) => Ok(ttype),
+                ttype => Ok(Type::Nullable(r(ttype))),
+            }
+        },
         Type::Ambiguous(ref variants) => {
             let newvars = variants.iter().map(|variant| resolve_type(session, variant.clone(), require_resolve)).collect::<Result<Vec<Type>, Error>>()?;
             Ok(Type::Ambiguous(newvars))
@@ -82,6 +82,7 @@ class Test (object):
         self.expected_out = []
         self.expected_err = []
         self.expected_dec = []
+        self.expected_warn = []
         self.load_expected()
 
     def load_expected(self):
@@ -96,6 +97,8 @@ class Test (object):
                     self.expected_err.append(line[7:].rstrip())
                 elif line.startswith("//!dec "):
                     self.expected_dec.append(line[7:].rstrip())
+                elif line.startswith("//!warn "):
+                    self.expected_warn.append(line[8:].rstrip())
 
     def load_dec(self):
         self.dec_output = b""
@@ -129,7 +132,11 @@ class Test (object):
             print(out, " " * (64 - len(out)), exp)
 
     def check_result(self, retcode, stdout, stderr):
-        if self.expected_ret and retcode == 0 and self.compare_lines('expected_out', stdout) and self.compare_lines('expected_dec', self.dec_output):
+        # Warnings are printed to stderr along with the output of the build, so only the warning lines are compared
+        warnings = b"\n".join(line for line in stderr.split(b"\n") if b"Warning:" in line)
+        if not self.compare_lines('expected_warn', warnings):
+            return False
+        elif self.expected_ret and retcode == 0 and self.compare_lines('expected_out', stdout) and self.compare_lines('expected_dec', self.dec_output):
             return True
         elif not self.expected_ret and retcode != 0 and self.compare_lines('expected_err', stdout):
             return True